@@ -8,16 +8,20 @@ extern crate alloc;
 use alloc::vec::Vec;
 use core::ffi::{c_char, CStr};
 use core::slice;
-use dla_driver::layers::{conv2d, conv2d_bias, conv2d_bias_relu, conv2d_relu, grouped_conv2d};
-use dla_driver::tensor3::{Order3, Tensor3};
-use dla_driver::tensor4::{Order4, Tensor4};
-use dla_driver::utils::optimal_pp_bias_heuristic;
-use dla_driver::{Padding, Stride};
+use dla_driver::layers::{conv2d_bias_i32, conv2d_view, grouped_conv2d, try_conv2d_into};
+use dla_driver::quant::{requantize, QuantizedMultiplier, Requantization, Rounding};
+use dla_driver::tensor3::{Order3, Tensor3, Tensor3View};
+use dla_driver::tensor4::{Order4, Tensor4View};
+use dla_driver::utils::{
+    calculate_bias_shift, calculate_conv2d_out_param_dim, optimal_pp_bias_heuristic,
+    tvm_bias_pp_clip,
+};
+use dla_driver::{Dla, DlaError, Padding, Stride};
 use headsail_bsp::init_heap;
 
-/// Converts C-types to DLA Tensors for use with the highlevel layer
+/// Borrows C-arrays as DLA tensor views for use with the highlevel layer, without copying them
 #[allow(clippy::too_many_arguments)]
-unsafe fn ffi_data_import(
+unsafe fn ffi_data_import<'a>(
     input_data: *const i8,
     input_channels: usize,
     input_height: usize,
@@ -29,14 +33,13 @@ unsafe fn ffi_data_import(
     kernel_height: usize,
     kernel_width: usize,
     kernel_order: *const c_char,
-) -> (Tensor3<i8>, Tensor4<i8>) {
-    let input_data: Vec<i8> = unsafe {
-        slice::from_raw_parts(input_data, input_channels * input_height * input_width).to_vec()
-    };
+) -> (Tensor3View<'a, i8>, Tensor4View<'a, i8>) {
+    let input_data: &[i8] =
+        unsafe { slice::from_raw_parts(input_data, input_channels * input_height * input_width) };
 
     let input_order_string = unsafe { CStr::from_ptr(input_order).to_str().unwrap_unchecked() };
     let input_tensor = unsafe {
-        Tensor3::from_data_buffer(
+        Tensor3View::from_slice(
             input_channels,
             input_height,
             input_width,
@@ -46,17 +49,16 @@ unsafe fn ffi_data_import(
         .unwrap_unchecked()
     };
 
-    let kernels_data: Vec<i8> = unsafe {
+    let kernels_data: &[i8] = unsafe {
         slice::from_raw_parts(
             kernel_data,
             kernel_amount * kernel_channels * kernel_height * kernel_width,
         )
-        .to_vec()
     };
 
     let kernel_order_string = unsafe { CStr::from_ptr(kernel_order).to_str().unwrap_unchecked() };
     let kernels_tensor = unsafe {
-        Tensor4::from_data_buffer(
+        Tensor4View::from_slice(
             kernel_amount,
             kernel_channels,
             kernel_height,
@@ -70,6 +72,51 @@ unsafe fn ffi_data_import(
     (input_tensor, kernels_tensor)
 }
 
+/// Borrows the C-array a Conv2D layer writes its output to
+///
+/// The layer is validated before its output size is calculated, so that invalid layers are
+/// reported instead of borrowing a wrapped-around length.
+unsafe fn ffi_output_import<'a, T>(
+    output: *mut T,
+    input: &Tensor3View<i8>,
+    kernels: &Tensor4View<i8>,
+    padding: &Padding,
+    stride: &Stride,
+) -> Result<&'a mut [T], DlaError> {
+    if stride.x == 0 || stride.y == 0 {
+        return Err(DlaError::InvalidDimension);
+    }
+    if (input.width() as u32 + padding.left + padding.right) < kernels.width() as u32
+        || (input.height() as u32 + padding.top + padding.bottom) < kernels.height() as u32
+    {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let (output_width, output_height) = calculate_conv2d_out_param_dim(
+        (input.width() as u32, input.height() as u32),
+        (kernels.width() as u32, kernels.height() as u32),
+        Some(padding.clone()),
+        Some(stride.clone()),
+    );
+    Ok(unsafe {
+        slice::from_raw_parts_mut(output, kernels.kernels() * output_height * output_width)
+    })
+}
+
+/// Converts C-arrays of fixed-point multipliers and shifts to requantization scales
+unsafe fn ffi_multipliers_import(
+    multipliers: *const i32,
+    shifts: *const i32,
+    length: usize,
+) -> Vec<QuantizedMultiplier> {
+    let multipliers = unsafe { slice::from_raw_parts(multipliers, length) };
+    let shifts = unsafe { slice::from_raw_parts(shifts, length) };
+    multipliers
+        .iter()
+        .zip(shifts)
+        .map(|(&multiplier, &shift)| QuantizedMultiplier::new(multiplier, shift))
+        .collect()
+}
+
 /// Initializes DLA by setting up necessary heap allocator from headsail-bsp. This should be called only once in the program.
 #[no_mangle]
 pub unsafe extern "C" fn dla_init() {
@@ -119,27 +166,35 @@ pub unsafe extern "C" fn dla_conv2d(
         )
     };
 
-    let result: Tensor3<i8> = conv2d(
+    let padding = Padding {
+        top: pad_top,
+        right: pad_right,
+        left: pad_left,
+        bottom: pad_bottom,
+        padding_value: pad_value,
+    };
+    let stride = Stride {
+        x: stride_x,
+        y: stride_y,
+    };
+    let output =
+        unsafe { ffi_output_import(output, &input_tensor, &kernels_tensor, &padding, &stride) }
+            .unwrap();
+    try_conv2d_into(
+        &Dla::new(),
         input_tensor,
         kernels_tensor,
-        Some(Padding {
-            top: pad_top,
-            right: pad_right,
-            left: pad_left,
-            bottom: pad_bottom,
-            padding_value: pad_value,
-        }),
-        Some(Stride {
-            x: stride_x,
-            y: stride_y,
-        }),
+        None,
+        false,
+        Some(padding),
+        Some(stride),
+        None,
         Some(mac_clip),
         Some(pp_clip),
         None,
-    );
-    unsafe {
-        core::ptr::copy_nonoverlapping(result.to_buffer().as_mut_ptr(), output, result.get_size())
-    };
+        output,
+    )
+    .unwrap();
 }
 
 /// Executes Conv2D + ReLU on DLA with given parameters and writes result to output buffer.
@@ -184,27 +239,35 @@ pub unsafe extern "C" fn dla_conv2d_relu(
         )
     };
 
-    let result: Tensor3<i8> = conv2d_relu(
+    let padding = Padding {
+        top: pad_top,
+        right: pad_right,
+        left: pad_left,
+        bottom: pad_bottom,
+        padding_value: pad_value,
+    };
+    let stride = Stride {
+        x: stride_x,
+        y: stride_y,
+    };
+    let output =
+        unsafe { ffi_output_import(output, &input_tensor, &kernels_tensor, &padding, &stride) }
+            .unwrap();
+    try_conv2d_into(
+        &Dla::new(),
         input_tensor,
         kernels_tensor,
-        Some(Padding {
-            top: pad_top,
-            right: pad_right,
-            left: pad_left,
-            bottom: pad_bottom,
-            padding_value: pad_value,
-        }),
-        Some(Stride {
-            x: stride_x,
-            y: stride_y,
-        }),
+        None,
+        true,
+        Some(padding),
+        Some(stride),
+        None,
         Some(mac_clip),
         Some(pp_clip),
         None,
-    );
-    unsafe {
-        core::ptr::copy_nonoverlapping(result.to_buffer().as_mut_ptr(), output, result.get_size())
-    };
+        output,
+    )
+    .unwrap();
 }
 
 /// Executes Conv2D + Bias on DLA with given parameters and writes result to output buffer.
@@ -255,30 +318,37 @@ pub unsafe extern "C" fn dla_conv2d_bias(
         )
     };
 
-    let bias: Vec<i16> = unsafe { slice::from_raw_parts(bias as *const i16, bias_length).to_vec() };
+    let bias: &[i16] = unsafe { slice::from_raw_parts(bias as *const i16, bias_length) };
 
-    let result = conv2d_bias(
+    let padding = Padding {
+        top: pad_top,
+        right: pad_right,
+        left: pad_left,
+        bottom: pad_bottom,
+        padding_value: pad_value,
+    };
+    let stride = Stride {
+        x: stride_x,
+        y: stride_y,
+    };
+    let output =
+        unsafe { ffi_output_import(output, &input_tensor, &kernels_tensor, &padding, &stride) }
+            .unwrap();
+    try_conv2d_into(
+        &Dla::new(),
         input_tensor,
         kernels_tensor,
-        bias,
-        Some(Padding {
-            top: pad_top,
-            right: pad_right,
-            left: pad_left,
-            bottom: pad_bottom,
-            padding_value: pad_value,
-        }),
-        Some(Stride {
-            x: stride_x,
-            y: stride_y,
-        }),
+        Some(bias),
+        false,
+        Some(padding),
+        Some(stride),
+        None,
         Some(mac_clip),
         Some(pp_clip),
         None,
-    );
-    unsafe {
-        core::ptr::copy_nonoverlapping(result.to_buffer().as_mut_ptr(), output, result.get_size())
-    };
+        output,
+    )
+    .unwrap();
 }
 
 /// Executes Conv2D + Bias + ReLU on DLA with given parameters and writes result to output buffer.
@@ -336,10 +406,11 @@ pub unsafe extern "C" fn dla_conv2d_bias_relu(
             .collect()
     };
 
-    let result = conv2d_bias_relu(
+    let result: Tensor3<i8> = conv2d_view(
         input_tensor,
         kernels_tensor,
-        bias,
+        Some(&bias),
+        true,
         Some(Padding {
             top: pad_top,
             right: pad_right,
@@ -351,6 +422,7 @@ pub unsafe extern "C" fn dla_conv2d_bias_relu(
             x: stride_x,
             y: stride_y,
         }),
+        None,
         Some(mac_clip),
         Some(pp_clip),
         None,
@@ -414,21 +486,14 @@ pub unsafe extern "C" fn dla_tvm_qnn_conv2d_bias(
         )
     };
 
-    // NOTE:(20241025 vaino-waltteri.granat@tuni.fi) TVM expects 32-bit bias, but DLA only support 16-bit bias, so we clip the incoming bias
-    // to range suitable for DLA
-    let bias: Vec<i16> = unsafe {
-        slice::from_raw_parts(bias as *const i32, bias_length)
-            .into_iter()
-            .map(|x| (*x).clamp(i16::MIN as i32, i16::MAX as i32) as i16)
-            .collect()
-    };
-
-    //let optimized_pp = optimal_pp_bias_heuristic(&bias);
-    let optimized_pp = 7;
+    // TVM expects 32-bit bias, which the driver fits to DLA's 16-bit bias by shifting it together
+    // with the MAC results. PP clip has to be at least as large as the shift.
+    let bias: Vec<i32> = unsafe { slice::from_raw_parts(bias, bias_length).to_vec() };
+    let optimized_pp = tvm_bias_pp_clip(&bias);
 
-    let result: Tensor3<i8> = conv2d_bias(
-        input_tensor,
-        kernels_tensor,
+    let result: Tensor3<i8> = conv2d_bias_i32(
+        input_tensor.to_owned(),
+        kernels_tensor.to_owned(),
         bias,
         Some(Padding {
             top: pad_top,
@@ -461,6 +526,183 @@ pub unsafe extern "C" fn dla_tvm_qnn_conv2d_bias(
     unsafe { core::ptr::copy_nonoverlapping(res_i32.as_mut_ptr(), output, result.get_size()) };
 }
 
+/// Executes Conv2D + Bias on DLA and requantizes the result to 8 bits like TVM's `qnn.requantize`
+///
+/// PP clip is chosen from the requantization scales instead of being fixed, and the DLA output is
+/// shifted back before requantization, so no float code is needed.
+///
+/// NOTE: Results are approximate. DLA only writes 8-bit outputs on the ASIC, so the accumulators
+/// lose their low `mac_clip + pp_clip` bits before requantization, and an output may be less than
+/// `2^(mac_clip + pp_clip) * scale + 1` below the one of `qnn.requantize`. For bit-exact results,
+/// run the layer with 32-bit outputs on the VP and requantize them with [`dla_tvm_qnn_requantize`].
+///
+/// # Arguments
+///
+/// * `bias` - Buffer containing 32-bit bias data in the scale of the MAC results
+/// * `input_zero_point` - Zero point of the input, folded into bias and used as padding value
+/// * `multipliers` - Fixed-point multipliers in Q0.31 format, one per tensor or per channel
+/// * `shifts` - Power of two exponents of the scales, one for each multiplier
+/// * `output_zero_point` - Zero point of the requantized output
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn dla_tvm_qnn_conv2d_bias_requantize(
+    input_data: *const i8,
+    kernel_data: *const i8,
+    bias: *const i32,
+    output: *mut i8,
+    input_channels: usize,
+    input_height: usize,
+    input_width: usize,
+    input_order: *const c_char,
+    kernel_amount: usize,
+    kernel_channels: usize,
+    kernel_height: usize,
+    kernel_width: usize,
+    kernel_order: *const c_char,
+    bias_length: usize,
+    pad_top: u32,
+    pad_right: u32,
+    pad_left: u32,
+    pad_bottom: u32,
+    input_zero_point: i32,
+    stride_x: u32,
+    stride_y: u32,
+    mac_clip: u32,
+    multipliers: *const i32,
+    shifts: *const i32,
+    multipliers_length: usize,
+    output_zero_point: i32,
+) {
+    let (input_tensor, kernels_tensor) = unsafe {
+        ffi_data_import(
+            input_data,
+            input_channels,
+            input_height,
+            input_width,
+            input_order,
+            kernel_data,
+            kernel_amount,
+            kernel_channels,
+            kernel_height,
+            kernel_width,
+            kernel_order,
+        )
+    };
+
+    // Padding stands for the input zero point, which DLA reads as a signed byte
+    assert!(
+        i8::try_from(input_zero_point).is_ok(),
+        "input zero point does not fit in 8 bits"
+    );
+    let bias: Vec<i32> = unsafe { slice::from_raw_parts(bias, bias_length).to_vec() };
+    // Fold input zero point into bias: sum((x - zp) * w) = sum(x * w) - zp * sum(w)
+    let bias: Vec<i32> = bias
+        .iter()
+        .enumerate()
+        .map(|(k, &b)| {
+            let weight_sum: i32 = kernels_tensor
+                .slice_kernels(k..k + 1)
+                .iter_with_order(Order4::KCHW)
+                .map(|&w| w as i32)
+                .sum();
+            input_zero_point
+                .checked_mul(weight_sum)
+                .and_then(|offset| b.checked_sub(offset))
+                .expect("bias overflows when folding in the input zero point")
+        })
+        .collect();
+    let requantization = Requantization::per_channel(
+        unsafe { ffi_multipliers_import(multipliers, shifts, multipliers_length) },
+        0,
+        output_zero_point,
+        Rounding::Upward,
+    );
+    // PP clip has to be at least as large as the shift fitting bias to 16 bits
+    let pp_clip = requantization
+        .dla_pp_clip()
+        .saturating_sub(mac_clip)
+        .max(calculate_bias_shift(&bias));
+
+    let result: Tensor3<i8> = conv2d_bias_i32(
+        input_tensor.to_owned(),
+        kernels_tensor.to_owned(),
+        bias,
+        Some(Padding {
+            top: pad_top,
+            right: pad_right,
+            left: pad_left,
+            bottom: pad_bottom,
+            padding_value: input_zero_point,
+        }),
+        Some(Stride {
+            x: stride_x,
+            y: stride_y,
+        }),
+        Some(mac_clip),
+        Some(pp_clip),
+        None,
+    );
+
+    // Shift DLA output back to the scale of the MAC results
+    let shift = mac_clip + pp_clip;
+    let accumulators =
+        result.map(|x| ((*x as i64) << shift).clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+    let result = requantize(&accumulators, &requantization).unwrap();
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(result.to_buffer().as_mut_ptr(), output, result.get_size())
+    };
+}
+
+/// Requantizes 32-bit values to 8 bits like TVM's `qnn.requantize`, using only integer arithmetic
+///
+/// # Arguments
+///
+/// * `multipliers` - Fixed-point multipliers in Q0.31 format, one per tensor or per channel
+/// * `shifts` - Power of two exponents of the scales, one for each multiplier
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn dla_tvm_qnn_requantize(
+    input_data: *const i32,
+    output: *mut i8,
+    input_channels: usize,
+    input_height: usize,
+    input_width: usize,
+    input_order: *const c_char,
+    multipliers: *const i32,
+    shifts: *const i32,
+    multipliers_length: usize,
+    input_zero_point: i32,
+    output_zero_point: i32,
+) {
+    let input_data: Vec<i32> = unsafe {
+        slice::from_raw_parts(input_data, input_channels * input_height * input_width).to_vec()
+    };
+    let input_order_string = unsafe { CStr::from_ptr(input_order).to_str().unwrap_unchecked() };
+    let input_tensor = unsafe {
+        Tensor3::from_data_buffer(
+            input_channels,
+            input_height,
+            input_width,
+            input_data,
+            Order3::try_from(input_order_string).unwrap_unchecked(),
+        )
+        .unwrap_unchecked()
+    };
+
+    let requantization = Requantization::per_channel(
+        unsafe { ffi_multipliers_import(multipliers, shifts, multipliers_length) },
+        input_zero_point,
+        output_zero_point,
+        Rounding::Upward,
+    );
+    let result = requantize(&input_tensor, &requantization).unwrap();
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(result.to_buffer().as_mut_ptr(), output, result.get_size())
+    };
+}
+
 /// # Arguments
 ///
 /// * `bias` - Buffer containing bias data. NOTE: Bias is actually i16 in hardware, here we use 32 for TVM compatibility
@@ -520,8 +762,8 @@ pub unsafe extern "C" fn dla_tvm_qnn_conv2d_grouped_bias(
     let optimized_pp = optimal_pp_bias_heuristic(&bias);
 
     let result: Tensor3<i8> = grouped_conv2d(
-        input_tensor,
-        kernels_tensor,
+        input_tensor.to_owned(),
+        kernels_tensor.to_owned(),
         bias,
         Some(Padding {
             top: pad_top,
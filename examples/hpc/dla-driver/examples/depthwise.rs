@@ -7,10 +7,14 @@ extern crate alloc;
 use headsail_bsp::{init_heap, rt::entry, sprint, sprintln};
 use panic_halt as _;
 
+use dla_driver::stats::with_stats;
 use dla_driver::tensor3::{Order3, Tensor3};
 use dla_driver::tensor4::{Order4, Tensor4};
+use dla_driver::Dla;
 
 use alloc::vec::Vec;
+use rand::rngs::SmallRng;
+use rand::{RngCore, SeedableRng};
 
 fn conv_test() {
     sprintln!("conv_test: enter");
@@ -77,14 +81,23 @@ fn conv_test() {
         7,6,3,
     ];
 
-    let bias: Vec<i16> = vec![-16, 16, 0, 0, 8, -8, -36, 36];
+    let bias: Vec<i16> = vec![-16, 16, 0, 0];
 
     let din_tensor: Tensor3<i8> = Tensor3::from_data_buffer(4, 5, 5, din, Order3::CHW).unwrap();
-    let wgt_tensor: Tensor4<i8> = Tensor4::from_data_buffer(2, 4, 3, 3, wgt, Order4::KCHW).unwrap();
+    let wgt_tensor: Tensor4<i8> =
+        Tensor4::from_data_buffer(4, 1, 3, 3, wgt[..4 * 9].to_vec(), Order4::KCHW).unwrap();
 
     sprintln!("Data loaded");
-    let mut output: Tensor3<i8> = dla_driver::layers::grouped_conv2d(
-        din_tensor, wgt_tensor, bias, None, None, None, None, None, 4,
+
+    let mut output = dla_driver::layers::depthwise_conv2d::<i8>(
+        din_tensor,
+        wgt_tensor,
+        Some(bias),
+        None,
+        None,
+        None,
+        None,
+        None,
     );
     output.permute(Order3::CWH);
 
@@ -100,11 +113,79 @@ fn conv_test() {
     sprintln!("\nconv_test: leave");
 }
 
+/// Compares cycles of depthwise_conv2d against grouped_conv2d, which runs DLA once per channel
+fn benchmark(channels: usize, size: usize, multiplier: usize) {
+    let mut rng = SmallRng::seed_from_u64(1234567890);
+    let input: Vec<i8> = (0..channels * size * size)
+        .map(|_| rng.next_u32() as i8)
+        .collect();
+    let kernels: Vec<i8> = (0..channels * multiplier * 9)
+        .map(|_| (rng.next_u32() & 0x7) as i8 - 4)
+        .collect();
+    let bias: Vec<i16> = (0..channels * multiplier)
+        .map(|_| (rng.next_u32() & 0xFF) as i16 - 128)
+        .collect();
+
+    let input = Tensor3::from_data_buffer(channels, size, size, input, Order3::CHW).unwrap();
+    let kernels =
+        Tensor4::from_data_buffer(channels * multiplier, 1, 3, 3, kernels, Order4::KCHW).unwrap();
+
+    let dla = Dla::new();
+    let (grouped, grouped_stats) = with_stats(&dla, |dla| {
+        dla_driver::layers::try_grouped_conv2d::<i8, _>(
+            dla,
+            input.clone(),
+            kernels.clone(),
+            bias.clone(),
+            None,
+            None,
+            Some(4),
+            None,
+            None,
+            channels,
+        )
+    });
+    let (depthwise, depthwise_stats) = with_stats(&dla, |dla| {
+        dla_driver::layers::try_depthwise_conv2d::<i8, _>(
+            dla,
+            input.clone(),
+            kernels.clone(),
+            Some(bias.clone()),
+            None,
+            None,
+            Some(4),
+            None,
+            None,
+        )
+    });
+
+    sprintln!(
+        "{}x{}x{}, multiplier {}: grouped {} cycles, depthwise {} cycles, speedup {}.{:02}",
+        channels,
+        size,
+        size,
+        multiplier,
+        grouped_stats.cycles,
+        depthwise_stats.cycles,
+        grouped_stats.cycles / depthwise_stats.cycles.max(1),
+        grouped_stats.cycles * 100 / depthwise_stats.cycles.max(1) % 100,
+    );
+    let (grouped, depthwise) = (grouped.unwrap(), depthwise.unwrap());
+    assert!(
+        grouped.to_buffer_with_order(Order3::CHW) == depthwise.to_buffer_with_order(Order3::CHW)
+    );
+}
+
 #[entry]
 fn main() -> ! {
     // Safety: init heap is called once only
     unsafe { init_heap() };
     conv_test();
 
+    for (channels, size, multiplier) in [(4, 5, 1), (16, 16, 1), (32, 16, 2), (64, 8, 1)] {
+        benchmark(channels, size, multiplier);
+    }
+    sprintln!("benchmark: leave");
+
     loop {}
 }
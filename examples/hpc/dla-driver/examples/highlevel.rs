@@ -27,7 +27,16 @@ fn dense_test() {
     }
 
     dla_driver::layers::dense(5, din_tensor, weight);
-    sprintln!("dense_test: leave");
+
+    // Two input vectors of 4 features through a 3x4 weight matrix
+    let inputs: Vec<i8> = vec![1, 2, 3, 4, -1, -2, -3, -4];
+    let weights: Vec<i8> = vec![1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 2];
+    let output: Vec<i8> =
+        dla_driver::layers::linear(inputs, weights, Some(vec![0, 1, 2]), 3, true, None, None);
+    for x in output {
+        sprint!(" {}", x);
+    }
+    sprintln!("\ndense_test: leave");
 }
 
 fn conv_test() {
@@ -51,7 +60,7 @@ fn conv_test() {
     let _dout_tensor: Tensor3<i32> = Tensor3::from_data_buffer(2, 3, 3, dout, Order3::CHW).unwrap();
 
     let mut output: Tensor3<i8> =
-        dla_driver::layers::conv2d(din_tensor, wgt_tensor, None, None, None, None, None);
+        dla_driver::layers::conv2d(din_tensor, wgt_tensor, None, None, None, None, None, None);
     output.permute(Order3::CWH);
     sprintln!("conv_test: leave");
 }
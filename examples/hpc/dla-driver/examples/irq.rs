@@ -0,0 +1,82 @@
+//! Runs a Conv2D layer that completes on DLA's interrupt and checks it against the reference
+//!
+//! Build with feature `irq`. Assumes the test is run on hart 0 with no other cores interfering.
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use headsail_bsp::riscv::{self, InterruptNumber};
+use headsail_bsp::{init_heap, rt::entry, sprint, sprintln, Interrupt, PLIC};
+use panic_halt as _;
+
+use dla_driver::layers::try_conv2d;
+use dla_driver::reference;
+use dla_driver::tensor3::{Order3, Tensor3};
+use dla_driver::tensor4::{Order4, Tensor4};
+use dla_driver::Dla;
+
+use alloc::vec::Vec;
+
+/// Number of DLA interrupts claimed from PLIC
+static DLA_IRQS: AtomicUsize = AtomicUsize::new(0);
+
+#[export_name = "MachineExternal"]
+fn external_interrupt() {
+    if let Some(id) = PLIC::ctx0().claim().claim::<Interrupt>() {
+        if matches!(id, Interrupt::Dla) {
+            DLA_IRQS.fetch_add(1, Ordering::Relaxed);
+            dla_driver::on_interrupt();
+        }
+        PLIC::ctx0().claim().complete(id);
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    // SAFETY: `init_heap` must be called once only
+    unsafe { init_heap() };
+
+    // Enables DLA interrupt in PLIC
+    let dla = Dla::new();
+    // SAFETY: the handler above only touches the PLIC claim register and DLA driver's waker
+    unsafe { riscv::interrupt::enable() };
+
+    let din: Vec<i8> = (0..3 * 8 * 8).map(|x| (x % 7) as i8 - 3).collect();
+    let wgt: Vec<i8> = (0..4 * 3 * 3 * 3).map(|x| (x % 5) as i8 - 2).collect();
+    let input = Tensor3::from_data_buffer(3, 8, 8, din, Order3::CHW).unwrap();
+    let kernels = Tensor4::from_data_buffer(4, 3, 3, 3, wgt, Order4::KCHW).unwrap();
+
+    let output: Tensor3<i8> = try_conv2d(
+        &dla,
+        input.clone(),
+        kernels.clone(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let expected =
+        reference::conv2d_bias_relu(&input, &kernels, None, false, None, None, None, None, None)
+            .unwrap();
+
+    let irqs = DLA_IRQS.load(Ordering::Relaxed);
+    sprintln!(
+        "DLA interrupts claimed from PLIC source {}: {}",
+        Interrupt::Dla.number(),
+        irqs
+    );
+    if irqs > 0
+        && output.to_buffer_with_order(Order3::HWC) == expected.to_buffer_with_order(Order3::HWC)
+    {
+        sprintln!("Layer completed on interrupt");
+    } else {
+        sprintln!("Layer did not complete on interrupt");
+    }
+
+    loop {}
+}
@@ -75,9 +75,9 @@ fn run_random_layer(
 
     // Initalize layer
     let config = LayerConfig {
-        input_bank: Some(MemoryBank::Bank0),
-        kernel_bank: Some(MemoryBank::Bank4),
-        output_bank: Some(MemoryBank::Bank8),
+        input_bank: BankSelection::Bank(MemoryBank::Bank0),
+        kernel_bank: BankSelection::Bank(MemoryBank::Bank4),
+        output_bank: BankSelection::Bank(MemoryBank::Bank8),
         bias_addr: Some((MEMORY_BANK_12_OFFSET + MEMORY_BANK_BASE_ADDR) as u32),
         pp_enabled: true,
         relu_enabled: true,
@@ -103,24 +103,39 @@ fn run_random_layer(
         stride: Some(Stride { x: 1, y: 1 }),
         mac_clip: Some(8),
         pp_clip: Some(8),
+        mac_sat_max: None,
+        mac_sat_min: None,
+        pp_rounding: false,
         simd_mode: Some(SimdBitMode::EightBits),
+        pooling: None,
+        output_width: Some(OutputWidth::EightBits),
     };
 
-    dla.init_layer(config);
+    dla.try_init_layer(config).unwrap();
 
     // Write input and kernel to buffer
-    dla.write_input(&mut input);
-    dla.write_kernel(&mut kernel);
+    dla.write_input(&mut input).unwrap();
+    dla.write_kernel(&mut kernel).unwrap();
 
     // Mark data ready to start calculations
+    let start = dla.stats_snapshot();
     dla.kernel_data_ready(true);
     dla.input_data_ready(true);
 
     // Print the matrix
     sprintln!("Waiting for calculation");
     while !dla.handle_handshake() {}
-    sprintln!("Calculation ready");
+    let stats = start.elapsed(dla);
+    sprintln!(
+        "Calculation ready in {} cycles ({} mtime ticks), data wait A/B {}/{}, pipeline stalls {}",
+        stats.cycles,
+        stats.mtime,
+        stats.buf_data_wait_a,
+        stats.buf_data_wait_b,
+        stats.buf_pipe_stall
+    );
     dla.read_output_i8(output_width as usize * output_height as usize * 16)
+        .unwrap()
 }
 
 #[entry]
@@ -38,7 +38,7 @@ fn validate_conv2d_tiny() -> bool {
     let dout_tensor =
         generate_output_tensor(&din_tensor, &wgt_tensor, dout_i32, Order3::HWC, None, None);
     let mut output: Tensor3<i32> =
-        dla_driver::layers::conv2d(din_tensor, wgt_tensor, None, None, None, None, None);
+        dla_driver::layers::conv2d(din_tensor, wgt_tensor, None, None, None, None, None, None);
     output.permute(Order3::HWC);
 
     sprint!("\ndla out | dout\n");
@@ -83,7 +83,7 @@ fn validate_conv2d() -> bool {
         generate_output_tensor(&din_tensor, &wgt_tensor, dout_i32, Order3::HWC, None, None);
 
     let mut output =
-        dla_driver::layers::conv2d(din_tensor, wgt_tensor, None, None, None, None, None);
+        dla_driver::layers::conv2d(din_tensor, wgt_tensor, None, None, None, None, None, None);
     output.permute(Order3::HWC);
 
     sprint!("\n");
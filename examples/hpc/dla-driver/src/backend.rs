@@ -0,0 +1,615 @@
+//! Access to DLA's configuration registers and memory
+//!
+//! [`Dla`](crate::Dla) talks to the hardware through the [`RegisterAccess`] trait. [`Mmio`] is used
+//! on target. [`Simulated`] keeps registers and memory banks in RAM and calculates layers like the
+//! VP does, so that the driver and the layer functions can be tested without Renode, e.g. on host.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! let dla = Dla::with_backend(Simulated::new());
+//! let output: Tensor3<i8> = try_conv2d(&dla, input, kernels, None, None, None, None, None, None)?;
+//! ```
+use crate::bank::NUM_BANKS;
+use crate::mmap::*;
+use crate::reference;
+use crate::simd::{packed_len, unpack, Packed, I2, I4};
+use crate::tensor3::{Order3, Tensor3};
+use crate::tensor4::{Order4, Tensor4};
+use crate::{cpu_addr, DlaError, Padding, SimdBitMode, Stride, DLA0_ADDR, MEMORY_BANK_BASE_ADDR};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::ptr;
+
+/// Size of DLA's configuration register space in bytes
+const REGISTER_SPACE_SIZE: usize = 0x100;
+
+/// Register and memory accesses needed by the driver
+pub trait RegisterAccess {
+    /// Writes u32 to DLA's configuration register at offset from DLA's base address
+    fn write_reg(&self, offset: usize, value: u32);
+    /// Reads u32 from DLA's configuration register at offset from DLA's base address
+    fn read_reg(&self, offset: usize) -> u32;
+    /// Writes byte to absolute address, e.g. to a memory bank
+    fn write_mem_u8(&self, addr: usize, value: u8);
+    /// Writes little-endian u32 to 4-byte aligned absolute address, e.g. to a memory bank
+    fn write_mem_u32(&self, addr: usize, value: u32);
+    /// Reads u32 from absolute address
+    fn read_mem_u32(&self, addr: usize) -> u32;
+    /// Reads u128 from absolute address
+    fn read_mem_u128(&self, addr: usize) -> u128;
+    /// Whether DLA's completion interrupt reaches the CPU, otherwise the handshake is polled
+    fn has_irq(&self) -> bool {
+        false
+    }
+    /// Whether DLA's post-processor can do pooling, otherwise it's done on CPU
+    fn has_pooling(&self) -> bool {
+        false
+    }
+    /// Whether DLA's post-processor can read bias from outside its memory banks, otherwise bias is
+    /// copied to the banks
+    fn has_external_bias(&self) -> bool {
+        false
+    }
+    /// Whether DLA calculates 4-bit and 2-bit values packed into bytes, otherwise packed layers are
+    /// run with 8-bit values
+    fn has_simd(&self) -> bool {
+        false
+    }
+    /// Whether DLA writes raw 32-bit MAC outputs for layers without MAC clip, otherwise outputs are
+    /// always post-processed to 8 bits
+    fn has_out32(&self) -> bool {
+        false
+    }
+    /// Makes CPU's preceding memory writes visible to DLA before the register writes that follow,
+    /// e.g. bias DLA reads from outside its memory banks
+    fn fence(&self) {}
+}
+
+/// Memory mapped access to the real DLA or the one in VP
+#[derive(Clone, Copy, Default)]
+pub struct Mmio;
+
+impl RegisterAccess for Mmio {
+    fn write_reg(&self, offset: usize, value: u32) {
+        unsafe { ptr::write_volatile((DLA0_ADDR + offset) as *mut _, value) }
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { ptr::read_volatile((DLA0_ADDR + offset) as *const _) }
+    }
+
+    fn write_mem_u8(&self, addr: usize, value: u8) {
+        unsafe { ptr::write_volatile(addr as *mut _, value) }
+    }
+
+    fn write_mem_u32(&self, addr: usize, value: u32) {
+        unsafe { ptr::write_volatile(addr as *mut _, value) }
+    }
+
+    fn read_mem_u32(&self, addr: usize) -> u32 {
+        unsafe { ptr::read_volatile(addr as *const _) }
+    }
+
+    fn read_mem_u128(&self, addr: usize) -> u128 {
+        unsafe { ptr::read_volatile(addr as *const _) }
+    }
+
+    // NOTE: The VP models neither pooling nor SIMD modes, and only reads bias from the memory banks
+    fn has_pooling(&self) -> bool {
+        cfg!(all(feature = "hw-pooling", not(feature = "vp")))
+    }
+
+    fn has_external_bias(&self) -> bool {
+        !cfg!(feature = "vp")
+    }
+
+    fn has_simd(&self) -> bool {
+        !cfg!(feature = "vp")
+    }
+
+    // NOTE: Only the VP writes 32-bit outputs, given `DLA_VP_OUT32` is set in its environment
+    fn has_out32(&self) -> bool {
+        cfg!(feature = "vp")
+    }
+
+    // NOTE: headsail-bsp has no cache maintenance operations, so data DLA reads from SDRAM is only
+    // ordered, not written back
+    fn fence(&self) {
+        #[cfg(target_arch = "riscv64")]
+        unsafe {
+            core::arch::asm!("fence iorw, iorw")
+        };
+    }
+
+    fn has_irq(&self) -> bool {
+        cfg!(all(target_arch = "riscv64", feature = "irq"))
+    }
+}
+
+/// In-memory model of DLA's registers and memory
+///
+/// Stores register values and memory contents as the hardware would, and calculates a layer once
+/// its data has been marked ready, following `vp/devel/python_peripherals/DLA.py` with the
+/// arithmetic of [`reference`]. Like the VP, it doesn't model pooling or bias outside the memory
+/// banks, and calculates in SIMD modes only if enabled with [`Simulated::set_simd`]. Memory outside
+/// the banks is kept sparsely, so e.g. bias written to external memory can be inspected as well.
+///
+/// Unlike the VP, power domains, MAC saturation and the rounding bit of PP_CTRL are modeled. Power
+/// domains acknowledge power down immediately, and memory banks are cleared once all of them are
+/// down.
+pub struct Simulated {
+    registers: RefCell<Vec<u32>>,
+    banks: RefCell<Vec<u8>>,
+    external: RefCell<BTreeMap<usize, u8>>,
+    out32: Cell<bool>,
+    simd: Cell<bool>,
+}
+
+impl Default for Simulated {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulated {
+    /// Creates a model with memory and registers cleared
+    ///
+    /// Only MAC saturation bounds are set, to the whole 32-bit range, so that MAC results are not
+    /// saturated before the driver sets them.
+    pub fn new() -> Self {
+        let mut registers = vec![0; REGISTER_SPACE_SIZE / 4];
+        registers[DLA_MAC_SAT_MAX / 4] = i32::MAX as u32;
+        registers[DLA_MAC_SAT_MIN / 4] = i32::MIN as u32;
+        Simulated {
+            registers: RefCell::new(registers),
+            banks: RefCell::new(vec![0; NUM_BANKS * MEMORY_BANK_SIZE]),
+            external: RefCell::new(BTreeMap::new()),
+            out32: Cell::new(false),
+            simd: Cell::new(false),
+        }
+    }
+
+    /// Writes 32-bit outputs for layers without MAC clip, like the VP does with `DLA_VP_OUT32` set
+    pub fn set_out32(&self, enable: bool) {
+        self.out32.set(enable)
+    }
+
+    /// Reads inputs and kernels packed in the SIMD mode of MAC_CTRL, as the hardware does, instead of
+    /// calculating 8-bit values like the VP does
+    pub fn set_simd(&self, enable: bool) {
+        self.simd.set(enable)
+    }
+
+    /// Returns raw contents of all memory banks as they would be seen by DLA
+    pub fn banks(&self) -> Vec<u8> {
+        self.banks.borrow().clone()
+    }
+
+    /// Writes raw bytes to memory banks starting from offset, e.g. to emulate DLA writing outputs
+    pub fn write_banks(&self, offset: usize, data: &[u8]) {
+        self.banks.borrow_mut()[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    /// Returns index into the bank memory if address is inside memory banks
+    fn bank_index(addr: usize) -> Option<usize> {
+        addr.checked_sub(MEMORY_BANK_BASE_ADDR)
+            .filter(|&idx| idx < NUM_BANKS * MEMORY_BANK_SIZE)
+    }
+
+    fn read_mem_u8(&self, addr: usize) -> u8 {
+        match Self::bank_index(addr) {
+            Some(idx) => self.banks.borrow()[idx],
+            None => self.external.borrow().get(&addr).copied().unwrap_or(0),
+        }
+    }
+
+    /// Returns the bits of register field described by `offset` and `bitmask`
+    fn field(&self, register: usize, offset: usize, bitmask: usize) -> usize {
+        (self.read_reg(register) as usize & bitmask) >> offset
+    }
+
+    /// Returns whether a single bit register field is set
+    fn flag(&self, register: usize, bitmask: usize) -> bool {
+        self.read_reg(register) as usize & bitmask != 0
+    }
+
+    /// Sets or clears a single bit register field
+    fn set_flag(&self, register: usize, bitmask: usize, value: bool) {
+        let reg = self.read_reg(register) & !(bitmask as u32);
+        self.registers.borrow_mut()[register / 4] = reg | if value { bitmask as u32 } else { 0 };
+    }
+
+    /// Acknowledges the requested power domain states, losing bank contents when all are down
+    fn power(&self) {
+        let down = DLA_POWER_CTRL_DOWN_0_BITMASK
+            | DLA_POWER_CTRL_DOWN_1_BITMASK
+            | DLA_POWER_CTRL_DOWN_2_BITMASK;
+        let requested = self.read_reg(DLA_POWER_CTRL) as usize & down;
+        // Acknowledgements are at the same bit positions as the requests
+        self.registers.borrow_mut()[DLA_POWER_STAT / 4] = requested as u32;
+        if requested == down {
+            self.banks.borrow_mut().fill(0);
+        }
+    }
+
+    /// Reads `len` bytes starting from the given bank, undoing the reversal of each 64-bit chunk
+    fn read_buffer(&self, bank: usize, len: usize) -> Vec<i8> {
+        let start = MEMORY_BANK_BASE_ADDR + bank * MEMORY_BANK_SIZE;
+        let mut data = Vec::with_capacity(len);
+        for chunk in (0..len).step_by(8) {
+            let chunk_len = core::cmp::min(8, len - chunk);
+            for i in (0..chunk_len).rev() {
+                data.push(self.read_mem_u8(start + chunk + i) as i8);
+            }
+        }
+        data
+    }
+
+    /// Reads `pixels` pixels of `channels` values packed in `simd_mode`, see [`crate::simd`]
+    fn read_values(
+        &self,
+        bank: usize,
+        pixels: usize,
+        channels: usize,
+        simd_mode: SimdBitMode,
+    ) -> Result<Vec<i8>, DlaError> {
+        match simd_mode {
+            SimdBitMode::EightBits => self.read_packed::<i8>(bank, pixels, channels),
+            SimdBitMode::FourBits => self.read_packed::<I4>(bank, pixels, channels),
+            SimdBitMode::TwoBits => self.read_packed::<I2>(bank, pixels, channels),
+        }
+    }
+
+    fn read_packed<P: Packed>(
+        &self,
+        bank: usize,
+        pixels: usize,
+        channels: usize,
+    ) -> Result<Vec<i8>, DlaError> {
+        let bytes: Vec<u8> = self
+            .read_buffer(bank, packed_len::<P>(pixels, channels))
+            .into_iter()
+            .map(|b| b as u8)
+            .collect();
+        let values =
+            unpack::<P>(&bytes, pixels * channels, channels).ok_or(DlaError::DimensionMismatch)?;
+        Ok(values.into_iter().map(P::value).collect())
+    }
+
+    /// Returns the SIMD mode layers are calculated in
+    fn simd_mode(&self) -> SimdBitMode {
+        if !self.simd.get() {
+            return SimdBitMode::EightBits;
+        }
+        match self.field(
+            DLA_MAC_CTRL,
+            DLA_SIMD_SELECT_OFFSET,
+            DLA_SIMD_SELECT_BITMASK,
+        ) {
+            1 => SimdBitMode::FourBits,
+            2 => SimdBitMode::TwoBits,
+            _ => SimdBitMode::EightBits,
+        }
+    }
+
+    /// Clears done status once the driver has acknowledged it, like `handle_handshake` of the VP
+    fn handle_handshake(&self) {
+        let stages = [
+            (
+                DLA_HANDSHAKE_BUFFER_ENABLE_BITMASK,
+                DLA_HANDSHAKE_BUFFER_VALID_BITMASK,
+                DLA_BUF_DONE_BITMASK,
+            ),
+            (
+                DLA_HANDSHAKE_MAC_ENABLE_BITMASK,
+                DLA_HANDSHAKE_MAC_VALID_BITMASK,
+                DLA_MAC_DONE_BITMASK,
+            ),
+            (
+                DLA_HANDSHAKE_BYPASS_ENABLE_BITMASK,
+                DLA_HANDSHAKE_ACTIVE_VALID_BITMASK,
+                DLA_PP_DONE_BITMASK,
+            ),
+        ];
+        for (enable, valid, done) in stages {
+            if !self.flag(DLA_HANDSHAKE, enable) && self.flag(DLA_HANDSHAKE, valid) {
+                self.set_flag(DLA_HANDSHAKE, valid, false);
+                self.set_flag(DLA_STATUS_ADDR, done, false);
+            }
+        }
+    }
+
+    /// Calculates the configured layer once its data is ready
+    fn process(&self) {
+        self.handle_handshake();
+
+        let done = DLA_BUF_DONE_BITMASK | DLA_MAC_DONE_BITMASK | DLA_PP_DONE_BITMASK;
+        if self.flag(DLA_STATUS_ADDR, done) {
+            return;
+        }
+
+        let pp_only = self.flag(DLA_PP_CTRL, DLA_PP_SELECT_BITMASK)
+            && !self.flag(DLA_HANDSHAKE, DLA_HANDSHAKE_MAC_ENABLE_BITMASK);
+        if !self.flag(DLA_BUF_CTRL, DLA_READ_B_VALID_BITMASK)
+            || (!pp_only && !self.flag(DLA_BUF_CTRL, DLA_READ_A_VALID_BITMASK))
+        {
+            return;
+        }
+
+        // Configurations the driver rejects are written out as empty output, like the VP does
+        if let Ok((output, out32)) = self.calculate(pp_only) {
+            let mut addr = cpu_addr(self.read_reg(DLA_PP_AXI_WRITE));
+            for value in output.to_buffer_with_order(Order3::HWC) {
+                if out32 {
+                    for byte in value.to_be_bytes() {
+                        self.write_mem_u8(addr, byte);
+                        addr += 1;
+                    }
+                } else {
+                    let value = reference::pp_clip(
+                        reference::saturate(value, 16),
+                        self.pp_clip_amount(),
+                        self.flag(DLA_PP_CTRL, DLA_ROUNDING_BITMASK),
+                    );
+                    self.write_mem_u8(addr, reference::saturate_output(value) as u8);
+                    addr += 1;
+                }
+            }
+        }
+
+        self.set_flag(DLA_STATUS_ADDR, done, true);
+        self.set_flag(
+            DLA_BUF_CTRL,
+            DLA_READ_A_VALID_BITMASK | DLA_READ_B_VALID_BITMASK,
+            false,
+        );
+    }
+
+    fn pp_clip_amount(&self) -> u32 {
+        self.field(DLA_PP_CTRL, DLA_PP_CLIP_OFFSET, DLA_PP_CLIP_BITMASK) as u32
+    }
+
+    /// Calculates the layer up to bias and ReLU, returning also whether the output is 32 bits wide
+    fn calculate(&self, pp_only: bool) -> Result<(Tensor3<i32>, bool), DlaError> {
+        let channels = self.field(
+            DLA_BUF_INPUT,
+            DLA_BUF_INPUT_CHANNELS_OFFSET,
+            DLA_BUF_INPUT_CHANNELS_BITMASK,
+        ) + 1;
+        let (width, height) = if pp_only {
+            (
+                self.field(
+                    DLA_PP_INPUT,
+                    DLA_PP_INPUT_WIDTH_OFFSET,
+                    DLA_PP_INPUT_WIDTH_BITMASK,
+                ),
+                self.field(
+                    DLA_PP_INPUT,
+                    DLA_PP_INPUT_HEIGHT_OFFSET,
+                    DLA_PP_INPUT_HEIGHT_BITMASK,
+                ),
+            )
+        } else {
+            (
+                self.field(
+                    DLA_BUF_INPUT,
+                    DLA_BUF_INPUT_WIDTH_OFFSET,
+                    DLA_BUF_INPUT_WIDTH_BITMASK,
+                ),
+                self.field(
+                    DLA_BUF_INPUT,
+                    DLA_BUF_INPUT_HEIGHT_OFFSET,
+                    DLA_BUF_INPUT_HEIGHT_BITMASK,
+                ),
+            )
+        };
+        let (width, height) = (width + 1, height + 1);
+        let input_bank = self.field(
+            DLA_BUF_DATA_BANK,
+            DLA_BUF_DATA_BANK_B_OFFSET,
+            DLA_BUF_DATA_BANK_B_BITMASK,
+        );
+        // Post-processor reads 8-bit values regardless of the SIMD mode
+        let simd_mode = if pp_only {
+            SimdBitMode::EightBits
+        } else {
+            self.simd_mode()
+        };
+        let input = Tensor3::from_data_buffer(
+            channels,
+            height,
+            width,
+            self.read_values(input_bank, height * width, channels, simd_mode)?,
+            Order3::HWC,
+        )
+        .map_err(|_| DlaError::DimensionMismatch)?;
+
+        let (mac, out32) = if pp_only {
+            (input.map(|&x| x as i32), false)
+        } else {
+            let mac_clip = self.field(DLA_MAC_CTRL, DLA_MAC_CLIP_OFFSET, DLA_MAC_CLIP_BITMASK);
+            (
+                self.calculate_mac(&input, simd_mode)?,
+                self.out32.get() && mac_clip == 0,
+            )
+        };
+
+        if !self.flag(DLA_HANDSHAKE, DLA_HANDSHAKE_BYPASS_ENABLE_BITMASK) {
+            return Ok((mac, out32));
+        }
+        let bias = if self.flag(DLA_HANDSHAKE, DLA_HANDSHAKE_BIAS_ENABLE_BITMASK) {
+            let addr = cpu_addr(self.read_reg(DLA_PP_AXI_READ));
+            (0..mac.channels())
+                .map(|i| {
+                    let low = self.read_mem_u8(addr + 2 * i);
+                    let high = self.read_mem_u8(addr + 2 * i + 1);
+                    i16::from_le_bytes([low, high]) as i32
+                })
+                .collect()
+        } else {
+            vec![0; mac.channels()]
+        };
+        let relu = self.flag(DLA_HANDSHAKE, DLA_HANDSHAKE_ACTIVE_ENABLE_BITMASK);
+
+        let (kernels, height, width) = mac.dimensions();
+        let output = mac
+            .to_buffer_with_order(Order3::HWC)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, value)| {
+                let value = value.wrapping_add(bias[idx % kernels]);
+                if relu {
+                    value.max(0)
+                } else {
+                    value
+                }
+            })
+            .collect();
+        let output = Tensor3::from_data_buffer(kernels, height, width, output, Order3::HWC)
+            .map_err(|_| DlaError::DimensionMismatch)?;
+        Ok((output, out32))
+    }
+
+    /// Calculates the MAC array output with MAC saturation and MAC clip applied
+    fn calculate_mac(
+        &self,
+        input: &Tensor3<i8>,
+        simd_mode: SimdBitMode,
+    ) -> Result<Tensor3<i32>, DlaError> {
+        let kernel_width = self.field(
+            DLA_BUF_KERNEL_0,
+            DLA_BUF_KERNEL_0_WIDTH_OFFSET,
+            DLA_BUF_KERNEL_0_WIDTH_BITMASK,
+        ) + 1;
+        let kernel_height = self.field(
+            DLA_BUF_KERNEL_0,
+            DLA_BUF_KERNEL_0_HEIGHT_OFFSET,
+            DLA_BUF_KERNEL_0_HEIGHT_BITMASK,
+        ) + 1;
+        let num_kernels = self.field(
+            DLA_BUF_KERNEL_1,
+            DLA_BUF_KERNEL_1_NUM_OFFSET,
+            DLA_BUF_KERNEL_1_NUM_BITMASK,
+        ) + 1;
+        let kernel_bank = self.field(
+            DLA_BUF_DATA_BANK,
+            DLA_BUF_DATA_BANK_A_OFFSET,
+            DLA_BUF_DATA_BANK_A_BITMASK,
+        );
+        let channels = input.channels();
+        let kernels = Tensor4::from_data_buffer(
+            num_kernels,
+            channels,
+            kernel_height,
+            kernel_width,
+            self.read_values(
+                kernel_bank,
+                num_kernels * kernel_height * kernel_width,
+                channels,
+                simd_mode,
+            )?,
+            Order4::HWKC,
+        )
+        .map_err(|_| DlaError::DimensionMismatch)?;
+
+        // Padding value is read as a signed byte, like the VP does
+        let padding = Padding {
+            top: self.field(DLA_BUF_PAD, DLA_BUF_PAD_TOP_OFFSET, DLA_BUF_PAD_TOP_BITMASK) as u32,
+            right: self.field(
+                DLA_BUF_PAD,
+                DLA_BUF_PAD_RIGHT_OFFSET,
+                DLA_BUF_PAD_RIGHT_BITMASK,
+            ) as u32,
+            left: self.field(
+                DLA_BUF_PAD,
+                DLA_BUF_PAD_LEFT_OFFSET,
+                DLA_BUF_PAD_LEFT_BITMASK,
+            ) as u32,
+            bottom: self.field(
+                DLA_BUF_PAD,
+                DLA_BUF_PAD_BOTTOM_OFFSET,
+                DLA_BUF_PAD_BOTTOM_BITMASK,
+            ) as u32,
+            padding_value: self.field(DLA_BUF_PAD, DLA_BUF_PAD_VALUE_OFFSET, 0xFF << 16) as u8 as i8
+                as i32,
+        };
+        let stride = Stride {
+            x: self.field(
+                DLA_BUF_STRIDE,
+                DLA_BUF_STRIDE_X_OFFSET,
+                DLA_BUF_STRIDE_X_BITMASK,
+            ) as u32
+                + 1,
+            y: self.field(
+                DLA_BUF_STRIDE,
+                DLA_BUF_STRIDE_Y_OFFSET,
+                DLA_BUF_STRIDE_Y_BITMASK,
+            ) as u32
+                + 1,
+        };
+        let mac_clip = self.field(DLA_MAC_CTRL, DLA_MAC_CLIP_OFFSET, DLA_MAC_CLIP_BITMASK) as u32;
+        let mac_sat_max = self.read_reg(DLA_MAC_SAT_MAX) as i32;
+        let mac_sat_min = self.read_reg(DLA_MAC_SAT_MIN) as i32;
+
+        let mac = reference::conv2d_mac(
+            input,
+            &kernels,
+            Some(padding),
+            Some(stride),
+            Some(simd_mode),
+        )?;
+        Ok(mac.map(|&x| reference::mac_clip(x.clamp(mac_sat_min, mac_sat_max), mac_clip)))
+    }
+}
+
+impl RegisterAccess for Simulated {
+    fn write_reg(&self, offset: usize, value: u32) {
+        self.registers.borrow_mut()[offset / 4] = value;
+        if offset == DLA_POWER_CTRL {
+            self.power();
+            return;
+        }
+        self.process();
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        self.registers.borrow()[offset / 4]
+    }
+
+    fn write_mem_u8(&self, addr: usize, value: u8) {
+        match Self::bank_index(addr) {
+            Some(idx) => self.banks.borrow_mut()[idx] = value,
+            None => {
+                self.external.borrow_mut().insert(addr, value);
+            }
+        }
+    }
+
+    fn write_mem_u32(&self, addr: usize, value: u32) {
+        for (i, b) in value.to_le_bytes().into_iter().enumerate() {
+            self.write_mem_u8(addr + i, b);
+        }
+    }
+
+    fn read_mem_u32(&self, addr: usize) -> u32 {
+        (0..4).fold(0, |acc, i| {
+            acc | (self.read_mem_u8(addr + i) as u32) << (8 * i)
+        })
+    }
+
+    fn read_mem_u128(&self, addr: usize) -> u128 {
+        (0..16).fold(0, |acc, i| {
+            acc | (self.read_mem_u8(addr + i) as u128) << (8 * i)
+        })
+    }
+
+    fn has_simd(&self) -> bool {
+        self.simd.get()
+    }
+
+    fn has_out32(&self) -> bool {
+        self.out32.get()
+    }
+}
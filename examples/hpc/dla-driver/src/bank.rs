@@ -0,0 +1,208 @@
+//! Allocation of DLA's memory banks
+//!
+//! Banks are handed out as [`BankRange`] handles, which release their banks when dropped. This
+//! allows keeping e.g. weights resident in DLA's memory while other buffers come and go.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! let weights = BankRange::allocate(kernels.get_size())?;
+//! for input in inputs {
+//!     let input_banks = BankRange::allocate(input.get_size())?;
+//!     ...
+//! } // input_banks is freed here, weights stay allocated
+//! ```
+use crate::mmap::MEMORY_BANK_SIZE;
+use crate::{DlaError, MemoryBank};
+use core::sync::atomic::{AtomicU16, Ordering};
+
+/// Number of memory banks in DLA
+pub const NUM_BANKS: usize = 16;
+
+/// Bitmap of allocated banks, bit N is set when bank N is in use
+static ALLOCATED: AtomicU16 = AtomicU16::new(0);
+
+/// Consecutive memory banks reserved for a buffer, freed on drop
+pub struct BankRange {
+    start: MemoryBank,
+    len: usize,
+}
+
+impl BankRange {
+    /// Allocates enough consecutive banks to hold `bytes` bytes
+    ///
+    /// # Errors
+    /// - [`DlaError::BankOverflow`] if there aren't enough consecutive free banks.
+    pub fn allocate(bytes: usize) -> Result<Self, DlaError> {
+        Self::allocate_banks(bytes.div_ceil(MEMORY_BANK_SIZE))
+    }
+
+    /// Allocates `count` consecutive banks
+    ///
+    /// # Errors
+    /// - [`DlaError::BankOverflow`] if there aren't `count` consecutive free banks.
+    pub fn allocate_banks(count: usize) -> Result<Self, DlaError> {
+        // Zero sized buffers still need a valid bank address
+        let count = count.max(1);
+        if count > NUM_BANKS {
+            return Err(DlaError::BankOverflow);
+        }
+        let mask = range_mask(0, count);
+
+        let mut allocated = ALLOCATED.load(Ordering::Acquire);
+        loop {
+            let start = (0..=NUM_BANKS - count)
+                .find(|&start| allocated & (mask << start) == 0)
+                .ok_or(DlaError::BankOverflow)?;
+            match ALLOCATED.compare_exchange_weak(
+                allocated,
+                allocated | (mask << start),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    return Ok(BankRange {
+                        start: MemoryBank::Bank0
+                            .checked_add(start)
+                            .ok_or(DlaError::BankOverflow)?,
+                        len: count,
+                    })
+                }
+                Err(current) => allocated = current,
+            }
+        }
+    }
+
+    /// Returns the first bank of the range
+    pub fn start(&self) -> MemoryBank {
+        self.start
+    }
+
+    /// Returns the number of banks in the range
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the range contains no banks, which never happens for allocated ranges
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes the range can hold
+    pub fn size(&self) -> usize {
+        self.len * MEMORY_BANK_SIZE
+    }
+
+    /// Returns the absolute address of the first bank of the range
+    pub fn addr(&self) -> usize {
+        self.start.addr()
+    }
+}
+
+impl Drop for BankRange {
+    fn drop(&mut self) {
+        let mask = range_mask(usize::from(self.start), self.len);
+        ALLOCATED.fetch_and(!mask, Ordering::AcqRel);
+    }
+}
+
+/// Returns the number of banks that are currently not allocated
+pub fn free_banks() -> usize {
+    ALLOCATED.load(Ordering::Acquire).count_zeros() as usize
+}
+
+/// Returns the length of the longest run of consecutive free banks
+pub fn largest_free_range() -> usize {
+    let allocated = ALLOCATED.load(Ordering::Acquire);
+    let mut longest = 0;
+    let mut current = 0;
+    for bank in 0..NUM_BANKS {
+        if allocated & (1 << bank) == 0 {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// Bitmask covering `len` banks starting from bank `start`
+fn range_mask(start: usize, len: usize) -> u16 {
+    (((1u32 << len) - 1) << start) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::lock;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn banks_are_freed_on_drop() {
+        let _lock = lock();
+        let weights = BankRange::allocate(3 * MEMORY_BANK_SIZE).unwrap();
+        assert_eq!(weights.len(), 3);
+        {
+            let input = BankRange::allocate(MEMORY_BANK_SIZE + 1).unwrap();
+            assert_eq!(input.len(), 2);
+            assert_eq!(usize::from(input.start()), 3);
+            assert_eq!(free_banks(), NUM_BANKS - 5);
+        }
+        assert_eq!(free_banks(), NUM_BANKS - 3);
+        drop(weights);
+        assert_eq!(free_banks(), NUM_BANKS);
+    }
+
+    #[test]
+    fn exhausted_banks_overflow() {
+        let _lock = lock();
+        assert_eq!(
+            BankRange::allocate_banks(NUM_BANKS + 1).err(),
+            Some(DlaError::BankOverflow)
+        );
+        let all = BankRange::allocate(NUM_BANKS * MEMORY_BANK_SIZE).unwrap();
+        assert_eq!(free_banks(), 0);
+        assert_eq!(BankRange::allocate(1).err(), Some(DlaError::BankOverflow));
+        drop(all);
+        assert!(BankRange::allocate(1).is_ok());
+    }
+
+    #[test]
+    fn fragmented_banks_overflow() {
+        let _lock = lock();
+        let mut banks: Vec<_> = (0..NUM_BANKS)
+            .map(|_| BankRange::allocate_banks(1).unwrap())
+            .collect();
+        // Free every other bank
+        let mut bank = 0usize;
+        banks.retain(|_| {
+            bank += 1;
+            bank.is_multiple_of(2)
+        });
+        assert_eq!(free_banks(), NUM_BANKS / 2);
+        assert_eq!(largest_free_range(), 1);
+        assert_eq!(
+            BankRange::allocate_banks(2).err(),
+            Some(DlaError::BankOverflow)
+        );
+
+        // Freeing a neighbour joins the free banks around it
+        banks.remove(0);
+        assert_eq!(largest_free_range(), 3);
+        let joined = BankRange::allocate_banks(3).unwrap();
+        assert_eq!(usize::from(joined.start()), 0);
+    }
+
+    #[test]
+    fn zero_sized_request_gets_a_bank() {
+        let _lock = lock();
+        let empty = BankRange::allocate(0).unwrap();
+        assert_eq!(empty.len(), 1);
+        assert!(!empty.is_empty());
+        assert_eq!(empty.size(), MEMORY_BANK_SIZE);
+        let empty = BankRange::allocate_banks(0).unwrap();
+        assert_eq!(empty.len(), 1);
+        assert_eq!(free_banks(), NUM_BANKS - 2);
+    }
+}
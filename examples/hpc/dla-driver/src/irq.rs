@@ -0,0 +1,127 @@
+//! Interrupt driven completion of DLA layers
+//!
+//! DLA raises PLIC source [`Interrupt::Dla`] once a layer has been calculated, and keeps it raised
+//! until the handshake has been completed. Instead of busy-waiting on the handshake, the hart
+//! sleeps in [`Dla::wait_handshake_irq`] until the interrupt or a periodic timer wake-up.
+//!
+//! The application owns the `MachineExternal` handler. It must forward DLA's interrupt to
+//! [`on_interrupt`] and enable interrupts globally:
+//!
+//! ```
+//! #[export_name = "MachineExternal"]
+//! fn external_interrupt() {
+//!     if let Some(id) = PLIC::ctx0().claim().claim::<Interrupt>() {
+//!         if matches!(id, Interrupt::Dla) {
+//!             dla_driver::on_interrupt();
+//!         }
+//!         PLIC::ctx0().claim().complete(id);
+//!     }
+//! }
+//! ```
+//!
+//! [`on_interrupt`] also wakes up the task awaiting a [`crate::job::DlaJob`].
+//!
+//! The PLIC driver in headsail-bsp only exists for the VP, so feature `irq` implies `vp`.
+//! Interrupts are routed to hart 0.
+use crate::backend::RegisterAccess;
+use crate::{Dla, DlaError};
+use core::cell::UnsafeCell;
+use core::task::Waker;
+use headsail_bsp::riscv::register::mie;
+use headsail_bsp::{riscv, Interrupt, Priority, CLINT, PLIC};
+
+/// Longest time in `mtime` ticks the hart sleeps between handshake checks, in case the interrupt
+/// is not forwarded to [`on_interrupt`]
+const WAKEUP_TICKS: u64 = 10_000;
+
+/// Waker of the task awaiting the running layer
+struct WakerSlot(UnsafeCell<Option<Waker>>);
+
+// SAFETY: The slot is only accessed with interrupts disabled on hart 0
+unsafe impl Sync for WakerSlot {}
+
+static WAKER: WakerSlot = WakerSlot(UnsafeCell::new(None));
+
+/// Handles DLA's interrupt, to be called from the application's `MachineExternal` handler
+///
+/// DLA keeps the interrupt raised until the handshake has been completed, so the source is masked
+/// here and unmasked by the next handshake check. DLA itself is not touched, as the layer may
+/// still be handed over by its owner.
+pub fn on_interrupt() {
+    PLIC::ctx0().enables().disable(Interrupt::Dla);
+    if let Some(waker) = riscv::interrupt::free(|| unsafe { (*WAKER.0.get()).take() }) {
+        waker.wake();
+    }
+}
+
+/// Registers `waker` to be woken up by the next DLA interrupt
+pub(crate) fn register_waker(waker: &Waker) {
+    riscv::interrupt::free(|| unsafe {
+        let slot = &mut *WAKER.0.get();
+        if !slot.as_ref().is_some_and(|old| old.will_wake(waker)) {
+            *slot = Some(waker.clone());
+        }
+    });
+}
+
+impl Dla {
+    /// Enables DLA interrupt in PLIC for hart 0 and machine external interrupts on current hart
+    ///
+    /// Interrupts are not enabled globally, as the application owns the interrupt handler.
+    pub(crate) fn enable_irq(&self) {
+        unsafe {
+            PLIC::priorities().set_priority(Interrupt::Dla, Priority::P7);
+            riscv::register::mie::set_mext();
+        }
+    }
+}
+
+impl<B: RegisterAccess> Dla<B> {
+    /// Checks whether the running layer has been completed, unmasking DLA interrupt if not
+    pub(crate) fn poll_handshake_irq(&self) -> bool {
+        if self.handle_handshake() {
+            return true;
+        }
+        unsafe { PLIC::ctx0().enables().enable(Interrupt::Dla) };
+        false
+    }
+
+    /// Sleeps until the running layer has been completed, checking the handshake at most
+    /// `max_polls` times
+    pub(crate) fn wait_handshake_irq(&self, max_polls: usize) -> Result<(), DlaError> {
+        for _ in 0..max_polls {
+            // Interrupts are masked while checking, so that the completion can't slip in between
+            // the check and `wfi`. A pending interrupt wakes the hart up regardless of the mask
+            // and the handler runs as soon as the mask is lifted.
+            let done = riscv::interrupt::free(|| {
+                if self.poll_handshake_irq() {
+                    return true;
+                }
+                sleep(WAKEUP_TICKS);
+                false
+            });
+            if done {
+                return Ok(());
+            }
+        }
+        Err(DlaError::Timeout)
+    }
+}
+
+/// Sleeps until an interrupt is pending or `ticks` of `mtime` have passed
+///
+/// Borrows hart 0's timer compare register and restores it afterwards. Must be called with
+/// interrupts masked, so that the timer wakes the hart up without trapping.
+fn sleep(ticks: u64) {
+    let mtimecmp = CLINT::mtimecmp0();
+    let previous = mtimecmp.read();
+    let timer_enabled = mie::read().mtimer();
+
+    mtimecmp.write(CLINT::mtime().read().wrapping_add(ticks));
+    unsafe { mie::set_mtimer() };
+    riscv::asm::wfi();
+    if !timer_enabled {
+        unsafe { mie::clear_mtimer() };
+    }
+    mtimecmp.write(previous);
+}
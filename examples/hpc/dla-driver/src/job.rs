@@ -0,0 +1,297 @@
+//! Non-blocking execution of DLA layers
+use crate::backend::{Mmio, RegisterAccess};
+use crate::bank::BankRange;
+use crate::layers::DlaOutput;
+use crate::stats::{LayerStats, StatsSnapshot};
+use crate::tensor3::{Order3, Tensor3};
+use crate::{Dla, DlaError, DEFAULT_HANDSHAKE_TIMEOUT};
+use alloc::vec::Vec;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+
+/// Set while a layer is running on DLA, as DLA calculates a single layer at a time
+static BUSY: AtomicBool = AtomicBool::new(false);
+
+/// Reservation of DLA for running a single layer, released on drop
+pub(crate) struct Reservation(());
+
+impl Reservation {
+    /// Reserves DLA for a layer
+    ///
+    /// # Errors
+    /// - [`DlaError::Busy`] if DLA is still reserved for another layer, e.g. by an unfinished
+    ///   [`DlaJob`].
+    pub(crate) fn acquire() -> Result<Self, DlaError> {
+        if BUSY.swap(true, Ordering::Acquire) {
+            return Err(DlaError::Busy);
+        }
+        Ok(Reservation(()))
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        BUSY.store(false, Ordering::Release);
+    }
+}
+
+/// Handle to a layer that has been submitted to DLA
+///
+/// Returned by [`crate::layers::submit_conv2d`]. The CPU is free to do other work while the layer
+/// is being calculated. The result can be collected with [`DlaJob::wait`], by polling
+/// [`DlaJob::is_done`] or by awaiting the job.
+///
+/// DLA calculates a single layer at a time, so the job has to be completed before the next layer
+/// is submitted. Layers submitted or run meanwhile fail with [`DlaError::Busy`]. The memory banks
+/// used by the layer, as well as bias DLA reads from outside the banks, stay allocated until the
+/// job is dropped. Dropping an unfinished job waits for DLA to complete the layer, so that DLA
+/// doesn't write into banks that have been given to the next layer.
+///
+/// Awaiting the job busy-polls DLA, i.e. the job wakes itself up whenever it's pending. With
+/// feature `irq`, the job is woken up by [`crate::on_interrupt`] instead.
+pub struct DlaJob<'a, T, B: RegisterAccess = Mmio> {
+    dla: &'a Dla<B>,
+    _reservation: Reservation,
+    _banks: Vec<BankRange>,
+    _bias: Option<Vec<i16>>,
+    kernels: usize,
+    height: usize,
+    width: usize,
+    done: bool,
+    start: StatsSnapshot,
+    stats: Option<LayerStats>,
+    _output: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: DlaOutput + Clone, B: RegisterAccess> DlaJob<'a, T, B> {
+    /// Creates a handle for a layer that has been started with output of given dimensions
+    ///
+    /// `bias` is kept alive for DLA if it isn't in `banks`. `start` is taken right before the layer
+    /// was started.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        dla: &'a Dla<B>,
+        reservation: Reservation,
+        banks: Vec<BankRange>,
+        kernels: usize,
+        height: usize,
+        width: usize,
+        bias: Option<Vec<i16>>,
+        start: StatsSnapshot,
+    ) -> Self {
+        DlaJob {
+            dla,
+            _reservation: reservation,
+            _banks: banks,
+            _bias: bias,
+            kernels,
+            height,
+            width,
+            done: false,
+            start,
+            stats: None,
+            _output: PhantomData,
+        }
+    }
+
+    /// Checks without blocking whether DLA has completed the layer
+    pub fn is_done(&mut self) -> bool {
+        if !self.done && self.dla.poll_layer() {
+            self.complete();
+        }
+        self.done
+    }
+
+    /// Blocks until DLA has completed the layer and returns its output
+    pub fn wait(self) -> Result<Tensor3<T>, DlaError> {
+        self.wait_with_stats().map(|(output, _)| output)
+    }
+
+    /// Blocks until DLA has completed the layer and returns its output and statistics
+    pub fn wait_with_stats(mut self) -> Result<(Tensor3<T>, LayerStats), DlaError> {
+        self.finish()?;
+        Ok((self.read_output()?, self.stats.unwrap_or_default()))
+    }
+
+    /// Blocks until DLA has completed the layer and reads its output into `output` in HWC order
+    ///
+    /// # Errors
+    /// - [`DlaError::DimensionMismatch`] if `output` doesn't have exactly the size of the layer
+    ///   output
+    pub fn wait_into(mut self, output: &mut [T]) -> Result<(), DlaError> {
+        if output.len() != self.kernels * self.height * self.width {
+            return Err(DlaError::DimensionMismatch);
+        }
+        self.finish()?;
+        T::read_output_into(self.dla, output)
+    }
+
+    /// Returns statistics of the layer once it has been completed
+    pub fn stats(&self) -> Option<LayerStats> {
+        self.stats
+    }
+
+    /// Blocks until DLA has completed the layer
+    fn finish(&mut self) -> Result<(), DlaError> {
+        if !self.done {
+            if let Err(err) = self.dla.wait_layer(DEFAULT_HANDSHAKE_TIMEOUT) {
+                // Don't wait for the layer again on drop
+                self.done = true;
+                return Err(err);
+            }
+            self.complete();
+        }
+        Ok(())
+    }
+
+    /// Marks the layer completed and collects its statistics
+    fn complete(&mut self) {
+        self.done = true;
+        self.stats = Some(self.start.elapsed(self.dla));
+    }
+
+    /// Reads output of a completed layer from DLA's output banks
+    fn read_output(&self) -> Result<Tensor3<T>, DlaError> {
+        let output_buffer = T::read_output(self.dla, self.kernels * self.height * self.width)?;
+
+        Tensor3::from_data_buffer(
+            self.kernels,
+            self.height,
+            self.width,
+            output_buffer,
+            Order3::HWC, // NOTE: (20240610 vaino-waltteri.granat@tuni.fi) This might not be true on ASIC
+        )
+        .map_err(|_| DlaError::DimensionMismatch)
+    }
+}
+
+impl<T: DlaOutput + Clone, B: RegisterAccess> Future for DlaJob<'_, T, B> {
+    type Output = Result<Tensor3<T>, DlaError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let job = self.get_mut();
+        // Waker is registered before checking, so that an interrupt right after the check wakes
+        // the job up
+        #[cfg(all(target_arch = "riscv64", feature = "irq"))]
+        let woken_by_irq = job.dla.backend().has_irq() && {
+            crate::irq::register_waker(cx.waker());
+            true
+        };
+        #[cfg(not(all(target_arch = "riscv64", feature = "irq")))]
+        let woken_by_irq = false;
+
+        if job.is_done() {
+            Poll::Ready(job.read_output())
+        } else {
+            if !woken_by_irq {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, B: RegisterAccess> Drop for DlaJob<'_, T, B> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.dla.wait_layer(DEFAULT_HANDSHAKE_TIMEOUT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{submit_conv2d, try_conv2d};
+    use crate::reference;
+    use crate::test_utils::{self, assert_tensor_eq, rng, simulated};
+
+    #[test]
+    fn layers_are_rejected_while_job_is_unfinished() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 2, 5, 5);
+        let kernels = test_utils::tensor4(&mut rng, 3, 2, 3, 3);
+
+        let job = submit_conv2d::<i8, _>(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let busy = try_conv2d::<i8, _>(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(busy.unwrap_err(), DlaError::Busy);
+        assert_eq!(
+            submit_conv2d::<i8, _>(
+                &dla,
+                input.clone(),
+                kernels.clone(),
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+                None
+            )
+            .err(),
+            Some(DlaError::Busy)
+        );
+
+        let expected = reference::conv2d_bias_relu(
+            &input, &kernels, None, false, None, None, None, None, None,
+        )
+        .unwrap();
+        assert_tensor_eq(&job.wait().unwrap(), &expected);
+
+        let output = try_conv2d::<i8, _>(&dla, input, kernels, None, None, None, None, None, None);
+        assert_tensor_eq(&output.unwrap(), &expected);
+    }
+
+    #[test]
+    fn dropping_unfinished_job_releases_dla() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 2, 4, 4);
+        let kernels = test_utils::tensor4(&mut rng, 2, 2, 2, 2);
+
+        let job = submit_conv2d::<i8, _>(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        drop(job);
+
+        assert!(
+            try_conv2d::<i8, _>(&dla, input, kernels, None, None, None, None, None, None).is_ok()
+        );
+    }
+}
@@ -1,217 +1,668 @@
-use crate::tensor3::{Order3, Tensor3};
-use crate::tensor4::{Order4, Tensor4};
-use crate::{Dla, InputSize, KernelSize, LayerConfig, Padding, SimdBitMode, Stride};
+use crate::backend::RegisterAccess;
+use crate::bank::BankRange;
+use crate::job::{DlaJob, Reservation};
+use crate::quant::{AddRequantization, MulRequantization, ADD_LEFT_SHIFT, MAX_PP_CLIP};
+use crate::reference;
+use crate::simd::{pack_iter, packed_len, Packed};
+use crate::tensor3::{Order3, Tensor3, Tensor3View};
+use crate::tensor4::{Order4, Tensor4, Tensor4View};
+use crate::{
+    dla_addr, BankSelection, Dilation, Dla, DlaError, InputSize, KernelSize, LayerConfig,
+    OutputPadding, OutputWidth, Padding, PoolMode, Pooling, PpConfig, SimdBitMode, Stride,
+    DEFAULT_MAC_CLIP, DEFAULT_PADDING, DEFAULT_PP_CLIP, DEFAULT_STRIDE, MAX_POOL_SIZE,
+};
 use alloc::vec::Vec;
+use core::mem::size_of;
 
-use crate::utils::{calculate_conv2d_out_param_dim, get_banks_for_layer};
+use crate::utils::{
+    calculate_conv2d_out_param_dim, calculate_pp_tile_height, calculate_tile_input_range,
+    calculate_tile_size, check_stride, dilate_kernels, rescale_bias, zero_insert,
+};
+
+/// Returns the address DLA can read bias from without copying, if there is one
+fn external_bias_addr<B: RegisterAccess>(dla: &Dla<B>, bias: &[i16]) -> Option<u32> {
+    if !dla.backend().has_external_bias() {
+        return None;
+    }
+    dla_addr(bias.as_ptr() as usize).ok()
+}
 
 // Define a trait for output handling
-pub trait DlaOutput: Sized {
-    fn read_output(dla: &Dla, size: usize) -> Vec<Self>;
+pub trait DlaOutput: Sized + Default {
+    /// Element width DLA is configured to write for this type
+    const WIDTH: OutputWidth;
+
+    fn read_output<B: RegisterAccess>(dla: &Dla<B>, size: usize) -> Result<Vec<Self>, DlaError>;
+
+    /// Fills `output` from DLA's output banks
+    fn read_output_into<B: RegisterAccess>(
+        dla: &Dla<B>,
+        output: &mut [Self],
+    ) -> Result<(), DlaError>;
+
+    /// Converts a value after bias and ReLU to the output DLA writes with `pp_clip`, rounding the
+    /// clipped value if `pp_rounding` is set
+    fn from_post_processed(value: i32, pp_clip: u32, pp_rounding: bool) -> Self;
 }
 
 // Implement the trait for i8
 impl DlaOutput for i8 {
-    fn read_output(dla: &Dla, size: usize) -> Vec<Self> {
+    const WIDTH: OutputWidth = OutputWidth::EightBits;
+
+    fn read_output<B: RegisterAccess>(dla: &Dla<B>, size: usize) -> Result<Vec<Self>, DlaError> {
         dla.read_output_i8(size)
     }
+
+    fn read_output_into<B: RegisterAccess>(
+        dla: &Dla<B>,
+        output: &mut [Self],
+    ) -> Result<(), DlaError> {
+        dla.read_output_i8_into(output)
+    }
+
+    fn from_post_processed(value: i32, pp_clip: u32, pp_rounding: bool) -> Self {
+        reference::saturate_output(reference::pp_clip(
+            reference::saturate(value, 16),
+            pp_clip,
+            pp_rounding,
+        ))
+    }
 }
 
 // Implement the trait for i16
 impl DlaOutput for i16 {
-    fn read_output(dla: &Dla, size: usize) -> Vec<Self> {
+    const WIDTH: OutputWidth = OutputWidth::SixteenBits;
+
+    fn read_output<B: RegisterAccess>(dla: &Dla<B>, size: usize) -> Result<Vec<Self>, DlaError> {
         dla.read_output_i16(size)
     }
+
+    fn read_output_into<B: RegisterAccess>(
+        dla: &Dla<B>,
+        output: &mut [Self],
+    ) -> Result<(), DlaError> {
+        dla.read_output_i16_into(output)
+    }
+
+    fn from_post_processed(value: i32, pp_clip: u32, pp_rounding: bool) -> Self {
+        reference::pp_clip(reference::saturate(value, 16), pp_clip, pp_rounding) as i16
+    }
 }
 
 // Implement the trait for i32
 impl DlaOutput for i32 {
-    fn read_output(dla: &Dla, size: usize) -> Vec<Self> {
+    const WIDTH: OutputWidth = OutputWidth::ThirtyTwoBits;
+
+    fn read_output<B: RegisterAccess>(dla: &Dla<B>, size: usize) -> Result<Vec<Self>, DlaError> {
         dla.read_output_i32(size)
     }
+
+    fn read_output_into<B: RegisterAccess>(
+        dla: &Dla<B>,
+        output: &mut [Self],
+    ) -> Result<(), DlaError> {
+        dla.read_output_i32_into(output)
+    }
+
+    fn from_post_processed(value: i32, _pp_clip: u32, _pp_rounding: bool) -> Self {
+        value
+    }
 }
 
+/// Largest number of channels the input and kernel size registers can hold
+const MAX_CHANNELS: usize = 4096;
+
+/// Largest kernel height the kernel size register can hold
+const MAX_KERNEL_HEIGHT: usize = 16;
+
+/// Largest width the input size register can hold
+const MAX_INPUT_WIDTH: usize = 512;
+
+/// Largest number of rows the post-processor input size register can hold
+const MAX_PP_INPUT_HEIGHT: usize = 512;
+
+/// Performs a fully connected layer with DLA.
+///
+/// Panicking version of [`try_dense`].
 pub fn dense(outputs: usize, input: Tensor3<i8>, weights: Vec<i8>) -> Vec<i32> {
-    // Build kernels to produce 1 to 1 mac operation
-    let kernels_wrap = Tensor4::from_data_buffer(
+    try_dense(&Dla::new(), outputs, input, weights).unwrap()
+}
+
+/// Performs a fully connected layer with DLA.
+///
+/// # Arguments
+/// - `outputs`: Number of output values.
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input.
+/// - `weights`: Weights in KCHW order, `outputs` times the size of `input`.
+///
+/// # Returns
+/// - Raw MAC outputs, see [`try_linear`] for bias, ReLU and clipping.
+///
+/// # Errors
+/// - See [`try_linear`].
+pub fn try_dense<B: RegisterAccess>(
+    dla: &Dla<B>,
+    outputs: usize,
+    input: Tensor3<i8>,
+    weights: Vec<i8>,
+) -> Result<Vec<i32>, DlaError> {
+    try_linear(
+        dla,
+        input.to_buffer_with_order(Order3::CHW),
+        weights,
+        None,
         outputs,
-        input.channels(),
-        input.height(),
-        input.width(),
+        false,
+        None,
+        None,
+    )
+}
+
+/// Performs a fully connected layer with optional Bias and ReLU with DLA.
+///
+/// Panicking version of [`try_linear`].
+pub fn linear<T: DlaOutput + Clone>(
+    inputs: Vec<i8>,
+    weights: Vec<i8>,
+    bias: Option<Vec<i16>>,
+    out_features: usize,
+    relu: bool,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Vec<T> {
+    try_linear(
+        &Dla::new(),
+        inputs,
         weights,
-        Order4::KCHW,
-    );
+        bias,
+        out_features,
+        relu,
+        mac_clip,
+        pp_clip,
+    )
+    .unwrap()
+}
+
+/// Performs a fully connected layer with optional Bias and ReLU with DLA.
+///
+/// Up to 512 input vectors are calculated in the same run as pixels of a 1x1 convolution, so the
+/// weights are written to DLA only once per 512 vectors. Weights that don't fit into the memory
+/// banks are split by output features into several runs.
+///
+/// Input features that don't fit into a single run are split into runs of at most 4096 features.
+/// Their raw MAC outputs are summed up, and MAC clip, bias, ReLU and PP clip are applied on CPU.
+/// This needs 32-bit outputs, which only the VP writes, see [`RegisterAccess::has_out32`].
+///
+/// # Arguments
+/// - `inputs`: One or more input vectors of `in_features` values back to back.
+/// - `weights`: Weight matrix of `out_features` rows of `in_features` values.
+/// - `bias`: An optional vector of 16-bit signed integers containing biases for each output feature.
+/// - `out_features`: Number of output values per input vector.
+/// - `relu`: Whether ReLU is applied to the outputs.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after MAC operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+///
+/// # Returns
+/// - `out_features` values of type `T` for each input vector, back to back.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if `weights` is not `out_features` rows, `inputs` are not
+///   whole vectors or bias length doesn't match `out_features`.
+/// - [`DlaError::Unsupported`] if input features need to be split and the backend doesn't write
+///   32-bit outputs.
+/// - See [`try_conv2d_bias`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_linear<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    inputs: Vec<i8>,
+    weights: Vec<i8>,
+    bias: Option<Vec<i16>>,
+    out_features: usize,
+    relu: bool,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Result<Vec<T>, DlaError> {
+    if out_features == 0
+        || weights.is_empty()
+        || !weights.len().is_multiple_of(out_features)
+        || bias.as_ref().is_some_and(|b| b.len() != out_features)
+    {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let in_features = weights.len() / out_features;
+    if inputs.is_empty() || !inputs.len().is_multiple_of(in_features) {
+        return Err(DlaError::DimensionMismatch);
+    }
+
+    let mut output = Vec::with_capacity(inputs.len() / in_features * out_features);
+    // Input vectors are the columns of the input tensor, which is at most `MAX_INPUT_WIDTH` wide
+    for vectors in inputs.chunks(MAX_INPUT_WIDTH * in_features) {
+        output.extend(linear_vectors(
+            dla,
+            vectors,
+            &weights,
+            bias.as_deref(),
+            out_features,
+            relu,
+            mac_clip,
+            pp_clip,
+        )?);
+    }
+    Ok(output)
+}
+
+/// Calculates a fully connected layer for at most `MAX_INPUT_WIDTH` input vectors
+///
+/// Features that fit into a single run are calculated by DLA from start to end. Otherwise they
+/// are split into runs of raw MAC outputs, which are summed up and post-processed on CPU.
+#[allow(clippy::too_many_arguments)]
+fn linear_vectors<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    inputs: &[i8],
+    weights: &[i8],
+    bias: Option<&[i16]>,
+    out_features: usize,
+    relu: bool,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Result<Vec<T>, DlaError> {
+    let in_features = weights.len() / out_features;
+    let batch = inputs.len() / in_features;
+
+    // Features that don't fit into channels are spread over the height of input and kernels
+    let height = (1..=MAX_KERNEL_HEIGHT)
+        .find(|&h| in_features.is_multiple_of(h) && in_features / h <= MAX_CHANNELS);
+    if let Some(height) = height {
+        if let Some(rows) = linear_rows::<T>(
+            in_features / height,
+            height,
+            batch,
+            out_features,
+            bias.is_some(),
+        ) {
+            return linear_features(
+                dla,
+                inputs,
+                weights,
+                bias,
+                out_features,
+                0..in_features,
+                height,
+                rows,
+                relu,
+                mac_clip,
+                pp_clip,
+            );
+        }
+    }
 
-    let kernels = match kernels_wrap {
-        Ok(kernels) => kernels,
-        Err(_e) => return [0].to_vec(),
+    // Partial sums must not be clipped before they are added up
+    if !dla.backend().has_out32() {
+        return Err(DlaError::Unsupported);
+    }
+    let mac_clip = mac_clip.unwrap_or(DEFAULT_MAC_CLIP);
+    let pp_clip = pp_clip.unwrap_or(DEFAULT_PP_CLIP);
+    if mac_clip > 21 {
+        return Err(DlaError::InvalidClip(mac_clip));
+    }
+    if pp_clip > 0x1F {
+        return Err(DlaError::InvalidClip(pp_clip));
+    }
+
+    let mut sums = vec![0i32; batch * out_features];
+    for first in (0..in_features).step_by(MAX_CHANNELS) {
+        let features = first..core::cmp::min(first + MAX_CHANNELS, in_features);
+        let rows = linear_rows::<i32>(features.len(), 1, batch, out_features, false)
+            .ok_or(DlaError::BankOverflow)?;
+        let partial: Vec<i32> = linear_features(
+            dla,
+            inputs,
+            weights,
+            None,
+            out_features,
+            features,
+            1,
+            rows,
+            false,
+            Some(0),
+            None,
+        )?;
+        for (sum, value) in sums.iter_mut().zip(partial) {
+            *sum = sum.wrapping_add(value);
+        }
+    }
+
+    let (mac_sat_min, mac_sat_max) = dla.layer_mac_saturation();
+    let pp_rounding = dla.layer_pp_rounding();
+    Ok(sums
+        .into_iter()
+        .enumerate()
+        .map(|(idx, sum)| {
+            let mut value = reference::mac_clip(sum.clamp(mac_sat_min, mac_sat_max), mac_clip);
+            if let Some(bias) = bias {
+                value = value.wrapping_add(bias[idx % out_features] as i32);
+            }
+            if relu {
+                value = value.max(0);
+            }
+            T::from_post_processed(value, pp_clip, pp_rounding)
+        })
+        .collect())
+}
+
+/// Finds how many rows of weights can be calculated in a single run
+fn linear_rows<T>(
+    channels: usize,
+    height: usize,
+    batch: usize,
+    out_features: usize,
+    bias: bool,
+) -> Option<usize> {
+    (1..=core::cmp::min(out_features, MAX_CHANNELS))
+        .rev()
+        .find(|&rows| {
+            calculate_tile_size(
+                (channels, height, batch),
+                (rows, height, 1),
+                (1, batch),
+                None,
+                size_of::<T>(),
+                bias,
+            )
+            .is_ok()
+        })
+}
+
+/// Calculates `features` of each input vector against all rows of weights, `rows` rows at a time
+#[allow(clippy::too_many_arguments)]
+fn linear_features<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    inputs: &[i8],
+    weights: &[i8],
+    bias: Option<&[i16]>,
+    out_features: usize,
+    features: core::ops::Range<usize>,
+    height: usize,
+    rows: usize,
+    relu: bool,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Result<Vec<T>, DlaError> {
+    let in_features = weights.len() / out_features;
+    let batch = inputs.len() / in_features;
+    let channels = features.len() / height;
+    let select = |values: &[i8]| -> Vec<i8> {
+        values
+            .chunks_exact(in_features)
+            .flat_map(|vector| &vector[features.clone()])
+            .copied()
+            .collect()
     };
 
-    let output = conv2d(input, kernels, None, None, None, None, None);
-    output.to_buffer()
+    let input =
+        Tensor3::from_data_buffer(channels, height, batch, select(inputs), Order3::WCH).unwrap();
+
+    let mut chunks = Vec::new();
+    for first in (0..out_features).step_by(rows) {
+        let last = core::cmp::min(first + rows, out_features);
+        let kernels = Tensor4::from_data_buffer(
+            last - first,
+            channels,
+            height,
+            1,
+            select(&weights[first * in_features..last * in_features]),
+            Order4::KCHW,
+        )
+        .unwrap();
+
+        let result: Tensor3<T> = run_tiles(
+            dla,
+            input.view(),
+            kernels.view(),
+            bias.map(|bias| bias[first..last].to_vec()),
+            bias.is_some(),
+            relu,
+            None,
+            None,
+            mac_clip,
+            pp_clip,
+            None,
+        )?;
+        // Output has a single row in HWC order, i.e. the features of each input back to back
+        chunks.push(result.to_buffer_with_order(Order3::HWC));
+    }
+
+    let mut output = Vec::with_capacity(batch * out_features);
+    for vector in 0..batch {
+        for chunk in &chunks {
+            let features = chunk.len() / batch;
+            output.extend_from_slice(&chunk[vector * features..(vector + 1) * features]);
+        }
+    }
+    Ok(output)
 }
 
 /// Performs a 2D convolution operation with DLA.
 ///
-/// # Arguments
-/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
-/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
-/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
-/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
-/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
-/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processign pipeline.
-/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
-///
-/// # Returns
-/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
-/// ```
+/// Panicking version of [`try_conv2d`].
+#[allow(clippy::too_many_arguments)]
 pub fn conv2d<T: DlaOutput + Clone>(
     input: Tensor3<i8>,
     kernels: Tensor4<i8>,
     padding: Option<Padding>,
     stride: Option<Stride>,
+    dilation: Option<Dilation>,
     mac_clip: Option<u32>,
     pp_clip: Option<u32>,
     simd_mode: Option<SimdBitMode>,
 ) -> Tensor3<T> {
-    run_layers(
-        input, kernels, None, false, false, padding, stride, mac_clip, pp_clip, simd_mode,
-    )
-}
-
-pub fn relu(input: Tensor3<i8>, pp_clip: Option<u32>) -> Tensor3<i8> {
-    let kernel_buf = vec![1; input.get_size() * input.channels()]; // 1 filled kernels for constant conv2d
-    let kernels: Tensor4<i8> = Tensor4::from_data_buffer(
-        input.channels(),
-        input.channels(),
-        input.height(),
-        input.width(),
-        kernel_buf,
-        Order4::HWKC,
-    )
-    .unwrap();
-
-    run_layers(
+    try_conv2d(
+        &Dla::new(),
         input,
         kernels,
-        None,
-        false,
-        true,
-        None,
-        None,
-        Some(0),
+        padding,
+        stride,
+        dilation,
+        mac_clip,
         pp_clip,
-        Some(SimdBitMode::EightBits),
+        simd_mode,
     )
+    .unwrap()
 }
 
-pub fn bias(input: Tensor3<i8>, bias: Vec<i16>, pp_clip: Option<u32>) -> Tensor3<i8> {
-    let kernel_buf = vec![1; input.get_size() * input.channels()]; // 1 filled kernels for constant conv2d
-    let kernels: Tensor4<i8> = Tensor4::from_data_buffer(
-        input.channels(),
-        input.channels(),
-        input.height(),
-        input.width(),
-        kernel_buf,
-        Order4::HWKC,
-    )
-    .unwrap();
-
-    run_layers(
-        input,
-        kernels,
-        Some(bias),
-        true,
-        false,
-        None,
-        None,
-        Some(0),
-        pp_clip,
-        Some(SimdBitMode::EightBits),
-    )
-}
-/// Performs a 2D convolution + ReLU operation with DLA.
+/// Performs a 2D convolution operation with DLA.
+///
+/// DLA has no dilation support, so kernels are expanded with zeros between their elements for
+/// dilations above 1, see [`dilate_kernels`].
 ///
 /// # Arguments
 /// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
 /// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
 /// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
 /// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `dilation`: An optional `Dilation` parameter defining the spacing of kernel elements in X and Y directions.
 /// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
-/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processign pipeline.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
 /// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
 ///
 /// # Returns
 /// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
-/// ```
-pub fn conv2d_relu<T: DlaOutput + Clone>(
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if `mac_clip` or `pp_clip` is out of range.
+/// - [`DlaError::BankOverflow`] if even a single output pixel does not fit into DLA's memory banks.
+///   Larger layers are split into spatial tiles.
+/// - [`DlaError::DimensionMismatch`] if input and kernel dimensions are incompatible.
+/// - [`DlaError::InvalidDimension`] if stride or dilation is zero.
+/// - [`DlaError::RegisterFieldOverflow`] if a dimension does not fit into DLA's registers.
+/// - [`DlaError::Timeout`] if DLA does not complete the calculation.
+/// - [`DlaError::Busy`] if DLA is still running the layer of an unfinished [`DlaJob`].
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
     input: Tensor3<i8>,
     kernels: Tensor4<i8>,
     padding: Option<Padding>,
     stride: Option<Stride>,
+    dilation: Option<Dilation>,
     mac_clip: Option<u32>,
     pp_clip: Option<u32>,
     simd_mode: Option<SimdBitMode>,
-) -> Tensor3<T> {
-    run_layers(
-        input, kernels, None, false, true, padding, stride, mac_clip, pp_clip, simd_mode,
+) -> Result<Tensor3<T>, DlaError> {
+    try_conv2d_view(
+        dla,
+        input.view(),
+        kernels.view(),
+        None,
+        false,
+        padding,
+        stride,
+        dilation,
+        mac_clip,
+        pp_clip,
+        simd_mode,
     )
 }
 
-/// Performs a 2D convolution + Bias operation with DLA.
+/// Applies ReLU to a feature map with DLA's post-processor.
+///
+/// Panicking version of [`try_relu`].
+pub fn relu(input: Tensor3<i8>, pp_clip: Option<u32>) -> Tensor3<i8> {
+    try_relu(&Dla::new(), input, pp_clip).unwrap()
+}
+
+/// Applies ReLU to a feature map with DLA's post-processor.
+///
+/// Only the post-processor runs, reading the input straight from the memory banks without the MAC
+/// array.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+///
+/// # Returns
+/// - A 3-dimensional tensor of 8-bit signed integers in the order of the input.
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if `pp_clip` is out of range.
+/// - [`DlaError::BankOverflow`] if a single row of the input and output doesn't fit into DLA's
+///   memory banks.
+pub fn try_relu<B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let mut output = run_pp_tiles(dla, input.view(), None, true, pp_clip)?;
+    output.permute(input.order());
+    Ok(output)
+}
+
+/// Adds bias to each channel of a feature map with DLA's post-processor.
+///
+/// Panicking version of [`try_bias`].
+pub fn bias(input: Tensor3<i8>, bias: Vec<i16>, pp_clip: Option<u32>) -> Tensor3<i8> {
+    try_bias(&Dla::new(), input, bias, pp_clip).unwrap()
+}
+
+/// Adds bias to each channel of a feature map with DLA's post-processor.
+///
+/// Only the post-processor runs, reading the input straight from the memory banks without the MAC
+/// array.
 ///
 /// # Arguments
 /// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
-/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
 /// - `bias`: A vector of 16-bit signed integers containing biases for each channel.
-/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
-/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
-/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
-/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processign pipeline.
-/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
 ///
 /// # Returns
-/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
-/// ```
-pub fn conv2d_bias<T: DlaOutput + Clone>(
+/// - A 3-dimensional tensor of 8-bit signed integers in the order of the input.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if there isn't a bias for each channel.
+/// - See [`try_relu`] for the rest.
+pub fn try_bias<B: RegisterAccess>(
+    dla: &Dla<B>,
     input: Tensor3<i8>,
-    kernels: Tensor4<i8>,
     bias: Vec<i16>,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let mut output = run_pp_tiles(dla, input.view(), Some(bias), false, pp_clip)?;
+    output.permute(input.order());
+    Ok(output)
+}
+
+/// Performs a 2D convolution + ReLU operation with DLA.
+///
+/// Panicking version of [`try_conv2d_relu`].
+pub fn conv2d_relu<T: DlaOutput + Clone>(
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
     padding: Option<Padding>,
     stride: Option<Stride>,
     mac_clip: Option<u32>,
     pp_clip: Option<u32>,
     simd_mode: Option<SimdBitMode>,
 ) -> Tensor3<T> {
-    run_layers(
+    try_conv2d_relu(
+        &Dla::new(),
         input,
         kernels,
-        Some(bias),
-        true,
-        false,
         padding,
         stride,
         mac_clip,
         pp_clip,
         simd_mode,
     )
+    .unwrap()
 }
-/// Performs a 2D convolution + Bias + ReLU operation with DLA.
+
+/// Performs a 2D convolution + ReLU operation with DLA.
 ///
 /// # Arguments
 /// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
 /// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
-/// - `bias`: A vector of 16-bit signed integers containing biases for each channel.
 /// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
 /// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
 /// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
-/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processign pipeline.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
 /// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
 ///
 /// # Returns
 /// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
-/// ```
-pub fn conv2d_bias_relu<T: DlaOutput + Clone>(
+///
+/// # Errors
+/// - See [`try_conv2d`].
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_relu<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    run_tiles(
+        dla,
+        input.view(),
+        kernels.view(),
+        None,
+        false,
+        true,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+}
+
+/// Performs a 2D convolution + Bias operation with DLA.
+///
+/// Panicking version of [`try_conv2d_bias`].
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_bias<T: DlaOutput + Clone>(
     input: Tensor3<i8>,
     kernels: Tensor4<i8>,
     bias: Vec<i16>,
@@ -221,21 +672,21 @@ pub fn conv2d_bias_relu<T: DlaOutput + Clone>(
     pp_clip: Option<u32>,
     simd_mode: Option<SimdBitMode>,
 ) -> Tensor3<T> {
-    run_layers(
+    try_conv2d_bias(
+        &Dla::new(),
         input,
         kernels,
-        Some(bias),
-        true,
-        true,
+        bias,
         padding,
         stride,
         mac_clip,
         pp_clip,
         simd_mode,
     )
+    .unwrap()
 }
 
-/// Performs a 2D grouped convolution + Bias operation with DLA.
+/// Performs a 2D convolution + Bias operation with DLA.
 ///
 /// # Arguments
 /// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
@@ -244,25 +695,369 @@ pub fn conv2d_bias_relu<T: DlaOutput + Clone>(
 /// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
 /// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
 /// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
-/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processign pipeline.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
 /// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
-/// - `groups`: Number of groups used.
 ///
 /// # Returns
 /// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
 ///
-/// # Notes
-/// - The total number of input channels must be divisible by `groups`.
-/// - The total number of kernels must also be divisible by `groups`.
-/// - Padding and stride configurations are applied consistently across all groups.
-///
-///# Example
-/// For an input tensor with 8 channels, kernels with 16 filters, and `groups = 2`:
-/// - The input channels are split into 2 groups of 4 channels each.
-/// - Each group processes its portion with 8 filters (16 filters / 2 groups).
-/// - The final output will have 16 channels (8 channels per group concatenated).
-/// ```
-pub fn grouped_conv2d<T: DlaOutput + Clone>(
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if there is not exactly one bias per kernel.
+/// - [`DlaError::BankOverflow`] if no memory bank is left for bias.
+/// - See [`try_conv2d`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_bias<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Vec<i16>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    run_tiles(
+        dla,
+        input.view(),
+        kernels.view(),
+        Some(bias),
+        true,
+        false,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+}
+
+/// Performs a 2D convolution + Bias + ReLU operation with DLA.
+///
+/// Panicking version of [`try_conv2d_bias_relu`].
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_bias_relu<T: DlaOutput + Clone>(
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Vec<i16>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Tensor3<T> {
+    try_conv2d_bias_relu(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D convolution + Bias + ReLU operation with DLA.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: A vector of 16-bit signed integers containing biases for each channel.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
+///
+/// # Errors
+/// - See [`try_conv2d_bias`].
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_bias_relu<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Vec<i16>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    run_tiles(
+        dla,
+        input.view(),
+        kernels.view(),
+        Some(bias),
+        true,
+        true,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+}
+
+/// Performs a 2D convolution + Bias operation with 32-bit bias with DLA.
+///
+/// Panicking version of [`try_conv2d_bias_i32`].
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_bias_i32<T: DlaOutput + Clone>(
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Vec<i32>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Tensor3<T> {
+    try_conv2d_bias_i32(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D convolution + Bias operation with 32-bit bias with DLA.
+///
+/// DLA adds bias at 16 bits, so bias that doesn't fit is shifted right together with the MAC
+/// results, and the post-processing clip is reduced to match, see [`rescale_bias`]. The output is
+/// scaled as it would be with 16-bit bias and the given clips. With a shift, MAC results are
+/// saturated to 16 bits even if `mac_clip` is 0.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: A vector of 32-bit signed integers containing biases for each channel.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if `pp_clip` is too small to fit the bias into 16 bits.
+/// - See [`try_conv2d_bias`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_bias_i32<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Vec<i32>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    let (bias, mac_clip, pp_clip) = rescale_bias(
+        &bias,
+        mac_clip.unwrap_or(DEFAULT_MAC_CLIP),
+        pp_clip.unwrap_or(DEFAULT_PP_CLIP),
+    )?;
+    run_tiles(
+        dla,
+        input.view(),
+        kernels.view(),
+        Some(bias),
+        true,
+        false,
+        padding,
+        stride,
+        Some(mac_clip),
+        Some(pp_clip),
+        simd_mode,
+    )
+}
+
+/// Performs a low-precision 2D convolution operation with DLA.
+///
+/// Panicking version of [`try_conv2d_packed`].
+pub fn conv2d_packed<P: Packed, T: DlaOutput + Clone>(
+    input: Tensor3<P>,
+    kernels: Tensor4<P>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Tensor3<T> {
+    try_conv2d_packed(
+        &Dla::new(),
+        input,
+        kernels,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+    )
+    .unwrap()
+}
+
+/// Performs a low-precision 2D convolution operation with DLA.
+///
+/// Inputs and kernels are packed into DLA's memory banks and calculated in the SIMD mode of `P`,
+/// e.g. `Tensor3<I4>` in [`SimdBitMode::FourBits`].
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of packed values (`Tensor3<P>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of packed values (`Tensor4<P>`) representing the convolution kernels.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
+///
+/// # Errors
+/// - See [`try_conv2d`].
+pub fn try_conv2d_packed<P: Packed, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<P>,
+    kernels: Tensor4<P>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<T>, DlaError> {
+    run_packed(
+        dla, input, kernels, None, false, padding, stride, mac_clip, pp_clip,
+    )
+}
+
+/// Performs a low-precision 2D convolution + Bias + ReLU operation with DLA.
+///
+/// Panicking version of [`try_conv2d_bias_relu_packed`].
+pub fn conv2d_bias_relu_packed<P: Packed, T: DlaOutput + Clone>(
+    input: Tensor3<P>,
+    kernels: Tensor4<P>,
+    bias: Vec<i16>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Tensor3<T> {
+    try_conv2d_bias_relu_packed(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+    )
+    .unwrap()
+}
+
+/// Performs a low-precision 2D convolution + Bias + ReLU operation with DLA.
+///
+/// See [`try_conv2d_packed`] for how the values are calculated.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of packed values (`Tensor3<P>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of packed values (`Tensor4<P>`) representing the convolution kernels.
+/// - `bias`: A vector of 16-bit signed integers containing biases for each channel.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
+///
+/// # Errors
+/// - See [`try_conv2d_bias`].
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_bias_relu_packed<P: Packed, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<P>,
+    kernels: Tensor4<P>,
+    bias: Vec<i16>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<T>, DlaError> {
+    run_packed(
+        dla,
+        input,
+        kernels,
+        Some(bias),
+        true,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+    )
+}
+
+/// Runs a low-precision layer packed in its SIMD mode, or unpacked to 8 bits without SIMD support
+#[allow(clippy::too_many_arguments)]
+fn run_packed<P: Packed, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<P>,
+    kernels: Tensor4<P>,
+    bias: Option<Vec<i16>>,
+    relu_enabled: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<T>, DlaError> {
+    let bias_enabled = bias.is_some();
+    if dla.backend().has_simd() {
+        return run_tiles(
+            dla,
+            input.view(),
+            kernels.view(),
+            bias,
+            bias_enabled,
+            relu_enabled,
+            padding,
+            stride,
+            mac_clip,
+            pp_clip,
+            Some(P::SIMD_MODE),
+        );
+    }
+    run_tiles(
+        dla,
+        input.map(|x| x.value()).view(),
+        kernels.map(|x| x.value()).view(),
+        bias,
+        bias_enabled,
+        relu_enabled,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        Some(SimdBitMode::EightBits),
+    )
+}
+
+/// Performs a 2D grouped convolution + Bias operation with DLA.
+///
+/// Panicking version of [`try_grouped_conv2d`].
+#[allow(clippy::too_many_arguments)]
+pub fn grouped_conv2d<T: DlaOutput + Clone>(
     input: Tensor3<i8>,
     kernels: Tensor4<i8>,
     bias: Vec<i16>,
@@ -273,42 +1068,1325 @@ pub fn grouped_conv2d<T: DlaOutput + Clone>(
     simd_mode: Option<SimdBitMode>,
     groups: usize,
 ) -> Tensor3<T> {
-    let total_in_channels = input.channels();
-    let group_in_channels = total_in_channels / groups;
-    let group_out_channels = kernels.kernels() / groups;
+    try_grouped_conv2d(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+        groups,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D grouped convolution + Bias operation with DLA.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: A vector of 16-bit signed integers containing biases for each channel.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+/// - `groups`: Number of groups used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` representing the output of the convolution operation.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if channels or kernels are not divisible by `groups`.
+/// - See [`try_conv2d_bias`] for the rest.
+///
+/// # Notes
+/// - The total number of input channels must be divisible by `groups`.
+/// - The total number of kernels must also be divisible by `groups`.
+/// - Padding and stride configurations are applied consistently across all groups.
+///
+///# Example
+/// For an input tensor with 8 channels, kernels with 16 filters, and `groups = 2`:
+/// - The input channels are split into 2 groups of 4 channels each.
+/// - Each group processes its portion with 8 filters (16 filters / 2 groups).
+/// - The final output will have 16 channels (8 channels per group concatenated).
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn try_grouped_conv2d<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Vec<i16>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+    groups: usize,
+) -> Result<Tensor3<T>, DlaError> {
+    let total_in_channels = input.channels();
+    if groups == 0
+        || !total_in_channels.is_multiple_of(groups)
+        || !kernels.kernels().is_multiple_of(groups)
+        || bias.len() != kernels.kernels()
+    {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let group_in_channels = total_in_channels / groups;
+    let group_out_channels = kernels.kernels() / groups;
+
+    let mut output_tensors = Vec::new();
+
+    for g in 0..groups {
+        let input_group = input
+            .view()
+            .slice_channels(g * group_in_channels..(g + 1) * group_in_channels);
+        let kernels_group = kernels
+            .view()
+            .slice_kernels(g * group_out_channels..(g + 1) * group_out_channels);
+        let bias_group = bias[g * group_out_channels..(g + 1) * group_out_channels].to_vec();
+
+        let output_group = run_tiles(
+            dla,
+            input_group,
+            kernels_group,
+            Some(bias_group),
+            true,
+            false,
+            padding.clone(),
+            stride.clone(),
+            mac_clip,
+            pp_clip,
+            simd_mode,
+        )?;
+
+        output_tensors.push(output_group);
+    }
+
+    // Concatenate the output tensors along the channel dimension
+    let views: Vec<_> = output_tensors.iter().map(Tensor3::view).collect();
+    Ok(concat_channels(&views))
+}
+
+/// Performs a 2D depthwise convolution with optional Bias with DLA.
+///
+/// Panicking version of [`try_depthwise_conv2d`].
+#[allow(clippy::too_many_arguments)]
+pub fn depthwise_conv2d<T: DlaOutput + Clone>(
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Tensor3<T> {
+    try_depthwise_conv2d(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D depthwise convolution with optional Bias with DLA.
+///
+/// Unlike [`try_grouped_conv2d`], which runs DLA once per group, as many channels as fit into the
+/// memory banks are convolved in a single run using block-diagonal kernels, i.e. each kernel has
+/// zeros for all but its own input channel.
+///
+/// NOTE: Batching `n` channels takes `n` times the kernel memory and `n` times the MACs of the
+/// depthwise kernels, trading MACs for fewer runs. See `examples/depthwise.rs` for cycles of both
+/// on the VP.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) with a single channel, and `multiplier` kernels for each input channel. Kernel `k` is applied to input channel `k / multiplier`.
+/// - `bias`: An optional vector of 16-bit signed integers containing biases for each kernel.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` with `kernels.kernels()` channels.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if kernels have more than one channel, the number of kernels
+///   is not a multiple of input channels or bias length doesn't match the number of kernels.
+/// - See [`try_conv2d_bias`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_depthwise_conv2d<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    let channels = input.channels();
+    if channels == 0
+        || kernels.channels() != 1
+        || !kernels.kernels().is_multiple_of(channels)
+        || bias.as_ref().is_some_and(|b| b.len() != kernels.kernels())
+    {
+        return Err(DlaError::DimensionMismatch);
+    }
+    check_stride(stride.as_ref())?;
+    let multiplier = kernels.kernels() / channels;
+    let (_, _, kernel_height, kernel_width) = kernels.dimensions();
+    let kernel_data = kernels.to_buffer_with_order(Order4::KCHW);
+    let window = kernel_height * kernel_width;
+
+    let batch = depthwise_batch_size::<T>(
+        &input,
+        &kernels,
+        multiplier,
+        bias.is_some(),
+        padding.clone(),
+        stride.clone(),
+    );
+
+    let mut outputs = Vec::new();
+    for first in (0..channels).step_by(batch) {
+        let batch_channels = core::cmp::min(batch, channels - first);
+        let batch_kernels = batch_channels * multiplier;
+        let first_kernel = first * multiplier;
+
+        // Block-diagonal kernels, zero for all but their own input channel
+        let mut data = vec![0; batch_kernels * batch_channels * window];
+        for k in 0..batch_kernels {
+            let dst = (k * batch_channels + k / multiplier) * window;
+            let src = (first_kernel + k) * window;
+            data[dst..dst + window].copy_from_slice(&kernel_data[src..src + window]);
+        }
+        let batch_kernel_tensor = Tensor4::from_data_buffer(
+            batch_kernels,
+            batch_channels,
+            kernel_height,
+            kernel_width,
+            data,
+            Order4::KCHW,
+        )
+        .unwrap();
+
+        outputs.push(run_tiles(
+            dla,
+            input.view().slice_channels(first..first + batch_channels),
+            batch_kernel_tensor.view(),
+            bias.as_ref()
+                .map(|bias| bias[first_kernel..first_kernel + batch_kernels].to_vec()),
+            bias.is_some(),
+            false,
+            padding.clone(),
+            stride.clone(),
+            mac_clip,
+            pp_clip,
+            simd_mode,
+        )?);
+    }
+
+    let views: Vec<_> = outputs.iter().map(Tensor3::view).collect();
+    Ok(concat_channels(&views))
+}
+
+/// Finds how many depthwise channels can be convolved at once without splitting the layer into
+/// tiles
+fn depthwise_batch_size<T>(
+    input: &Tensor3<i8>,
+    kernels: &Tensor4<i8>,
+    multiplier: usize,
+    bias: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+) -> usize {
+    let (channels, height, width) = input.dimensions();
+    let (output_width, output_height) = calculate_conv2d_out_param_dim(
+        (width as u32, height as u32),
+        (kernels.width() as u32, kernels.height() as u32),
+        padding,
+        stride.clone(),
+    );
+    (1..=channels)
+        .rev()
+        .find(|&batch| {
+            calculate_tile_size(
+                (batch, height, width),
+                (batch * multiplier, kernels.height(), kernels.width()),
+                (output_height, output_width),
+                stride.clone(),
+                size_of::<T>(),
+                bias,
+            ) == Ok((output_height, output_width))
+        })
+        .unwrap_or(1)
+}
+
+/// Concatenates tensors of equal height and width along the channel axis, in HWC order
+fn concat_channels<T: Clone>(tensors: &[Tensor3View<T>]) -> Tensor3<T> {
+    let (height, width) = (tensors[0].height(), tensors[0].width());
+    let channels: usize = tensors.iter().map(|tensor| tensor.channels()).sum();
+    let mut pixels: Vec<_> = tensors
+        .iter()
+        .map(|tensor| (tensor.channels(), tensor.iter_with_order(Order3::HWC)))
+        .collect();
+
+    let mut data = Vec::with_capacity(channels * height * width);
+    for _ in 0..height * width {
+        for (c, values) in &mut pixels {
+            data.extend(values.by_ref().take(*c).cloned());
+        }
+    }
+    Tensor3::from_data_buffer(channels, height, width, data, Order3::HWC).unwrap()
+}
+
+/// Performs 2D max pooling with DLA.
+///
+/// Panicking version of [`try_max_pool2d`].
+pub fn max_pool2d(input: Tensor3<i8>, size: u32) -> Tensor3<i8> {
+    try_max_pool2d(&Dla::new(), input, size).unwrap()
+}
+
+/// Performs 2D max pooling with DLA.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `size`: Width and height of the pooling window, which is also used as the stride.
+///
+/// Pooling is done on CPU unless DLA's post-processor can pool, see [`Pooling`].
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if the window is empty or larger than the input.
+/// - See [`try_conv2d`] for the rest.
+pub fn try_max_pool2d<B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    size: u32,
+) -> Result<Tensor3<i8>, DlaError> {
+    try_pool2d(
+        dla,
+        input,
+        Pooling {
+            mode: PoolMode::Max,
+            size,
+        },
+    )
+}
+
+/// Performs 2D average pooling with DLA.
+///
+/// Panicking version of [`try_avg_pool2d`].
+pub fn avg_pool2d(input: Tensor3<i8>, size: u32) -> Tensor3<i8> {
+    try_avg_pool2d(&Dla::new(), input, size).unwrap()
+}
+
+/// Performs 2D average pooling with DLA.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `size`: Width and height of the pooling window, which is also used as the stride.
+///
+/// # Errors
+/// - See [`try_max_pool2d`].
+pub fn try_avg_pool2d<B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    size: u32,
+) -> Result<Tensor3<i8>, DlaError> {
+    try_pool2d(
+        dla,
+        input,
+        Pooling {
+            mode: PoolMode::Average,
+            size,
+        },
+    )
+}
+
+/// Performs a 2D convolution + Bias + ReLU + pooling operation with DLA.
+///
+/// Panicking version of [`try_conv2d_bias_relu_pool`].
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_bias_relu_pool(
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    relu: bool,
+    pooling: Pooling,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Tensor3<i8> {
+    try_conv2d_bias_relu_pool(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        relu,
+        pooling,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D convolution + Bias + ReLU + pooling operation with DLA.
+///
+/// Pooling is done by DLA's post-processor with feature `hw-pooling`, if the window is at most
+/// [`MAX_POOL_SIZE`] and the layer fits into the memory banks as a whole. Otherwise, e.g. on the
+/// VP, which doesn't model pooling, it's done on CPU after the convolution.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: An optional vector of 16-bit signed integers containing biases for each channel.
+/// - `relu`: Enables ReLU in post-processing.
+/// - `pooling`: Pooling applied after bias and ReLU.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Errors
+/// - See [`try_max_pool2d`] and [`try_conv2d_bias`].
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_bias_relu_pool<B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    relu: bool,
+    pooling: Pooling,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let bias_enabled = bias.is_some();
+    if dla.backend().has_pooling() && pooling.size <= MAX_POOL_SIZE {
+        // Pooled layers aren't tiled, fall back to CPU pooling if the layer doesn't fit
+        match start_layer::<i8, i8, _>(
+            dla,
+            input.view(),
+            kernels.view(),
+            bias.clone(),
+            bias_enabled,
+            relu,
+            padding.clone(),
+            stride.clone(),
+            mac_clip,
+            pp_clip,
+            simd_mode,
+            Some(pooling),
+        ) {
+            Err(DlaError::BankOverflow) => {}
+            result => return result?.wait(),
+        }
+    }
+
+    let output = run_tiles::<i8, i8, _>(
+        dla,
+        input.view(),
+        kernels.view(),
+        bias,
+        bias_enabled,
+        relu,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )?;
+    reference::pool2d(&output, pooling)
+}
+
+/// Pools input by passing it through DLA with identity kernels, or on CPU if DLA can't pool
+fn try_pool2d<B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    pooling: Pooling,
+) -> Result<Tensor3<i8>, DlaError> {
+    // Identity convolution would only copy the input through DLA
+    if !dla.backend().has_pooling() || pooling.size > MAX_POOL_SIZE {
+        return reference::pool2d(&input, pooling);
+    }
+
+    let channels = input.channels();
+    let mut kernel_buf = vec![0; channels * channels];
+    for c in 0..channels {
+        kernel_buf[c * channels + c] = 1;
+    }
+    let kernels: Tensor4<i8> =
+        Tensor4::from_data_buffer(channels, channels, 1, 1, kernel_buf, Order4::KCHW)
+            .map_err(|_| DlaError::DimensionMismatch)?;
+
+    // No clipping, so that the values pass through MAC and post-processing unchanged
+    try_conv2d_bias_relu_pool(
+        dla,
+        input,
+        kernels,
+        None,
+        false,
+        pooling,
+        None,
+        None,
+        Some(0),
+        Some(0),
+        Some(SimdBitMode::EightBits),
+    )
+}
+
+/// Performs a 2D transposed convolution with optional Bias with DLA.
+///
+/// Panicking version of [`try_conv_transpose2d`].
+#[allow(clippy::too_many_arguments)]
+pub fn conv_transpose2d<T: DlaOutput + Clone>(
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    output_padding: Option<OutputPadding>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Tensor3<T> {
+    try_conv_transpose2d(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        padding,
+        stride,
+        output_padding,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D transposed convolution with optional Bias with DLA.
+///
+/// Calculated as a convolution over the input with `stride - 1` zeros inserted between its pixels
+/// and `kernel - 1 - padding` zeros around it, using the kernels rotated by 180 degrees. The
+/// output is `(input - 1) * stride + kernel - padding + output_padding` pixels in each direction.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`), one kernel per output channel as for [`try_conv2d`].
+/// - `bias`: An optional vector of 16-bit signed integers containing biases for each channel.
+/// - `padding`: An optional `Padding` parameter defining how much is cropped from each side of the output.
+/// - `stride`: An optional `Stride` parameter defining the upsampling factor in X and Y directions.
+/// - `output_padding`: An optional `OutputPadding` parameter defining how much is added to the bottom and right of the output.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` representing the output of the transposed convolution.
+///
+/// # Errors
+/// - [`DlaError::InvalidDimension`] if stride is zero.
+/// - [`DlaError::DimensionMismatch`] if input or kernels are empty, padding is not smaller than
+///   the kernel or output padding is not smaller than the stride.
+/// - See [`try_conv2d_bias`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv_transpose2d<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    output_padding: Option<OutputPadding>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    let padding = padding.unwrap_or(DEFAULT_PADDING);
+    let stride = stride.unwrap_or(DEFAULT_STRIDE);
+    let output_padding = output_padding.unwrap_or(OutputPadding { x: 0, y: 0 });
+    if stride.x == 0 || stride.y == 0 {
+        return Err(DlaError::InvalidDimension);
+    }
+    if output_padding.x >= stride.x || output_padding.y >= stride.y {
+        return Err(DlaError::DimensionMismatch);
+    }
+
+    // Transposed padding crops the fully padded convolution
+    let (kernel_height, kernel_width) = (kernels.height() as u32, kernels.width() as u32);
+    let full_padding = |pad: u32, kernel: u32| {
+        kernel
+            .saturating_sub(1)
+            .checked_sub(pad)
+            .ok_or(DlaError::DimensionMismatch)
+    };
+    let conv_padding = Padding {
+        top: full_padding(padding.top, kernel_height)?,
+        right: full_padding(padding.right, kernel_width)? + output_padding.x,
+        left: full_padding(padding.left, kernel_width)?,
+        bottom: full_padding(padding.bottom, kernel_height)? + output_padding.y,
+        padding_value: 0,
+    };
+
+    let bias_enabled = bias.is_some();
+    run_tiles(
+        dla,
+        zero_insert(&input, &stride)?.view(),
+        rotate_kernels(&kernels)?.view(),
+        bias,
+        bias_enabled,
+        false,
+        Some(conv_padding),
+        None,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+}
+
+/// Rotates each kernel by 180 degrees
+fn rotate_kernels(kernels: &Tensor4<i8>) -> Result<Tensor4<i8>, DlaError> {
+    let (k, c, h, w) = kernels.dimensions();
+    if k * c * h * w == 0 {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let mut data = kernels.to_buffer_with_order(Order4::KCHW);
+    for window in data.chunks_mut(h * w) {
+        window.reverse();
+    }
+    Ok(Tensor4::from_data_buffer(k, c, h, w, data, Order4::KCHW).unwrap())
+}
+
+/// Checks that upsampling scales are non-zero and input isn't empty
+fn check_upsample<T: Clone>(
+    input: &Tensor3<T>,
+    scale_y: usize,
+    scale_x: usize,
+) -> Result<(), DlaError> {
+    if scale_y == 0 || scale_x == 0 {
+        return Err(DlaError::InvalidDimension);
+    }
+    if input.get_size() == 0 {
+        return Err(DlaError::DimensionMismatch);
+    }
+    Ok(())
+}
+
+/// Upsamples input by repeating each pixel `scale_y` times vertically and `scale_x` times
+/// horizontally.
+///
+/// Panicking version of [`try_upsample_nearest`].
+pub fn upsample_nearest<T: Clone>(input: Tensor3<T>, scale_y: usize, scale_x: usize) -> Tensor3<T> {
+    try_upsample_nearest(input, scale_y, scale_x).unwrap()
+}
+
+/// Upsamples input by repeating each pixel `scale_y` times vertically and `scale_x` times
+/// horizontally.
+///
+/// Done on CPU, as it only moves data. The output keeps the order of the input.
+///
+/// # Errors
+/// - [`DlaError::InvalidDimension`] if a scale is zero.
+/// - [`DlaError::DimensionMismatch`] if input is empty.
+pub fn try_upsample_nearest<T: Clone>(
+    input: Tensor3<T>,
+    scale_y: usize,
+    scale_x: usize,
+) -> Result<Tensor3<T>, DlaError> {
+    check_upsample(&input, scale_y, scale_x)?;
+    let (channels, height, width) = input.dimensions();
+    let data = input.to_buffer_with_order(Order3::HWC);
+    let (out_height, out_width) = (height * scale_y, width * scale_x);
+
+    let mut output = Vec::with_capacity(channels * out_height * out_width);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let start = ((y / scale_y) * width + x / scale_x) * channels;
+            output.extend_from_slice(&data[start..start + channels]);
+        }
+    }
+
+    let mut output =
+        Tensor3::from_data_buffer(channels, out_height, out_width, output, Order3::HWC).unwrap();
+    output.permute(input.order());
+    Ok(output)
+}
+
+/// Upsamples input by `scale_y` vertically and `scale_x` horizontally with bilinear
+/// interpolation.
+///
+/// Panicking version of [`try_upsample_bilinear`].
+pub fn upsample_bilinear(input: Tensor3<i8>, scale_y: usize, scale_x: usize) -> Tensor3<i8> {
+    try_upsample_bilinear(input, scale_y, scale_x).unwrap()
+}
+
+/// Upsamples input by `scale_y` vertically and `scale_x` horizontally with bilinear
+/// interpolation.
+///
+/// Sampling points are at pixel centers, i.e. `align_corners = false`, and results are rounded
+/// to nearest. Done on CPU, the output keeps the order of the input.
+///
+/// # Errors
+/// - See [`try_upsample_nearest`].
+pub fn try_upsample_bilinear(
+    input: Tensor3<i8>,
+    scale_y: usize,
+    scale_x: usize,
+) -> Result<Tensor3<i8>, DlaError> {
+    check_upsample(&input, scale_y, scale_x)?;
+    let (channels, height, width) = input.dimensions();
+    let data = input.to_buffer_with_order(Order3::HWC);
+    let (out_height, out_width) = (height * scale_y, width * scale_x);
+
+    // Source position of output pixel center in units of 1 / (2 * scale), and the neighbouring
+    // source pixels along with the weight of the latter
+    let sample = |pos: usize, scale: usize, size: usize| {
+        let pos = (2 * pos + 1).saturating_sub(scale);
+        let low = (pos / (2 * scale)).min(size - 1);
+        let high = (low + 1).min(size - 1);
+        (low, high, (pos % (2 * scale)) as i32)
+    };
+    let (den_y, den_x) = (2 * scale_y as i32, 2 * scale_x as i32);
+    let den = den_y * den_x;
+
+    let mut output = Vec::with_capacity(channels * out_height * out_width);
+    for y in 0..out_height {
+        let (y0, y1, fy) = sample(y, scale_y, height);
+        for x in 0..out_width {
+            let (x0, x1, fx) = sample(x, scale_x, width);
+            for c in 0..channels {
+                let value = |y: usize, x: usize| data[(y * width + x) * channels + c] as i32;
+                let top = value(y0, x0) * (den_x - fx) + value(y0, x1) * fx;
+                let bottom = value(y1, x0) * (den_x - fx) + value(y1, x1) * fx;
+                let sum = top * (den_y - fy) + bottom * fy;
+                output.push((sum + den / 2).div_euclid(den) as i8);
+            }
+        }
+    }
+
+    let mut output =
+        Tensor3::from_data_buffer(channels, out_height, out_width, output, Order3::HWC).unwrap();
+    output.permute(input.order());
+    Ok(output)
+}
+
+/// Adds two quantized feature maps element-wise with DLA, e.g. for residual connections.
+///
+/// Panicking version of [`try_add`].
+pub fn add(a: Tensor3<i8>, b: Tensor3<i8>, params: &AddRequantization) -> Tensor3<i8> {
+    try_add(&Dla::new(), a, b, params).unwrap()
+}
+
+/// Adds two quantized feature maps element-wise with DLA, e.g. for residual connections.
+///
+/// The addition runs on DLA as a 1x1 convolution. Both inputs are read as channels of a single
+/// input, and each kernel weights a channel of `a` and the same channel of `b` by the scales of the
+/// inputs relative to the output. Zero points are folded into bias and the sum is scaled down by
+/// the post-processing clip. Falls back to CPU when 8-bit weights can't represent the scales
+/// accurately enough.
+///
+/// NOTE: DLA rounds the scales to 8-bit weights, so its results can differ by one from
+/// [`AddRequantization::add`], which is used on CPU.
+///
+/// # Arguments
+/// - `a`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the first input feature map.
+/// - `b`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the second input feature map.
+/// - `params`: Scales and zero points of the inputs and the output.
+///
+/// # Returns
+/// - A 3-dimensional tensor of 8-bit signed integers in the order of `a`.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if the inputs have different dimensions.
+/// - See [`try_conv2d_bias`] for the rest.
+pub fn try_add<B: RegisterAccess>(
+    dla: &Dla<B>,
+    a: Tensor3<i8>,
+    b: Tensor3<i8>,
+    params: &AddRequantization,
+) -> Result<Tensor3<i8>, DlaError> {
+    if a.dimensions() != b.dimensions() {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let Some((weights, bias, shift)) = dla_add_params(params) else {
+        return a
+            .zip_map(&b, |&a, &b| params.add(a as i32, b as i32))
+            .ok_or(DlaError::DimensionMismatch);
+    };
+
+    let (channels, height, width) = a.dimensions();
+    let batch = (1..=core::cmp::min(channels, MAX_CHANNELS / 2))
+        .rev()
+        .find(|&batch| {
+            calculate_tile_size(
+                (2 * batch, height, width),
+                (batch, 1, 1),
+                (height, width),
+                None,
+                size_of::<i8>(),
+                true,
+            ) == Ok((height, width))
+        })
+        .unwrap_or(1);
+
+    let mut outputs = Vec::new();
+    for first in (0..channels).step_by(batch) {
+        let batch_channels = core::cmp::min(batch, channels - first);
+        let range = first..first + batch_channels;
+        let input = concat_channels(&[
+            a.view().slice_channels(range.clone()),
+            b.view().slice_channels(range),
+        ]);
+
+        // Kernel k weights channel k of both inputs
+        let mut data = vec![0; batch_channels * 2 * batch_channels];
+        for k in 0..batch_channels {
+            data[k * 2 * batch_channels + k] = weights[0];
+            data[k * 2 * batch_channels + batch_channels + k] = weights[1];
+        }
+        let kernels =
+            Tensor4::from_data_buffer(batch_channels, 2 * batch_channels, 1, 1, data, Order4::KCHW)
+                .unwrap();
+
+        outputs.push(run_tiles::<i8, i8, _>(
+            dla,
+            input.view(),
+            kernels.view(),
+            Some(vec![bias; batch_channels]),
+            true,
+            false,
+            None,
+            None,
+            Some(0),
+            Some(shift),
+            None,
+        )?);
+    }
+
+    let views: Vec<_> = outputs.iter().map(Tensor3::view).collect();
+    let mut output = concat_channels(&views);
+    output.permute(a.order());
+    Ok(output)
+}
+
+/// Finds 8-bit weights for both inputs of an addition, along with bias and PP clip, so that DLA
+/// calculates `(weight_a * a + weight_b * b + bias) >> pp_clip`
+///
+/// Returns `None` if rounding the scales to the weights could change outputs by more than one, or
+/// the sum wouldn't fit DLA's 16-bit post-processing.
+fn dla_add_params(params: &AddRequantization) -> Option<([i8; 2], i16, u32)> {
+    // Scale of input relative to the output times 2^shift, as `numerator / 2^exponent`
+    let scaled = |input: usize, shift: u32| {
+        let multiplier = params.input_multipliers[input];
+        let numerator = multiplier.multiplier as i128 * params.output_multiplier.multiplier as i128;
+        let exponent = 62
+            - ADD_LEFT_SHIFT as i32
+            - multiplier.shift
+            - params.output_multiplier.shift
+            - shift as i32;
+        if exponent <= 0 {
+            (numerator << (-exponent).min(64), 0)
+        } else {
+            (numerator, exponent.min(126) as u32)
+        }
+    };
+    let round = |(numerator, exponent): (i128, u32)| match exponent {
+        0 => numerator,
+        _ => (numerator + (1 << (exponent - 1))) >> exponent,
+    };
+    let fits_i8 = |value: i128| (i8::MIN as i128..=i8::MAX as i128).contains(&value);
+
+    // Largest shift at which both weights fit 8 bits
+    let shift = (0..=MAX_PP_CLIP as u32)
+        .rev()
+        .find(|&shift| (0..2).all(|input| fits_i8(round(scaled(input, shift)))))?;
+    let scales = [scaled(0, shift), scaled(1, shift)];
+    let weights = scales.map(round);
+
+    // Error of the weights times the largest distance from zero point must stay below half an
+    // output step, so that results are within one of the exact ones
+    let exponent = scales[0].1.max(scales[1].1);
+    let mut error: i128 = 0;
+    for ((numerator, scale_exponent), (weight, zero_point)) in scales
+        .into_iter()
+        .zip(weights.into_iter().zip(params.input_zero_points))
+    {
+        let deviation = weight
+            .checked_mul(1 << scale_exponent)?
+            .checked_sub(numerator)?
+            .abs()
+            .checked_mul(1i128.checked_shl(exponent - scale_exponent)?)?;
+        let distance =
+            (i8::MAX as i128 - zero_point as i128).max(zero_point as i128 - i8::MIN as i128);
+        error = error.checked_add(deviation.checked_mul(distance)?)?;
+    }
+    if error > 1i128.checked_shl(shift + exponent)? / 2 {
+        return None;
+    }
+
+    let [za, zb] = params
+        .input_zero_points
+        .map(|zero_point| zero_point as i128);
+    let rounding = if shift > 0 { 1 << (shift - 1) } else { 0 };
+    let bias = ((params.output_zero_point as i128) << shift) - weights[0] * za - weights[1] * zb
+        + rounding;
+    let bias = i16::try_from(bias).ok()?;
+
+    // Sums outside of 16 bits saturate. That's harmless when the saturated sum still saturates the
+    // 8-bit output, but larger shifts would scale it back into range.
+    if shift > 8 {
+        let range = |weight: i128| (weight * i8::MIN as i128, weight * i8::MAX as i128);
+        let (a, b) = (range(weights[0]), range(weights[1]));
+        let low = a.0.min(a.1) + b.0.min(b.1) + bias as i128;
+        let high = a.0.max(a.1) + b.0.max(b.1) + bias as i128;
+        if low < i16::MIN as i128 || high > i16::MAX as i128 {
+            return None;
+        }
+    }
+
+    Some((weights.map(|weight| weight as i8), bias, shift))
+}
+
+/// Multiplies two quantized feature maps element-wise.
+///
+/// Panicking version of [`try_mul`].
+pub fn mul(a: Tensor3<i8>, b: Tensor3<i8>, params: &MulRequantization) -> Tensor3<i8> {
+    try_mul(a, b, params).unwrap()
+}
+
+/// Multiplies two quantized feature maps element-wise.
+///
+/// NOTE: DLA can only multiply inputs by constant weights, so this is done on CPU. The output
+/// keeps the order of `a`.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if the inputs have different dimensions.
+pub fn try_mul(
+    a: Tensor3<i8>,
+    b: Tensor3<i8>,
+    params: &MulRequantization,
+) -> Result<Tensor3<i8>, DlaError> {
+    a.zip_map(&b, |&a, &b| params.mul(a as i32, b as i32))
+        .ok_or(DlaError::DimensionMismatch)
+}
+
+/// Starts a 2D convolution with optional Bias and ReLU on DLA without waiting for it to complete.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: An optional vector of 16-bit signed integers containing biases for each channel.
+/// - `relu`: Enables ReLU in post-processing.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Returns
+/// - A [`DlaJob`] that produces the output of the convolution once DLA is done.
+///
+/// # Errors
+/// - [`DlaError::BankOverflow`] if the layer does not fit into DLA's memory banks. Unlike the
+///   blocking layer functions, this does not split the layer into tiles.
+/// - [`DlaError::Busy`] if the previous job has not been completed and dropped.
+/// - See [`try_conv2d_bias`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn submit_conv2d<'d, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &'d Dla<B>,
+    input: Tensor3<i8>,
+    kernels: Tensor4<i8>,
+    bias: Option<Vec<i16>>,
+    relu: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<DlaJob<'d, T, B>, DlaError> {
+    let bias_enabled = bias.is_some();
+    start_layer(
+        dla,
+        input.view(),
+        kernels.view(),
+        bias,
+        bias_enabled,
+        relu,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+        None,
+    )
+}
+
+/// Performs a 2D convolution with optional Bias and ReLU on borrowed tensors with DLA.
+///
+/// Panicking version of [`try_conv2d_view`].
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_view<T: DlaOutput + Clone>(
+    input: Tensor3View<i8>,
+    kernels: Tensor4View<i8>,
+    bias: Option<&[i16]>,
+    relu: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    dilation: Option<Dilation>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Tensor3<T> {
+    try_conv2d_view(
+        &Dla::new(),
+        input,
+        kernels,
+        bias,
+        relu,
+        padding,
+        stride,
+        dilation,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+    .unwrap()
+}
+
+/// Performs a 2D convolution with optional Bias and ReLU on borrowed tensors with DLA.
+///
+/// Input and kernels are written to DLA's memory banks straight from the borrowed memory, in the
+/// order DLA reads them, so e.g. feature maps in SDRAM or weights of a model image are not copied on
+/// the way. Owned tensors can be borrowed with [`Tensor3::view`] and [`Tensor4::view`], and buffers
+/// with [`Tensor3View::from_slice`] and [`Tensor4View::from_slice`].
+///
+/// # Arguments
+/// - `input`: A view of 8-bit signed integers (`Tensor3View<i8>`) representing the input feature map.
+/// - `kernels`: A view of 8-bit signed integers (`Tensor4View<i8>`) representing the convolution kernels.
+/// - `bias`: An optional slice of 16-bit signed integers containing biases for each kernel.
+/// - `relu`: Enables ReLU in post-processing.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `dilation`: An optional `Dilation` parameter defining the spacing of kernel elements in X and Y directions.
+///   Dilated kernels are expanded to a copy, see [`try_conv2d`].
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` to control which SIMD instruction is used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of type `T` in HWC order representing the output of the convolution operation.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if bias length doesn't match the number of kernels.
+/// - See [`try_conv2d`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_view<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3View<i8>,
+    kernels: Tensor4View<i8>,
+    bias: Option<&[i16]>,
+    relu: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    dilation: Option<Dilation>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    let dilated = dilate(kernels, dilation.as_ref())?;
+    let kernels = match &dilated {
+        Some(dilated) => dilated.view(),
+        None => kernels.reborrow(),
+    };
+    run_tiles(
+        dla,
+        input,
+        kernels,
+        bias.map(<[i16]>::to_vec),
+        bias.is_some(),
+        relu,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+    )
+}
+
+/// Performs a 2D convolution with optional Bias and ReLU on borrowed tensors with DLA, writing the
+/// output to the caller's buffer in HWC order.
+///
+/// See [`try_conv2d_view`] for the arguments.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if `output` doesn't have exactly the size of the layer
+///   output. Nothing is run on DLA then.
+/// - See [`try_conv2d_view`] for the rest.
+#[allow(clippy::too_many_arguments)]
+pub fn try_conv2d_into<T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3View<i8>,
+    kernels: Tensor4View<i8>,
+    bias: Option<&[i16]>,
+    relu: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    dilation: Option<Dilation>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+    output: &mut [T],
+) -> Result<(), DlaError> {
+    let dilated = dilate(kernels, dilation.as_ref())?;
+    let kernels = match &dilated {
+        Some(dilated) => dilated.view(),
+        None => kernels.reborrow(),
+    };
+    run_tiles_into(
+        dla,
+        input,
+        kernels,
+        bias.map(<[i16]>::to_vec),
+        bias.is_some(),
+        relu,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+        output,
+    )
+}
 
-    // Placeholder for the output tensor
-    let mut output_tensors = Vec::new();
+/// Expands kernels for the given dilation, `None` if they are used as they are
+fn dilate(
+    kernels: Tensor4View<i8>,
+    dilation: Option<&Dilation>,
+) -> Result<Option<Tensor4<i8>>, DlaError> {
+    match dilation {
+        None | Some(Dilation { x: 1, y: 1 }) => Ok(None),
+        Some(dilation) => dilate_kernels(kernels, dilation).map(Some),
+    }
+}
 
-    for g in 0..groups {
-        let input_group = input.slice_channels(g * group_in_channels..(g + 1) * group_in_channels);
-        let kernels_group =
-            kernels.slice_channels(g * group_in_channels..(g + 1) * group_in_channels);
-        let bias_group = bias[g * group_out_channels..(g + 1) * group_out_channels].to_vec();
+/// Runs a layer on DLA, splitting it into spatial tiles if its data does not fit into DLA's
+/// memory banks at once
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_tiles<I: Packed, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3View<I>,
+    kernels: Tensor4View<I>,
+    bias: Option<Vec<i16>>,
+    bias_enabled: bool,
+    relu_enabled: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<T>, DlaError> {
+    let (output_width, output_height) = layer_output_dim(
+        input.dimensions(),
+        kernels.dimensions(),
+        padding.as_ref(),
+        stride.as_ref(),
+    )?;
+    let mut output = vec![T::default(); kernels.kernels() * output_height * output_width];
+    run_tiles_into(
+        dla,
+        input,
+        kernels,
+        bias,
+        bias_enabled,
+        relu_enabled,
+        padding,
+        stride,
+        mac_clip,
+        pp_clip,
+        simd_mode,
+        &mut output,
+    )?;
 
-        let output_group = run_layers(
-            input_group,
-            kernels_group,
-            Some(bias_group),
-            true,
-            false,
-            padding.clone(),
-            stride.clone(),
+    Tensor3::from_data_buffer(
+        kernels.kernels(),
+        output_height,
+        output_width,
+        output,
+        Order3::HWC,
+    )
+    .map_err(|_| DlaError::DimensionMismatch)
+}
+
+/// Runs a layer on DLA like [`run_tiles`], reading its output into `output` in HWC order
+#[allow(clippy::too_many_arguments)]
+fn run_tiles_into<I: Packed, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3View<I>,
+    kernels: Tensor4View<I>,
+    bias: Option<Vec<i16>>,
+    bias_enabled: bool,
+    relu_enabled: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+    output: &mut [T],
+) -> Result<(), DlaError> {
+    let (output_width, output_height) = layer_output_dim(
+        input.dimensions(),
+        kernels.dimensions(),
+        padding.as_ref(),
+        stride.as_ref(),
+    )?;
+    if output.len() != kernels.kernels() * output_height * output_width {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let (tile_height, tile_width) = calculate_tile_size(
+        input.dimensions(),
+        (kernels.kernels(), kernels.height(), kernels.width()),
+        (output_height, output_width),
+        stride.clone(),
+        size_of::<T>(),
+        bias.as_deref()
+            .is_some_and(|bias| external_bias_addr(dla, bias).is_none()),
+    )?;
+
+    // Whole layer fits into banks
+    if tile_height >= output_height && tile_width >= output_width {
+        return start_layer(
+            dla,
+            input,
+            kernels,
+            bias,
+            bias_enabled,
+            relu_enabled,
+            padding,
+            stride,
             mac_clip,
             pp_clip,
             simd_mode,
-        );
+            None,
+        )?
+        .wait_into(output);
+    }
 
-        output_tensors.push(output_group);
+    let padding = padding.unwrap_or(DEFAULT_PADDING);
+    let stride = stride.unwrap_or(DEFAULT_STRIDE);
+    let output_row_len = output_width * kernels.kernels();
+
+    for tile_y in (0..output_height).step_by(tile_height) {
+        let rows = tile_y..core::cmp::min(tile_y + tile_height, output_height);
+        let (input_rows, padding_top, padding_bottom) = calculate_tile_input_range(
+            rows.clone(),
+            stride.y as usize,
+            kernels.height(),
+            padding.top as usize,
+            input.height(),
+        )?;
+
+        for tile_x in (0..output_width).step_by(tile_width) {
+            let cols = tile_x..core::cmp::min(tile_x + tile_width, output_width);
+            let (input_cols, padding_left, padding_right) = calculate_tile_input_range(
+                cols.clone(),
+                stride.x as usize,
+                kernels.width(),
+                padding.left as usize,
+                input.width(),
+            )?;
+
+            let row_len = cols.len() * kernels.kernels();
+            let mut tile = vec![T::default(); rows.len() * row_len];
+            start_layer::<I, T, _>(
+                dla,
+                input.slice_spatial(input_rows.clone(), input_cols),
+                kernels,
+                bias.clone(),
+                bias_enabled,
+                relu_enabled,
+                Some(Padding {
+                    top: padding_top,
+                    right: padding_right,
+                    left: padding_left,
+                    bottom: padding_bottom,
+                    padding_value: padding.padding_value,
+                }),
+                Some(stride.clone()),
+                mac_clip,
+                pp_clip,
+                simd_mode,
+                None,
+            )?
+            .wait_into(&mut tile)?;
+
+            // Place the HWC rows of the tile into the HWC output
+            for (y, tile_row) in rows.clone().zip(tile.chunks_exact(row_len)) {
+                let start = y * output_row_len + tile_x * kernels.kernels();
+                output[start..start + row_len].clone_from_slice(tile_row);
+            }
+        }
     }
+    Ok(())
+}
 
-    // Concatenate the output tensors along the channel dimension
-    Tensor3::concat_interleaved(&output_tensors)
+/// Checks that a layer can be run with [`run_tiles`] and calculates its output width and height
+///
+/// Dimensions are given as `(channels, height, width)` and `(kernels, channels, height, width)`.
+pub(crate) fn layer_output_dim(
+    input: (usize, usize, usize),
+    kernels: (usize, usize, usize, usize),
+    padding: Option<&Padding>,
+    stride: Option<&Stride>,
+) -> Result<(usize, usize), DlaError> {
+    let (channels, height, width) = input;
+    let (_, kernel_channels, kernel_height, kernel_width) = kernels;
+    if channels != kernel_channels || kernel_height == 0 || kernel_width == 0 {
+        return Err(DlaError::DimensionMismatch);
+    }
+    check_stride(stride)?;
+    let pad = padding.cloned().unwrap_or(DEFAULT_PADDING);
+    if (width as u32 + pad.left + pad.right) < kernel_width as u32
+        || (height as u32 + pad.top + pad.bottom) < kernel_height as u32
+    {
+        return Err(DlaError::DimensionMismatch);
+    }
+    Ok(calculate_conv2d_out_param_dim(
+        (width as u32, height as u32),
+        (kernel_width as u32, kernel_height as u32),
+        padding.cloned(),
+        stride.cloned(),
+    ))
 }
 
-fn run_layers<T: DlaOutput + Clone>(
-    input: Tensor3<i8>,
-    kernels: Tensor4<i8>,
+/// Configures DLA for a layer, writes its data and starts the calculation
+#[allow(clippy::too_many_arguments)]
+fn start_layer<'d, I: Packed, T: DlaOutput + Clone, B: RegisterAccess>(
+    dla: &'d Dla<B>,
+    input: Tensor3View<I>,
+    kernels: Tensor4View<I>,
     bias: Option<Vec<i16>>,
     bias_enabled: bool,
     relu_enabled: bool,
@@ -317,29 +2395,64 @@ fn run_layers<T: DlaOutput + Clone>(
     mac_clip: Option<u32>,
     pp_clip: Option<u32>,
     simd_mode: Option<SimdBitMode>,
-) -> Tensor3<T> {
+    pooling: Option<Pooling>,
+) -> Result<DlaJob<'d, T, B>, DlaError> {
+    if input.channels() != kernels.channels() {
+        return Err(DlaError::DimensionMismatch);
+    }
+    if let Some(bias) = &bias {
+        if bias.len() != kernels.kernels() {
+            return Err(DlaError::DimensionMismatch);
+        }
+    }
+    check_stride(stride.as_ref())?;
+    if pooling.is_some_and(|pooling| pooling.size == 0) {
+        return Err(DlaError::InvalidDimension);
+    }
+    let reservation = Reservation::acquire()?;
+
     let output_size = calculate_conv2d_out_param_dim(
         (input.width() as u32, input.height() as u32),
         (kernels.width() as u32, kernels.height() as u32),
         padding.clone(),
         stride.clone(),
     );
+    // Pooling drops rows and columns that don't fill a whole window
+    let output_size = match &pooling {
+        Some(pooling) => (
+            output_size.0 / pooling.size as usize,
+            output_size.1 / pooling.size as usize,
+        ),
+        None => output_size,
+    };
 
-    let dla = Dla::new();
-
-    let banks = get_banks_for_layer(
-        input.get_size(),
-        kernels.get_size(),
-        output_size.0 * output_size.1,
-    );
+    let input_banks = BankRange::allocate(packed_len::<I>(
+        input.height() * input.width(),
+        input.channels(),
+    ))?;
+    let kernel_banks = BankRange::allocate(packed_len::<I>(
+        kernels.kernels() * kernels.height() * kernels.width(),
+        kernels.channels(),
+    ))?;
+    let output_banks =
+        BankRange::allocate(kernels.kernels() * output_size.0 * output_size.1 * size_of::<T>())?;
+    // Bias is read straight from where it is if DLA can reach it, e.g. from SDRAM
+    let external_bias = bias
+        .as_deref()
+        .and_then(|bias| external_bias_addr(dla, bias));
+    let bias_banks = match (&bias, external_bias) {
+        (Some(bias), None) => Some(BankRange::allocate(bias.len() * size_of::<i16>())?),
+        _ => None,
+    };
 
     // Initalize layer
+    let (mac_sat_min, mac_sat_max) = dla.layer_mac_saturation();
     let config = LayerConfig {
-        input_bank: Some(banks.0),  // b
-        kernel_bank: Some(banks.1), // a
-        output_bank: Some(banks.2),
-        bias_addr: banks.3,
-        pp_enabled: relu_enabled || bias_enabled,
+        input_bank: BankSelection::Bank(input_banks.start()), // b
+        kernel_bank: BankSelection::Bank(kernel_banks.start()), // a
+        output_bank: BankSelection::Bank(output_banks.start()),
+        bias_addr: external_bias.or(bias_banks.as_ref().map(|banks| banks.addr() as u32)),
+        pp_enabled: relu_enabled || bias_enabled || pooling.is_some(),
         relu_enabled,
         bias_enabled,
         input_size: Some(InputSize {
@@ -357,32 +2470,1387 @@ fn run_layers<T: DlaOutput + Clone>(
         stride,
         mac_clip,
         pp_clip,
+        mac_sat_max: Some(mac_sat_max),
+        mac_sat_min: Some(mac_sat_min),
+        pp_rounding: dla.layer_pp_rounding(),
         simd_mode,
+        pooling,
+        output_width: Some(T::WIDTH),
     };
 
-    dla.init_layer(config);
+    dla.try_init_layer(config)?;
 
-    dla.write_input(&mut input.to_buffer_with_order(Order3::HWC));
-    dla.write_kernel(&mut kernels.to_buffer_with_order(Order4::HWKC));
+    // Data is packed and written in DLA's order straight from where it is
+    // Channels are last in both orders, so each pixel is packed separately
+    dla.write_input_iter(
+        pack_iter(
+            input.iter_with_order(Order3::HWC).copied(),
+            input.channels(),
+        )
+        .map(|b| b as i8),
+    )?;
+    dla.write_kernel_iter(
+        pack_iter(
+            kernels.iter_with_order(Order4::HWKC).copied(),
+            kernels.channels(),
+        )
+        .map(|b| b as i8),
+    )?;
 
-    if let Some(bias) = bias {
-        dla.write_bias(&bias)
+    if let (Some(bias), Some(_)) = (&bias, &bias_banks) {
+        dla.write_bias(bias)
+    }
+    if external_bias.is_some() {
+        dla.backend().fence();
     }
 
     // Mark data ready to start calculations
+    let start = dla.stats_snapshot();
     dla.kernel_data_ready(true);
     dla.input_data_ready(true);
 
-    while !dla.handle_handshake() {}
-
-    let output_buffer = T::read_output(&dla, output_size.0 * output_size.1 * kernels.kernels());
+    let mut banks = vec![input_banks, kernel_banks, output_banks];
+    banks.extend(bias_banks);
 
-    Tensor3::from_data_buffer(
+    Ok(DlaJob::new(
+        dla,
+        reservation,
+        banks,
         kernels.kernels(),
         output_size.1,
         output_size.0,
-        output_buffer,
-        Order3::HWC, // NOTE: (20240610 vaino-waltteri.granat@tuni.fi) This might not be true on ASIC
-    )
-    .unwrap()
+        external_bias.and(bias),
+        start,
+    ))
+}
+
+/// Runs post-processing on DLA, splitting the feature map into rows if its data does not fit into
+/// DLA's memory banks at once. Output is in HWC order.
+fn run_pp_tiles<B: RegisterAccess>(
+    dla: &Dla<B>,
+    input: Tensor3View<i8>,
+    bias: Option<Vec<i16>>,
+    relu_enabled: bool,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let (channels, height, width) = input.dimensions();
+    let tile_height = calculate_pp_tile_height(
+        input.dimensions(),
+        bias.as_deref()
+            .is_some_and(|bias| external_bias_addr(dla, bias).is_none()),
+    )?
+    .min(MAX_PP_INPUT_HEIGHT);
+
+    let mut output = Vec::with_capacity(input.get_size());
+    for tile_y in (0..height).step_by(tile_height) {
+        let rows = tile_y..core::cmp::min(tile_y + tile_height, height);
+        let tile = start_post_processing(
+            dla,
+            input.slice_spatial(rows, 0..width),
+            bias.clone(),
+            relu_enabled,
+            pp_clip,
+        )?
+        .wait()?;
+        // Rows of HWC tiles follow each other
+        output.extend(tile.to_buffer_with_order(Order3::HWC));
+    }
+
+    Tensor3::from_data_buffer(channels, height, width, output, Order3::HWC)
+        .map_err(|_| DlaError::DimensionMismatch)
+}
+
+/// Configures DLA for post-processing only, writes the input and starts the calculation
+fn start_post_processing<'d, B: RegisterAccess>(
+    dla: &'d Dla<B>,
+    input: Tensor3View<i8>,
+    bias: Option<Vec<i16>>,
+    relu_enabled: bool,
+    pp_clip: Option<u32>,
+) -> Result<DlaJob<'d, i8, B>, DlaError> {
+    if let Some(bias) = &bias {
+        if bias.len() != input.channels() {
+            return Err(DlaError::DimensionMismatch);
+        }
+    }
+    let reservation = Reservation::acquire()?;
+
+    let input_banks = BankRange::allocate(input.get_size())?;
+    let output_banks = BankRange::allocate(input.get_size())?;
+    // Bias is read straight from where it is if DLA can reach it, e.g. from SDRAM
+    let external_bias = bias
+        .as_deref()
+        .and_then(|bias| external_bias_addr(dla, bias));
+    let bias_banks = match (&bias, external_bias) {
+        (Some(bias), None) => Some(BankRange::allocate(bias.len() * size_of::<i16>())?),
+        _ => None,
+    };
+
+    let config = PpConfig {
+        input_bank: BankSelection::Bank(input_banks.start()),
+        output_bank: BankSelection::Bank(output_banks.start()),
+        bias_addr: external_bias.or(bias_banks.as_ref().map(|banks| banks.addr() as u32)),
+        relu_enabled,
+        bias_enabled: bias.is_some(),
+        input_size: Some(InputSize {
+            channels: input.channels() as u32,
+            width: input.width() as u32,
+            height: input.height() as u32,
+        }),
+        pp_clip,
+        pp_rounding: dla.layer_pp_rounding(),
+    };
+
+    dla.try_init_pp_layer(config)?;
+
+    dla.write_input_iter(input.iter_with_order(Order3::HWC).copied())?;
+
+    if let (Some(bias), Some(_)) = (&bias, &bias_banks) {
+        dla.write_bias(bias)
+    }
+    if external_bias.is_some() {
+        dla.backend().fence();
+    }
+
+    // Post-processor only waits for input data
+    let start = dla.stats_snapshot();
+    dla.input_data_ready(true);
+
+    let mut banks = vec![input_banks, output_banks];
+    banks.extend(bias_banks);
+
+    Ok(DlaJob::new(
+        dla,
+        reservation,
+        banks,
+        input.channels(),
+        input.height(),
+        input.width(),
+        external_bias.and(bias),
+        start,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmap::{DLA_MAC_CTRL, DLA_SIMD_SELECT_BITMASK, DLA_SIMD_SELECT_OFFSET};
+    use crate::quant::{QuantizedMultiplier, Rounding};
+    use crate::simd::{I2, I4};
+    use crate::test_utils::{self, assert_tensor_eq, rng, simulated};
+    use crate::utils::calculate_dilated_conv2d_out_param_dim;
+    use rand::Rng;
+
+    /// Builds addition parameters from the scales of the inputs relative to the output, like
+    /// TFLite does
+    fn add_params(
+        scales: [f64; 2],
+        input_zero_points: [i32; 2],
+        output_zero_point: i32,
+        rounding: Rounding,
+    ) -> AddRequantization {
+        let twice_max = 2.0 * scales[0].max(scales[1]);
+        AddRequantization {
+            input_multipliers: scales
+                .map(|scale| QuantizedMultiplier::from_scale(scale / twice_max).unwrap()),
+            input_zero_points,
+            output_multiplier: QuantizedMultiplier::from_scale(
+                twice_max / (1 << ADD_LEFT_SHIFT) as f64,
+            )
+            .unwrap(),
+            output_zero_point,
+            rounding,
+        }
+    }
+
+    /// Calculates a fully connected layer as a 1x1 convolution of the reference model
+    fn linear_reference(
+        inputs: &[i8],
+        weights: &[i8],
+        bias: Option<&[i16]>,
+        out_features: usize,
+        relu: bool,
+        mac_clip: Option<u32>,
+        pp_clip: Option<u32>,
+    ) -> Vec<i8> {
+        let in_features = weights.len() / out_features;
+        let batch = inputs.len() / in_features;
+        let input =
+            Tensor3::from_data_buffer(in_features, 1, batch, inputs.to_vec(), Order3::WHC).unwrap();
+        let kernels = Tensor4::from_data_buffer(
+            out_features,
+            in_features,
+            1,
+            1,
+            weights.to_vec(),
+            Order4::KCHW,
+        )
+        .unwrap();
+        reference::conv2d_bias_relu(
+            &input, &kernels, bias, relu, None, None, mac_clip, pp_clip, None,
+        )
+        .unwrap()
+        .to_buffer_with_order(Order3::HWC)
+    }
+
+    #[test]
+    fn linear_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        // (batch, in_features, out_features): features over kernel height, batch over input
+        // width and output features over kernels
+        for (batch, in_features, out_features) in [(3, 12, 5), (600, 4, 2), (2, 1, 5000)] {
+            let inputs = test_utils::values(&mut rng, batch * in_features, -128..=127);
+            let weights = test_utils::values(&mut rng, out_features * in_features, -128..=127);
+            let bias = test_utils::values(&mut rng, out_features, -500..=500);
+
+            let output: Vec<i8> = try_linear(
+                &dla,
+                inputs.clone(),
+                weights.clone(),
+                Some(bias.clone()),
+                out_features,
+                true,
+                Some(2),
+                Some(3),
+            )
+            .unwrap();
+            let expected = linear_reference(
+                &inputs,
+                &weights,
+                Some(&bias),
+                out_features,
+                true,
+                Some(2),
+                Some(3),
+            );
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn linear_sums_split_in_features() {
+        let (dla, _lock) = simulated();
+        dla.backend().set_out32(true);
+        let mut rng = rng();
+        // Prime, so that the features can't be spread over kernel height
+        let in_features = 4099;
+        let inputs = test_utils::values(&mut rng, 2 * in_features, -128..=127);
+        let weights = test_utils::values(&mut rng, 3 * in_features, -128..=127);
+        let bias = test_utils::values(&mut rng, 3, -500..=500);
+
+        let output: Vec<i8> = try_linear(
+            &dla,
+            inputs.clone(),
+            weights.clone(),
+            Some(bias.clone()),
+            3,
+            false,
+            Some(6),
+            Some(2),
+        )
+        .unwrap();
+        let expected = linear_reference(&inputs, &weights, Some(&bias), 3, false, Some(6), Some(2));
+        assert_eq!(output, expected);
+
+        dla.backend().set_out32(false);
+        let output: Result<Vec<i8>, _> = try_linear(
+            &dla,
+            inputs,
+            weights,
+            Some(bias),
+            3,
+            false,
+            Some(6),
+            Some(2),
+        );
+        assert_eq!(output, Err(DlaError::Unsupported));
+    }
+
+    #[test]
+    fn conv2d_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 9, 7);
+        let kernels = test_utils::tensor4(&mut rng, 4, 3, 3, 3);
+
+        let output: Tensor3<i8> = try_conv2d(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            None,
+            None,
+            None,
+            Some(4),
+            Some(3),
+            None,
+        )
+        .unwrap();
+        let expected = reference::conv2d_bias_relu(
+            &input,
+            &kernels,
+            None,
+            false,
+            None,
+            None,
+            Some(4),
+            Some(3),
+            None,
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    #[test]
+    fn conv2d_bias_relu_padding_stride_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 5, 11, 8);
+        let kernels = test_utils::tensor4(&mut rng, 6, 5, 3, 2);
+        let bias = test_utils::values(&mut rng, 6, -2000..=2000);
+        let padding = Padding {
+            top: 1,
+            right: 0,
+            left: 2,
+            bottom: 1,
+            padding_value: -3,
+        };
+        let stride = Stride { x: 2, y: 1 };
+
+        let output: Tensor3<i8> = try_conv2d_bias_relu(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            bias.clone(),
+            Some(padding.clone()),
+            Some(stride.clone()),
+            Some(2),
+            Some(5),
+            None,
+        )
+        .unwrap();
+        let expected = reference::conv2d_bias_relu(
+            &input,
+            &kernels,
+            Some(&bias),
+            true,
+            Some(padding),
+            Some(stride),
+            Some(2),
+            Some(5),
+            None,
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    /// Runs a low-precision layer in its SIMD mode and unpacked to 8 bits, comparing both to the
+    /// reference calculated in the SIMD mode
+    fn packed_layer_matches_reference<P: Packed>(pp_clip: u32) {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        // Odd number of channels leaves unused bits at the end of each pixel
+        let input = Tensor3::from_data_buffer(
+            5,
+            7,
+            6,
+            (0..5 * 7 * 6).map(|_| P::from_bits(rng.gen())).collect(),
+            Order3::CHW,
+        )
+        .unwrap();
+        let kernels = Tensor4::from_data_buffer(
+            3,
+            5,
+            3,
+            3,
+            (0..3 * 5 * 3 * 3)
+                .map(|_| P::from_bits(rng.gen()))
+                .collect(),
+            Order4::KCHW,
+        )
+        .unwrap();
+        let bias = test_utils::values(&mut rng, 3, -20..=20);
+        // Padding value fits all modes, so that it's the same unpacked
+        let padding = Padding {
+            top: 1,
+            right: 0,
+            left: 1,
+            bottom: 2,
+            padding_value: -1,
+        };
+        let stride = Stride { x: 1, y: 2 };
+
+        let expected = reference::conv2d_bias_relu(
+            &input.map(|x| x.value()),
+            &kernels.map(|x| x.value()),
+            Some(&bias),
+            true,
+            Some(padding.clone()),
+            Some(stride.clone()),
+            Some(0),
+            Some(pp_clip),
+            Some(P::SIMD_MODE),
+        )
+        .unwrap();
+
+        for (simd, simd_mode) in [(true, P::SIMD_MODE), (false, SimdBitMode::EightBits)] {
+            dla.backend().set_simd(simd);
+            let output: Tensor3<i8> = try_conv2d_bias_relu_packed(
+                &dla,
+                input.clone(),
+                kernels.clone(),
+                bias.clone(),
+                Some(padding.clone()),
+                Some(stride.clone()),
+                Some(0),
+                Some(pp_clip),
+            )
+            .unwrap();
+            assert_tensor_eq(&output, &expected);
+            assert_eq!(
+                dla.backend().read_reg(DLA_MAC_CTRL) as usize & DLA_SIMD_SELECT_BITMASK,
+                (simd_mode as usize) << DLA_SIMD_SELECT_OFFSET,
+                "simd {simd}"
+            );
+        }
+    }
+
+    #[test]
+    fn conv2d_packed_i4_matches_reference() {
+        packed_layer_matches_reference::<I4>(2);
+    }
+
+    #[test]
+    fn conv2d_packed_i2_matches_reference() {
+        packed_layer_matches_reference::<I2>(0);
+    }
+
+    #[test]
+    fn tiled_conv2d_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        // Input alone takes more than half of the memory banks, so the layer is split into tiles
+        let input = test_utils::tensor3(&mut rng, 32, 100, 90);
+        let kernels = test_utils::tensor4(&mut rng, 2, 32, 3, 3);
+
+        let output: Tensor3<i8> = try_conv2d(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            Some(Padding {
+                top: 1,
+                right: 1,
+                left: 1,
+                bottom: 1,
+                padding_value: 0,
+            }),
+            None,
+            None,
+            Some(8),
+            Some(4),
+            None,
+        )
+        .unwrap();
+        let expected = reference::conv2d_bias_relu(
+            &input,
+            &kernels,
+            None,
+            false,
+            Some(Padding {
+                top: 1,
+                right: 1,
+                left: 1,
+                bottom: 1,
+                padding_value: 0,
+            }),
+            None,
+            Some(8),
+            Some(4),
+            None,
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    #[test]
+    fn conv2d_into_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        // The second layer is split into tiles
+        for (channels, height, width) in [(3, 9, 7), (32, 100, 90)] {
+            let input = test_utils::tensor3(&mut rng, channels, height, width);
+            let kernels = test_utils::tensor4(&mut rng, 2, channels, 3, 3);
+            let bias = test_utils::values(&mut rng, 2, -500..=500);
+
+            let mut output = vec![0i8; 2 * (height - 2) * (width - 2)];
+            try_conv2d_into(
+                &dla,
+                input.view(),
+                kernels.view(),
+                Some(&bias),
+                true,
+                None,
+                None,
+                None,
+                Some(8),
+                Some(4),
+                None,
+                &mut output,
+            )
+            .unwrap();
+            let expected = reference::conv2d_bias_relu(
+                &input,
+                &kernels,
+                Some(&bias),
+                true,
+                None,
+                None,
+                Some(8),
+                Some(4),
+                None,
+            )
+            .unwrap();
+            assert_eq!(output, expected.to_buffer_with_order(Order3::HWC));
+        }
+    }
+
+    #[test]
+    fn conv2d_into_rejects_invalid_layers() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 4, 4);
+        let kernels = test_utils::tensor4(&mut rng, 2, 3, 3, 3);
+        let large_kernels = test_utils::tensor4(&mut rng, 2, 3, 5, 3);
+
+        let cases = [
+            (kernels.view(), None, 2 * 2 * 2 + 1),
+            (large_kernels.view(), None, 0),
+            (kernels.view(), Some(Stride { x: 0, y: 1 }), 0),
+        ];
+        for (kernels, stride, len) in cases {
+            let mut output = vec![0i8; len];
+            let result = try_conv2d_into(
+                &dla,
+                input.view(),
+                kernels,
+                None,
+                false,
+                None,
+                stride,
+                None,
+                None,
+                None,
+                None,
+                &mut output,
+            );
+            assert!(matches!(
+                result,
+                Err(DlaError::DimensionMismatch | DlaError::InvalidDimension)
+            ));
+        }
+    }
+
+    #[test]
+    fn conv2d_i32_matches_reference() {
+        let (dla, _lock) = simulated();
+        dla.backend().set_out32(true);
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 4, 6, 6);
+        let kernels = test_utils::tensor4(&mut rng, 3, 4, 2, 2);
+        let bias = test_utils::values(&mut rng, 3, i16::MIN..=i16::MAX);
+
+        let output: Tensor3<i32> = try_conv2d_bias(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            bias.clone(),
+            None,
+            None,
+            Some(0),
+            None,
+            None,
+        )
+        .unwrap();
+        let expected = reference::conv2d_bias_relu_i32(
+            &input,
+            &kernels,
+            Some(&bias),
+            false,
+            None,
+            None,
+            Some(0),
+            None,
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    /// Calculates dilated convolution + Bias + ReLU directly at the dilated kernel positions,
+    /// without expanding the kernels
+    #[allow(clippy::too_many_arguments)]
+    fn dilated_conv2d_reference(
+        input: &Tensor3<i8>,
+        kernels: &Tensor4<i8>,
+        bias: &[i16],
+        padding: &Padding,
+        stride: &Stride,
+        dilation: &Dilation,
+        mac_clip: u32,
+        pp_clip: u32,
+    ) -> Tensor3<i8> {
+        let (channels, height, width) = input.dimensions();
+        let (num_kernels, _, kernel_height, kernel_width) = kernels.dimensions();
+        let (dx, dy) = (dilation.x as usize, dilation.y as usize);
+        let (sx, sy) = (stride.x as usize, stride.y as usize);
+        let padded_width = width + (padding.left + padding.right) as usize;
+        let padded_height = height + (padding.top + padding.bottom) as usize;
+        let output_width = (padded_width - (kernel_width - 1) * dx - 1) / sx + 1;
+        let output_height = (padded_height - (kernel_height - 1) * dy - 1) / sy + 1;
+        let input = input.to_buffer_with_order(Order3::CHW);
+        let kernels = kernels.to_buffer_with_order(Order4::KCHW);
+
+        let mut output = Vec::new();
+        for out_y in 0..output_height {
+            for out_x in 0..output_width {
+                for k in 0..num_kernels {
+                    let mut sum = 0;
+                    for c in 0..channels {
+                        for ky in 0..kernel_height {
+                            let y = (out_y * sy + ky * dy) as isize - padding.top as isize;
+                            for kx in 0..kernel_width {
+                                let x = (out_x * sx + kx * dx) as isize - padding.left as isize;
+                                let value = if (0..height as isize).contains(&y)
+                                    && (0..width as isize).contains(&x)
+                                {
+                                    input[(c * height + y as usize) * width + x as usize] as i32
+                                } else {
+                                    padding.padding_value
+                                };
+                                let weight = kernels
+                                    [((k * channels + c) * kernel_height + ky) * kernel_width + kx];
+                                sum += value * weight as i32;
+                            }
+                        }
+                    }
+                    let value = (reference::mac_clip(sum, mac_clip) + bias[k] as i32).max(0);
+                    let value = reference::pp_clip(reference::saturate(value, 16), pp_clip, false);
+                    output.push(reference::saturate_output(value));
+                }
+            }
+        }
+        Tensor3::from_data_buffer(
+            num_kernels,
+            output_height,
+            output_width,
+            output,
+            Order3::HWC,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn dilated_conv2d_matches_direct_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 9, 11);
+        let kernels = test_utils::tensor4(&mut rng, 4, 3, 3, 2);
+        let bias = test_utils::values(&mut rng, 4, -500..=500);
+        let padding = Padding {
+            top: 1,
+            right: 2,
+            left: 0,
+            bottom: 1,
+            padding_value: 3,
+        };
+        // Dilation of 1 is a plain convolution, and the last dilation spans the padded input exactly
+        for (dilation, stride, output_size) in [
+            (Dilation { x: 1, y: 1 }, Stride { x: 1, y: 1 }, (12, 9)),
+            (Dilation { x: 2, y: 3 }, Stride { x: 2, y: 1 }, (6, 5)),
+            (Dilation { x: 12, y: 5 }, Stride { x: 1, y: 2 }, (1, 1)),
+        ] {
+            let output: Tensor3<i8> = try_conv2d_view(
+                &dla,
+                input.view(),
+                kernels.view(),
+                Some(&bias),
+                true,
+                Some(padding.clone()),
+                Some(stride.clone()),
+                Some(dilation.clone()),
+                Some(2),
+                Some(4),
+                None,
+            )
+            .unwrap();
+            let expected = dilated_conv2d_reference(
+                &input, &kernels, &bias, &padding, &stride, &dilation, 2, 4,
+            );
+            assert_tensor_eq(&output, &expected);
+            assert_eq!(
+                calculate_dilated_conv2d_out_param_dim(
+                    (11, 9),
+                    (2, 3),
+                    Some(padding.clone()),
+                    Some(stride),
+                    Some(dilation),
+                ),
+                Ok(output_size)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_dilations_are_errors() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 2, 5, 5);
+        let kernels = test_utils::tensor4(&mut rng, 2, 2, 3, 3);
+        let empty = test_utils::tensor4(&mut rng, 2, 2, 0, 3);
+        let run = |kernels: &Tensor4<i8>, x, y| {
+            try_conv2d::<i8, _>(
+                &dla,
+                input.clone(),
+                kernels.clone(),
+                None,
+                None,
+                Some(Dilation { x, y }),
+                None,
+                None,
+                None,
+            )
+            .unwrap_err()
+        };
+        assert_eq!(run(&kernels, 0, 1), DlaError::InvalidDimension);
+        // Dilated to 7x7, larger than the input
+        assert_eq!(run(&kernels, 3, 3), DlaError::DimensionMismatch);
+        assert_eq!(run(&empty, 2, 2), DlaError::DimensionMismatch);
+        assert_eq!(run(&empty, 1, 1), DlaError::DimensionMismatch);
+
+        let out_dim = |kernel, stride, dilation| {
+            calculate_dilated_conv2d_out_param_dim((5, 5), kernel, None, stride, dilation)
+        };
+        assert_eq!(
+            out_dim((3, 3), None, Some(Dilation { x: 3, y: 1 })),
+            Err(DlaError::DimensionMismatch)
+        );
+        assert_eq!(
+            out_dim((0, 3), None, None),
+            Err(DlaError::DimensionMismatch)
+        );
+        assert_eq!(
+            out_dim((3, 3), Some(Stride { x: 0, y: 1 }), None),
+            Err(DlaError::InvalidDimension)
+        );
+        assert_eq!(
+            out_dim((3, 3), None, Some(Dilation { x: 1, y: 0 })),
+            Err(DlaError::InvalidDimension)
+        );
+    }
+
+    #[test]
+    fn zero_stride_is_invalid_dimension() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 2, 4, 4);
+        let kernels = test_utils::tensor4(&mut rng, 2, 2, 2, 2);
+
+        let result: Result<Tensor3<i8>, _> = try_conv2d(
+            &dla,
+            input,
+            kernels,
+            None,
+            Some(Stride { x: 0, y: 1 }),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(result.unwrap_err(), DlaError::InvalidDimension);
+    }
+
+    #[test]
+    fn relu_and_bias_match_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 5, 4);
+        let bias = test_utils::values(&mut rng, 3, -300..=300);
+
+        let expected = reference::post_process(&input, None, true, Some(0)).unwrap();
+        assert_tensor_eq(&try_relu(&dla, input.clone(), Some(0)).unwrap(), &expected);
+
+        let expected = reference::post_process(&input, Some(&bias), false, Some(1)).unwrap();
+        assert_tensor_eq(&try_bias(&dla, input, bias, Some(1)).unwrap(), &expected);
+    }
+    #[test]
+    fn tvm_pp_clip_matches_clamped_bias_when_bias_fits() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 6, 5);
+        let kernels = test_utils::tensor4(&mut rng, 4, 3, 2, 2);
+        let bias = test_utils::values(&mut rng, 4, i16::MIN..=i16::MAX);
+        let bias_i32: Vec<i32> = bias.iter().map(|&x| x as i32).collect();
+
+        // Same clip and output as the FFI had with bias clamped to 16 bits
+        let pp_clip = crate::utils::tvm_bias_pp_clip(&bias_i32);
+        assert_eq!(pp_clip, 7);
+        let output: Tensor3<i8> = try_conv2d_bias_i32(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            bias_i32,
+            None,
+            None,
+            Some(2),
+            Some(pp_clip),
+            None,
+        )
+        .unwrap();
+        let expected: Tensor3<i8> = try_conv2d_bias(
+            &dla,
+            input,
+            kernels,
+            bias,
+            None,
+            None,
+            Some(2),
+            Some(7),
+            None,
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+
+        // Larger bias raises the clip to the shift
+        assert_eq!(crate::utils::tvm_bias_pp_clip(&[i16::MAX as i32 + 1]), 7);
+        assert_eq!(crate::utils::tvm_bias_pp_clip(&[i32::MIN]), 16);
+    }
+
+    #[test]
+    fn layer_pp_rounding_rounds_clipped_results() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 6, 5);
+        let kernels = test_utils::tensor4(&mut rng, 4, 3, 2, 2);
+        let bias = test_utils::values(&mut rng, 4, -500..=500);
+        let run = || -> Tensor3<i8> {
+            try_conv2d_view(
+                &dla,
+                input.view(),
+                kernels.view(),
+                Some(&bias),
+                true,
+                None,
+                None,
+                None,
+                Some(1),
+                Some(5),
+                None,
+            )
+            .unwrap()
+        };
+        let truncated = run();
+        dla.set_layer_pp_rounding(true);
+        let rounded = run();
+
+        let post_processed = reference::conv2d_bias_relu_i32(
+            &input,
+            &kernels,
+            Some(&bias),
+            true,
+            None,
+            None,
+            Some(1),
+            None,
+        )
+        .unwrap();
+        let expected = post_processed.map(|&x| {
+            reference::saturate_output(reference::pp_clip(reference::saturate(x, 16), 5, true))
+        });
+        assert_tensor_eq(&rounded, &expected);
+        assert_ne!(rounded.to_buffer(), truncated.to_buffer());
+    }
+
+    #[test]
+    fn layer_mac_saturation_bounds_mac_results() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 6, 5);
+        let kernels = test_utils::tensor4(&mut rng, 4, 3, 2, 2);
+        assert_eq!(
+            dla.set_layer_mac_saturation(1, 0),
+            Err(DlaError::InvalidSaturation)
+        );
+        dla.set_layer_mac_saturation(-2000, 3000).unwrap();
+
+        let output: Tensor3<i8> = try_conv2d(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            None,
+            None,
+            None,
+            Some(2),
+            Some(3),
+            None,
+        )
+        .unwrap();
+        dla.set_layer_mac_saturation(i32::MIN, i32::MAX).unwrap();
+        let mac = reference::conv2d_mac(&input, &kernels, None, None, None).unwrap();
+        assert!(mac
+            .to_buffer()
+            .iter()
+            .any(|&x| !(-2000..=3000).contains(&x)));
+        let expected = mac.map(|&x| {
+            let value = reference::mac_clip(x.clamp(-2000, 3000), 2);
+            reference::saturate_output(reference::pp_clip(reference::saturate(value, 16), 3, false))
+        });
+        assert_tensor_eq(&output, &expected);
+    }
+
+    #[test]
+    fn bias_shift_saturates_mac_when_mac_clip_is_0() {
+        let (dla, _lock) = simulated();
+        let input = Tensor3::from_data_buffer(4, 2, 2, vec![127; 16], Order3::CHW).unwrap();
+        let kernels = Tensor4::from_data_buffer(1, 4, 2, 2, vec![127; 16], Order4::KCHW).unwrap();
+        // MAC result of 258064 is shifted by 1 with the bias and saturates to i16::MAX
+        let bias = vec![-65536];
+
+        let output: Tensor3<i8> = try_conv2d_bias_i32(
+            &dla,
+            input,
+            kernels,
+            bias,
+            None,
+            None,
+            Some(0),
+            Some(7),
+            None,
+        )
+        .unwrap();
+        let saturated = ((i16::MAX as i32 - 32768) >> 6) as i8;
+        assert_eq!(output.to_buffer(), vec![saturated]);
+        assert_ne!(
+            saturated,
+            reference::saturate_output((258064 / 2 - 32768) >> 6)
+        );
+    }
+
+    /// Transposed convolution by scattering each input pixel over the output
+    fn conv_transpose2d_reference(
+        input: &Tensor3<i8>,
+        kernels: &Tensor4<i8>,
+        stride: usize,
+        padding: usize,
+        output_padding: usize,
+    ) -> Tensor3<i32> {
+        let (channels, height, width) = input.dimensions();
+        let (k, _, kh, kw) = kernels.dimensions();
+        let (full_h, full_w) = ((height - 1) * stride + kh, (width - 1) * stride + kw);
+        let mut full = vec![0i32; k * (full_h + output_padding) * (full_w + output_padding)];
+        let full_w_padded = full_w + output_padding;
+        for kernel in 0..k {
+            for c in 0..channels {
+                for y in 0..height {
+                    for x in 0..width {
+                        let value = *input.get(c, y, x).unwrap() as i32;
+                        for dy in 0..kh {
+                            for dx in 0..kw {
+                                let (oy, ox) = (y * stride + dy, x * stride + dx);
+                                full[(kernel * (full_h + output_padding) + oy) * full_w_padded
+                                    + ox] +=
+                                    value * *kernels.get(kernel, c, dy, dx).unwrap() as i32;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let (out_h, out_w) = (
+            full_h + output_padding - 2 * padding,
+            full_w + output_padding - 2 * padding,
+        );
+        let mut output = Vec::with_capacity(k * out_h * out_w);
+        for kernel in 0..k {
+            for y in padding..padding + out_h {
+                for x in padding..padding + out_w {
+                    output.push(full[(kernel * (full_h + output_padding) + y) * full_w_padded + x]);
+                }
+            }
+        }
+        Tensor3::from_data_buffer(k, out_h, out_w, output, Order3::CHW).unwrap()
+    }
+
+    #[test]
+    fn conv_transpose2d_with_output_padding_matches_reference() {
+        let (dla, _lock) = simulated();
+        dla.backend().set_out32(true);
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 2, 4, 3);
+        let kernels = test_utils::tensor4(&mut rng, 3, 2, 3, 3);
+        let padding = Padding {
+            top: 1,
+            right: 1,
+            left: 1,
+            bottom: 1,
+            padding_value: 0,
+        };
+
+        for output_padding in [0, 1] {
+            let output: Tensor3<i32> = try_conv_transpose2d(
+                &dla,
+                input.clone(),
+                kernels.clone(),
+                None,
+                Some(padding.clone()),
+                Some(Stride { x: 2, y: 2 }),
+                Some(OutputPadding {
+                    x: output_padding,
+                    y: output_padding,
+                }),
+                Some(0),
+                None,
+                None,
+            )
+            .unwrap();
+            let expected =
+                conv_transpose2d_reference(&input, &kernels, 2, 1, output_padding as usize);
+            assert_eq!(
+                output.dimensions(),
+                (3, 7 + output_padding as usize, 5 + output_padding as usize)
+            );
+            assert_tensor_eq(&output, &expected);
+        }
+
+        let result: Result<Tensor3<i32>, _> = try_conv_transpose2d(
+            &dla,
+            input,
+            kernels,
+            None,
+            None,
+            Some(Stride { x: 2, y: 2 }),
+            Some(OutputPadding { x: 2, y: 0 }),
+            Some(0),
+            None,
+            None,
+        );
+        assert_eq!(result.unwrap_err(), DlaError::DimensionMismatch);
+    }
+
+    #[test]
+    fn empty_inputs_and_zero_scales_are_errors() {
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 2, 3, 3);
+        let empty = test_utils::tensor3(&mut rng, 2, 0, 3);
+        assert_eq!(
+            try_upsample_nearest(input.clone(), 0, 2).unwrap_err(),
+            DlaError::InvalidDimension
+        );
+        assert_eq!(
+            try_upsample_bilinear(input.clone(), 2, 0).unwrap_err(),
+            DlaError::InvalidDimension
+        );
+        assert_eq!(
+            try_upsample_nearest(empty.clone(), 2, 2).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+        assert_eq!(
+            try_upsample_bilinear(empty.clone(), 2, 2).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+        assert_eq!(
+            zero_insert(&empty, &Stride { x: 2, y: 2 }).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+        assert_eq!(
+            zero_insert(&input, &Stride { x: 0, y: 2 }).unwrap_err(),
+            DlaError::InvalidDimension
+        );
+        let empty_kernels = test_utils::tensor4(&mut rng, 2, 2, 0, 3);
+        assert_eq!(
+            rotate_kernels(&empty_kernels).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+
+        let (dla, _lock) = simulated();
+        let result: Result<Tensor3<i8>, _> = try_conv_transpose2d(
+            &dla,
+            empty,
+            test_utils::tensor4(&mut rng, 2, 2, 3, 3),
+            None,
+            None,
+            Some(Stride { x: 2, y: 2 }),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(result.unwrap_err(), DlaError::DimensionMismatch);
+    }
+
+    #[test]
+    fn grouped_conv2d_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 6, 9, 7);
+        // Each group of 3 channels has 4 kernels
+        let kernels = test_utils::tensor4(&mut rng, 8, 3, 3, 3);
+        let bias = test_utils::values(&mut rng, 8, -500..=500);
+
+        let output: Tensor3<i8> = try_grouped_conv2d(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            bias.clone(),
+            None,
+            None,
+            Some(5),
+            Some(3),
+            None,
+            2,
+        )
+        .unwrap();
+        let groups: Vec<_> = (0..2)
+            .map(|g| {
+                reference::conv2d_bias_relu(
+                    &input.slice_channels(g * 3..(g + 1) * 3),
+                    &kernels.view().slice_kernels(g * 4..(g + 1) * 4).to_owned(),
+                    Some(&bias[g * 4..(g + 1) * 4]),
+                    false,
+                    None,
+                    None,
+                    Some(5),
+                    Some(3),
+                    None,
+                )
+                .unwrap()
+            })
+            .collect();
+        let views: Vec<_> = groups.iter().map(Tensor3::view).collect();
+        assert_tensor_eq(&output, &concat_channels(&views));
+    }
+
+    #[test]
+    fn depthwise_conv2d_matches_grouped_conv2d() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        // Enough channels to need more than one batch
+        let input = test_utils::tensor3(&mut rng, 64, 60, 60);
+        let kernels = test_utils::tensor4(&mut rng, 128, 1, 3, 3);
+        let bias = test_utils::values(&mut rng, 128, -500..=500);
+        let padding = Padding {
+            top: 1,
+            right: 0,
+            left: 1,
+            bottom: 2,
+            padding_value: -3,
+        };
+        let stride = Stride { x: 2, y: 1 };
+        let batch = depthwise_batch_size::<i8>(
+            &input,
+            &kernels,
+            2,
+            true,
+            Some(padding.clone()),
+            Some(stride.clone()),
+        );
+        assert!(1 < batch && batch < 64);
+
+        let output: Tensor3<i8> = try_depthwise_conv2d(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            Some(bias.clone()),
+            Some(padding.clone()),
+            Some(stride.clone()),
+            Some(5),
+            Some(3),
+            None,
+        )
+        .unwrap();
+        let expected: Tensor3<i8> = try_grouped_conv2d(
+            &dla,
+            input,
+            kernels,
+            bias,
+            Some(padding),
+            Some(stride),
+            Some(5),
+            Some(3),
+            None,
+            64,
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    #[test]
+    fn add_falls_back_to_cpu_when_weights_are_inexact() {
+        // Scales that 8-bit weights represent exactly run on DLA
+        let params = add_params([1.0, 0.5], [0, 0], 0, Rounding::TfLite);
+        assert_eq!(dla_add_params(&params), Some(([64, 32], 32, 6)));
+
+        // The second scale rounds to a zero weight at shift 6. Its error times the largest
+        // distance from the zero point must stay within half an output step.
+        let cases = [
+            // 0.2 / 64 * 128 = 0.4
+            ([0, 0], 0.2, true),
+            // 0.3 / 64 * 128 = 0.6
+            ([0, 0], 0.3, false),
+            // 0.2 / 64 * 255 = 0.8
+            ([0, 127], 0.2, false),
+        ];
+        for (zero_points, error, on_dla) in cases {
+            let params = add_params([1.0, error / 64.0], zero_points, 0, Rounding::TfLite);
+            assert_eq!(
+                dla_add_params(&params).is_some(),
+                on_dla,
+                "{zero_points:?}, {error}"
+            );
+        }
+
+        // Falling back gives exactly the results of the CPU
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let a = test_utils::tensor3(&mut rng, 3, 4, 5);
+        let b = test_utils::tensor3(&mut rng, 3, 4, 5);
+        let params = add_params([1.0, 0.3 / 64.0], [0, 0], 0, Rounding::TfLite);
+        let output = try_add(&dla, a.clone(), b.clone(), &params).unwrap();
+        let expected = a
+            .zip_map(&b, |&a, &b| params.add(a as i32, b as i32))
+            .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    #[test]
+    fn add_on_dla_is_within_one_of_cpu() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let mut on_dla = 0;
+
+        for _ in 0..200 {
+            let scales = [0, 1].map(|_| 2f64.powf(rng.gen_range(-8.0..2.0)));
+            let zero_points = [0, 1].map(|_| rng.gen_range(-128..=127));
+            let output_zero_point = rng.gen_range(-128..=127);
+            for rounding in [Rounding::Upward, Rounding::ToNearest, Rounding::TfLite] {
+                let params = add_params(scales, zero_points, output_zero_point, rounding);
+                let Some((weights, bias, shift)) = dla_add_params(&params) else {
+                    continue;
+                };
+                on_dla += 1;
+
+                // DLA sums without MAC clip and post-processes the sum with bias and PP clip
+                for a in i8::MIN as i32..=i8::MAX as i32 {
+                    for b in i8::MIN as i32..=i8::MAX as i32 {
+                        let sum = weights[0] as i32 * a + weights[1] as i32 * b + bias as i32;
+                        let actual = i8::from_post_processed(sum, shift, false) as i32;
+                        let expected = params.add(a, b) as i32;
+                        assert!(
+                            (actual - expected).abs() <= 1,
+                            "{params:?}: {a} + {b} = {actual}, expected {expected}"
+                        );
+                    }
+                }
+
+                let a = test_utils::tensor3(&mut rng, 2, 3, 4);
+                let b = test_utils::tensor3(&mut rng, 2, 3, 4);
+                let output = try_add(&dla, a.clone(), b.clone(), &params).unwrap();
+                let expected = a
+                    .zip_map(&b, |&a, &b| params.add(a as i32, b as i32))
+                    .unwrap();
+                for (actual, expected) in output
+                    .to_buffer_with_order(Order3::HWC)
+                    .into_iter()
+                    .zip(expected.to_buffer_with_order(Order3::HWC))
+                {
+                    assert!((actual as i32 - expected as i32).abs() <= 1, "{params:?}");
+                }
+            }
+        }
+        // Make sure the guarantee was checked on enough parameters that run on DLA
+        assert!(on_dla > 50, "{on_dla}");
+    }
+
+    #[test]
+    fn pooling_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 8, 7);
+
+        for size in [1, 3, MAX_POOL_SIZE + 1] {
+            let expected = reference::pool2d(
+                &input,
+                Pooling {
+                    mode: PoolMode::Max,
+                    size,
+                },
+            )
+            .unwrap();
+            assert_tensor_eq(
+                &try_max_pool2d(&dla, input.clone(), size).unwrap(),
+                &expected,
+            );
+
+            let expected = reference::pool2d(
+                &input,
+                Pooling {
+                    mode: PoolMode::Average,
+                    size,
+                },
+            )
+            .unwrap();
+            assert_tensor_eq(
+                &try_avg_pool2d(&dla, input.clone(), size).unwrap(),
+                &expected,
+            );
+        }
+        assert_eq!(
+            try_max_pool2d(&dla, input.clone(), 0).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+        assert_eq!(
+            try_avg_pool2d(&dla, input, 9).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+    }
+
+    #[test]
+    fn conv2d_bias_relu_pool_with_large_window_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 4, 12, 11);
+        let kernels = test_utils::tensor4(&mut rng, 2, 4, 2, 2);
+        let bias = test_utils::values(&mut rng, 2, -500..=500);
+        let pooling = Pooling {
+            mode: PoolMode::Max,
+            size: MAX_POOL_SIZE + 1,
+        };
+
+        let output = try_conv2d_bias_relu_pool(
+            &dla,
+            input.clone(),
+            kernels.clone(),
+            Some(bias.clone()),
+            true,
+            pooling,
+            None,
+            None,
+            Some(3),
+            Some(4),
+            None,
+        )
+        .unwrap();
+        let expected = reference::conv2d_bias_relu(
+            &input,
+            &kernels,
+            Some(&bias),
+            true,
+            None,
+            None,
+            Some(3),
+            Some(4),
+            None,
+        )
+        .unwrap();
+        let expected = reference::pool2d(&expected, pooling).unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
 }
@@ -1,17 +1,31 @@
 //! # DLA driver
 //!
 //! Implements driver for sochub headsail SoC's deep learning accelerator.
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate alloc;
 
+pub mod bank;
+pub mod job;
 pub mod layers;
+pub mod model;
+pub mod quant;
+pub mod reference;
+pub mod sequential;
+pub mod simd;
 pub mod tensor3;
 pub mod tensor4;
 pub mod utils;
 
+#[cfg(all(target_arch = "riscv64", feature = "irq"))]
+mod irq;
+#[cfg(all(target_arch = "riscv64", feature = "irq"))]
+pub use irq::on_interrupt;
 mod mmap;
+#[cfg(test)]
+mod test_utils;
+pub use mmap::EXTERNAL_BIT;
 pub use mmap::{
     DLA0_ADDR, MEMORY_BANK_0_OFFSET, MEMORY_BANK_10_OFFSET, MEMORY_BANK_11_OFFSET,
     MEMORY_BANK_12_OFFSET, MEMORY_BANK_13_OFFSET, MEMORY_BANK_14_OFFSET, MEMORY_BANK_15_OFFSET,
@@ -20,9 +34,6 @@ pub use mmap::{
     MEMORY_BANK_9_OFFSET, MEMORY_BANK_BASE_ADDR,
 };
 
-const DEFAULT_INPUT_BANK: MemoryBank = MemoryBank::Bank0;
-const DEFAULT_KERNEL_BANK: MemoryBank = MemoryBank::Bank4;
-const DEFAULT_OUTPUT_BANK: MemoryBank = MemoryBank::Bank10;
 const DEFAULT_BIAS_ADDR: u32 = MemoryBank::Bank15.addr() as u32;
 const DEFAULT_KERNEL_SIZE: KernelSize = KernelSize {
     s_channels: 1,
@@ -43,17 +54,113 @@ const DEFAULT_PADDING: Padding = Padding {
     padding_value: 0,
 };
 const DEFAULT_STRIDE: Stride = Stride { x: 1, y: 1 };
+const DEFAULT_DILATION: Dilation = Dilation { x: 1, y: 1 };
 const DEFAULT_MAC_CLIP: u32 = 0;
 const DEFAULT_PP_CLIP: u32 = 8;
 const DEFAULT_SIMD_MODE: SimdBitMode = SimdBitMode::EightBits;
+const DEFAULT_MAC_SAT_MAX: i32 = i32::MAX;
+const DEFAULT_MAC_SAT_MIN: i32 = i32::MIN;
+const DEFAULT_OUTPUT_WIDTH: OutputWidth = OutputWidth::EightBits;
+const DEFAULT_HANDSHAKE_TIMEOUT: usize = 0x1000_0000;
+const DEFAULT_POWER_TIMEOUT: usize = 0x10_0000;
+
+/// Whether [`sequential::Sequential`] powers DLA down after running a model
+static AUTO_POWER_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Output element width of the most recently configured layer, in bits
+static OUTPUT_WIDTH: AtomicU8 = AtomicU8::new(DEFAULT_OUTPUT_WIDTH as u8);
+
+/// MAC saturation bounds of the layers run by the layer functions
+static LAYER_MAC_SAT_MIN: AtomicI32 = AtomicI32::new(DEFAULT_MAC_SAT_MIN);
+static LAYER_MAC_SAT_MAX: AtomicI32 = AtomicI32::new(DEFAULT_MAC_SAT_MAX);
+
+/// Whether the layers run by the layer functions round post-processing results
+static LAYER_PP_ROUNDING: AtomicBool = AtomicBool::new(false);
 
 use alloc::vec::Vec;
-use core::ptr;
+use backend::{Mmio, RegisterAccess};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, Ordering};
+#[cfg(target_arch = "riscv64")]
 use headsail_bsp::{sprint, sprintln};
+
+/// There is no UART on host, so prints are dropped
+#[cfg(not(target_arch = "riscv64"))]
+macro_rules! sprintln {
+    ($($arg:tt)*) => {{}};
+}
 use mmap::*;
 
-/// Clip error type
-struct InvalidClip(u32);
+/// Errors reported by the DLA driver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DlaError {
+    /// Clip amount exceeds the maximum supported by the hardware
+    InvalidClip(u32),
+    /// Layer data does not fit into the DLA's memory banks
+    BankOverflow,
+    /// Tensor or buffer dimensions do not match each other
+    DimensionMismatch,
+    /// Dimension, stride or window size is zero
+    InvalidDimension,
+    /// Value does not fit into its register field
+    RegisterFieldOverflow,
+    /// DLA did not complete the calculation in time
+    Timeout,
+    /// MAC saturation lower bound is above the upper bound
+    InvalidSaturation,
+    /// Output is read with a different element width than DLA was configured to write
+    OutputWidthMismatch,
+    /// Memory at the address can't be accessed by DLA
+    UnreachableAddress,
+    /// Model image is malformed or of an unsupported version
+    InvalidModel,
+    /// DLA is still running a layer of an unfinished job
+    Busy,
+    /// Operation needs a capability the backend doesn't have
+    Unsupported,
+}
+
+impl core::fmt::Display for DlaError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DlaError::InvalidClip(amount) => write!(f, "invalid clip amount {}", amount),
+            DlaError::BankOverflow => write!(f, "layer does not fit into DLA memory banks"),
+            DlaError::DimensionMismatch => write!(f, "tensor dimensions do not match"),
+            DlaError::InvalidDimension => write!(f, "dimension must be non-zero"),
+            DlaError::RegisterFieldOverflow => write!(f, "value does not fit into register field"),
+            DlaError::Timeout => write!(f, "DLA did not complete in time"),
+            DlaError::InvalidSaturation => write!(f, "invalid MAC saturation bounds"),
+            DlaError::OutputWidthMismatch => write!(f, "output read with wrong element width"),
+            DlaError::UnreachableAddress => write!(f, "address not reachable by DLA"),
+            DlaError::InvalidModel => write!(f, "invalid model image"),
+            DlaError::Busy => write!(f, "DLA is busy with another layer"),
+            DlaError::Unsupported => write!(f, "operation not supported by DLA backend"),
+        }
+    }
+}
+
+/// Checks that `value` fits into the register field described by `offset` and `bitmask`
+fn check_field(value: u32, offset: usize, bitmask: usize) -> Result<(), DlaError> {
+    if value as usize > bitmask >> offset {
+        return Err(DlaError::RegisterFieldOverflow);
+    }
+    Ok(())
+}
+
+/// Checks that a dimension stored as `value - 1` fits into its register field
+fn check_dim_field(value: u32, offset: usize, bitmask: usize) -> Result<(), DlaError> {
+    let value = value.checked_sub(1).ok_or(DlaError::InvalidDimension)?;
+    check_field(value, offset, bitmask)
+}
+
+/// Reads up to `chunk.len()` values into `chunk`, returning how many were read
+fn fill_chunk(data: &mut impl Iterator<Item = i8>, chunk: &mut [u8]) -> usize {
+    let mut len = 0;
+    for (byte, value) in chunk.iter_mut().zip(data) {
+        *byte = value as u8;
+        len += 1;
+    }
+    len
+}
 
 /// Dimensions of kernel
 pub struct KernelSize {
@@ -105,11 +212,141 @@ pub struct Stride {
     pub y: u32,
 }
 
+/// Conv2d dilation, i.e. spacing between the kernel elements
+///
+/// DLA has no dilation support, so kernels are expanded with zeros in the driver, see
+/// [`utils::dilate_kernels`].
+#[derive(Clone)]
+pub struct Dilation {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Rows and columns added to the bottom and right of a transposed convolution's output
+///
+/// Selects between the input sizes a strided convolution maps to the same output size, like
+/// `output_padding` of PyTorch's `ConvTranspose2d`. Must be smaller than the stride.
+#[derive(Clone)]
+pub struct OutputPadding {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Pooling operation of DLA's post-processor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolMode {
+    Max,
+    Average,
+}
+
+/// Largest pooling window DLA's post-processor supports, as the window size - 1 is a 2-bit field
+pub const MAX_POOL_SIZE: u32 = (DLA_MAX_BITMASK >> DLA_MAX_OFFSET) as u32 + 1;
+
+/// Pooling done by DLA's post-processor after bias and ReLU
+///
+/// Pools non-overlapping `size` x `size` windows, i.e. stride equals the window size. Rows and
+/// columns that don't fill a whole window are dropped.
+///
+/// DLA pools windows of at most [`MAX_POOL_SIZE`]. Pooling is done on CPU instead unless feature
+/// `hw-pooling` is enabled.
+///
+/// NOTE: The VP doesn't model pooling. Encoding of the PP_CTRL fields follows their names in the
+/// register map: pool mode selects max or average and the max field holds the window size - 1.
+/// It hasn't been verified against RTL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pooling {
+    pub mode: PoolMode,
+    pub size: u32,
+}
+
+impl KernelSize {
+    /// Checks that kernel dimensions can be represented in DLA's registers
+    fn validate(&self) -> Result<(), DlaError> {
+        check_dim_field(
+            self.s_channels,
+            DLA_BUF_KERNEL_0_S_CHANNELS_OFFSET,
+            DLA_BUF_KERNEL_0_S_CHANNELS_BITMASK,
+        )?;
+        check_dim_field(
+            self.width,
+            DLA_BUF_KERNEL_0_WIDTH_OFFSET,
+            DLA_BUF_KERNEL_0_WIDTH_BITMASK,
+        )?;
+        check_dim_field(
+            self.height,
+            DLA_BUF_KERNEL_0_HEIGHT_OFFSET,
+            DLA_BUF_KERNEL_0_HEIGHT_BITMASK,
+        )?;
+        check_dim_field(
+            self.kernels,
+            DLA_BUF_KERNEL_1_NUM_OFFSET,
+            DLA_BUF_KERNEL_1_NUM_BITMASK,
+        )
+    }
+}
+
+impl InputSize {
+    /// Checks that input dimensions can be represented in DLA's registers
+    fn validate(&self) -> Result<(), DlaError> {
+        check_dim_field(
+            self.channels,
+            DLA_BUF_INPUT_CHANNELS_OFFSET,
+            DLA_BUF_INPUT_CHANNELS_BITMASK,
+        )?;
+        check_dim_field(
+            self.width,
+            DLA_BUF_INPUT_WIDTH_OFFSET,
+            DLA_BUF_INPUT_WIDTH_BITMASK,
+        )?;
+        check_dim_field(
+            self.height,
+            DLA_BUF_INPUT_HEIGHT_OFFSET,
+            DLA_BUF_INPUT_HEIGHT_BITMASK,
+        )
+    }
+}
+
+impl Padding {
+    /// Checks that padding amounts can be represented in DLA's registers
+    fn validate(&self) -> Result<(), DlaError> {
+        check_field(self.top, DLA_BUF_PAD_TOP_OFFSET, DLA_BUF_PAD_TOP_BITMASK)?;
+        check_field(
+            self.right,
+            DLA_BUF_PAD_RIGHT_OFFSET,
+            DLA_BUF_PAD_RIGHT_BITMASK,
+        )?;
+        check_field(
+            self.bottom,
+            DLA_BUF_PAD_BOTTOM_OFFSET,
+            DLA_BUF_PAD_BOTTOM_BITMASK,
+        )?;
+        check_field(self.left, DLA_BUF_PAD_LEFT_OFFSET, DLA_BUF_PAD_LEFT_BITMASK)
+    }
+}
+
+impl Pooling {
+    /// Checks that pooling window can be represented in DLA's registers
+    fn validate(&self) -> Result<(), DlaError> {
+        check_dim_field(self.size, DLA_MAX_OFFSET, DLA_MAX_BITMASK)
+    }
+}
+
+impl Stride {
+    /// Checks that stride can be represented in DLA's registers
+    fn validate(&self) -> Result<(), DlaError> {
+        check_dim_field(self.x, DLA_BUF_STRIDE_X_OFFSET, DLA_BUF_STRIDE_X_BITMASK)?;
+        check_dim_field(self.y, DLA_BUF_STRIDE_Y_OFFSET, DLA_BUF_STRIDE_Y_BITMASK)
+    }
+}
+
 /// Configures DLA for performing calculation for layers
+///
+/// Memory banks are always given, e.g. from [`bank::BankRange`] handles, or kept as they are
+/// configured with [`BankSelection::Keep`]. Other fields that are `None` use the driver defaults.
 pub struct LayerConfig {
-    pub input_bank: Option<MemoryBank>,
-    pub kernel_bank: Option<MemoryBank>,
-    pub output_bank: Option<MemoryBank>,
+    pub input_bank: BankSelection,
+    pub kernel_bank: BankSelection,
+    pub output_bank: BankSelection,
     pub bias_addr: Option<u32>,
     pub pp_enabled: bool,
     pub relu_enabled: bool,
@@ -120,7 +357,81 @@ pub struct LayerConfig {
     pub stride: Option<Stride>,
     pub mac_clip: Option<u32>,
     pub pp_clip: Option<u32>,
+    /// Upper bound MAC results are saturated to. `MAC_SAT_MAX` and `MAC_SAT_MIN` are left as they
+    /// are if neither bound is set
+    pub mac_sat_max: Option<i32>,
+    /// Lower bound MAC results are saturated to
+    pub mac_sat_min: Option<i32>,
+    /// Rounds post-processing results instead of truncating them when clipping
+    pub pp_rounding: bool,
     pub simd_mode: Option<SimdBitMode>,
+    pub pooling: Option<Pooling>,
+    /// Width of the elements written to the output bank
+    pub output_width: Option<OutputWidth>,
+}
+
+/// Configures DLA's post-processor to run on data in the input bank without the MAC array
+///
+/// Bias, ReLU, clipping and rounding are applied to the 8-bit input values as if they were MAC
+/// results. The output has the dimensions of the input and is written to the output bank in the
+/// same order as layer outputs. Memory banks and fields that are `None` are handled as in
+/// [`LayerConfig`].
+pub struct PpConfig {
+    pub input_bank: BankSelection,
+    pub output_bank: BankSelection,
+    pub bias_addr: Option<u32>,
+    pub relu_enabled: bool,
+    pub bias_enabled: bool,
+    pub input_size: Option<InputSize>,
+    pub pp_clip: Option<u32>,
+    /// Rounds post-processing results instead of truncating them when clipping
+    pub pp_rounding: bool,
+}
+
+/// Memory bank configured for a layer's data
+#[derive(Clone, Copy)]
+pub enum BankSelection {
+    /// Data is in the given bank
+    Bank(MemoryBank),
+    /// Data is in the bank DLA is currently configured with, e.g. the previous layer's
+    Keep,
+}
+
+impl BankSelection {
+    /// Returns the bank to configure, `None` for no change
+    fn bank(self) -> Option<MemoryBank> {
+        match self {
+            BankSelection::Bank(bank) => Some(bank),
+            BankSelection::Keep => None,
+        }
+    }
+}
+
+/// Width of the elements DLA writes to its output bank
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputWidth {
+    /// Post-processed results saturated to `i8`
+    EightBits = 8,
+    /// Post-processed results saturated to `i16`, which no DLA writes as of now
+    SixteenBits = 16,
+    /// Raw MAC results, requires `mac_clip` of 0 and a backend that writes them, see
+    /// [`RegisterAccess::has_out32`]
+    ThirtyTwoBits = 32,
+}
+
+impl OutputWidth {
+    /// Size of one output element in bytes
+    pub fn bytes(self) -> usize {
+        self as usize / 8
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            16 => OutputWidth::SixteenBits,
+            32 => OutputWidth::ThirtyTwoBits,
+            _ => OutputWidth::EightBits,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -183,12 +494,18 @@ impl core::ops::Add<usize> for MemoryBank {
     type Output = MemoryBank;
 
     fn add(self, other: usize) -> Self::Output {
-        let value: usize = self.into();
-        MemoryBank::try_from((value + other) as u32).unwrap()
+        self.checked_add(other)
+            .expect("memory bank index out of range")
     }
 }
 
 impl MemoryBank {
+    /// Returns the bank `other` banks after this one, or `None` if it's past the last bank
+    pub fn checked_add(self, other: usize) -> Option<MemoryBank> {
+        let value = usize::from(self).checked_add(other)?;
+        MemoryBank::try_from(u32::try_from(value).ok()?).ok()
+    }
+
     const fn offset(&self) -> usize {
         match self {
             MemoryBank::Bank0 => MEMORY_BANK_0_OFFSET,
@@ -235,8 +552,37 @@ macro_rules! get_bits {
     };
 }
 
+// Declared after the register macros, which they use
+pub mod backend;
+pub mod stats;
+
+/// Translates an address in CPU's memory map to the address DLA sees the same memory at
+///
+/// With feature `hpc`, memory outside HPC is seen by the CPU above [`EXTERNAL_BIT`], while DLA uses
+/// 32-bit addresses. HPC's local memory can't be reached by DLA.
+///
+/// # Errors
+///
+/// Returns [`DlaError::UnreachableAddress`] if DLA can't access the address.
+pub fn dla_addr(cpu_addr: usize) -> Result<u32, DlaError> {
+    let addr = cpu_addr
+        .checked_sub(EXTERNAL_BIT)
+        .ok_or(DlaError::UnreachableAddress)?;
+    u32::try_from(addr).map_err(|_| DlaError::UnreachableAddress)
+}
+
+/// Translates an address seen by DLA to CPU's memory map, see [`dla_addr`]
+pub fn cpu_addr(dla_addr: u32) -> usize {
+    dla_addr as usize + EXTERNAL_BIT
+}
+
 /// DLA driver struct
-pub struct Dla {}
+///
+/// Accesses the hardware through `B`, which is memory mapped I/O by default. See [`backend`] for
+/// a simulated alternative.
+pub struct Dla<B: RegisterAccess = Mmio> {
+    backend: B,
+}
 
 impl Default for Dla {
     fn default() -> Self {
@@ -246,32 +592,72 @@ impl Default for Dla {
 
 impl Dla {
     pub fn new() -> Self {
-        Dla {}
+        let dla = Dla { backend: Mmio };
+        #[cfg(all(target_arch = "riscv64", feature = "irq"))]
+        dla.enable_irq();
+        dla
+    }
+}
+
+impl<B: RegisterAccess> Dla<B> {
+    /// Creates a driver that accesses DLA through the given backend
+    pub fn with_backend(backend: B) -> Self {
+        Dla { backend }
     }
+
+    /// Returns the backend used for accessing DLA
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
     /// Writes u32 to dla configuration registers at offset
     fn write_u32(&self, offset: usize, value: u32) {
-        unsafe { ptr::write_volatile((DLA0_ADDR + offset) as *mut _, value) }
+        self.backend.write_reg(offset, value)
     }
 
     /// Reads u32 from dla configuration registers at offset
     fn read_u32(&self, offset: usize) -> u32 {
-        unsafe { ptr::read_volatile((DLA0_ADDR + offset) as *const _) }
+        self.backend.read_reg(offset)
     }
 
     /// Writes buffer DLA's data bank(s) based on offset
     pub fn write_data_bank(&self, offset: usize, buf: &mut [i8]) {
+        self.write_data_bank_iter(offset, buf.iter().copied())
+    }
+
+    /// Writes values to DLA's data bank(s) based on offset as they are read
+    ///
+    /// Lets layer data be written straight from the caller's memory in the order DLA needs, without
+    /// collecting it into a buffer first. Full 64-bit chunks are written as two 32-bit words when
+    /// `offset` is word aligned, the rest byte by byte.
+    ///
+    /// NOTE: Data is copied with the CPU. DLA has no DMA path to its banks the driver could use, see
+    /// `DLA_DMA_CTRL` in the memory map.
+    pub fn write_data_bank_iter(&self, offset: usize, data: impl IntoIterator<Item = i8>) {
         /* NOTE:(20240604 vaino-waltteri.granat@tuni.fi)
          * After RTL test examination, it was found that DLA needs to
          * be written by reversing the order of bytes in each 64-bit chunk
          */
-        for (cidx, chunk) in buf.chunks(8).enumerate() {
-            for (i, b) in chunk.iter().rev().enumerate() {
-                unsafe {
-                    ptr::write_volatile(
-                        (MEMORY_BANK_BASE_ADDR + offset + cidx * 8 + i) as *mut _,
-                        *b,
-                    )
-                };
+        let word_aligned = (MEMORY_BANK_BASE_ADDR + offset) & 0b11 == 0;
+        let mut data = data.into_iter();
+        let mut chunk = [0u8; 8];
+        for cidx in 0.. {
+            let addr = MEMORY_BANK_BASE_ADDR + offset + cidx * 8;
+            let len = fill_chunk(&mut data, &mut chunk);
+            if len == chunk.len() && word_aligned {
+                // Reversed chunk is its big-endian halves stored in swapped order
+                let [b0, b1, b2, b3, b4, b5, b6, b7] = chunk;
+                self.backend
+                    .write_mem_u32(addr, u32::from_be_bytes([b4, b5, b6, b7]));
+                self.backend
+                    .write_mem_u32(addr + 4, u32::from_be_bytes([b0, b1, b2, b3]));
+                continue;
+            }
+            for (i, b) in chunk[..len].iter().rev().enumerate() {
+                self.backend.write_mem_u8(addr + i, *b);
+            }
+            if len < chunk.len() {
+                break;
             }
         }
     }
@@ -281,77 +667,132 @@ impl Dla {
         if cfg!(feature = "vp") {
             let mut result: u128 = 0;
             for i in 0..4 {
-                result |= (unsafe {
-                    ptr::read_volatile(
-                        (MEMORY_BANK_BASE_ADDR + bank.offset() + offset + (i * 4)) as *mut u32,
-                    )
-                } as u128)
+                result |= (self
+                    .backend
+                    .read_mem_u32(MEMORY_BANK_BASE_ADDR + bank.offset() + offset + (i * 4))
+                    as u128)
                     << (32 * i)
             }
             result
         } else {
-            unsafe {
-                ptr::read_volatile((MEMORY_BANK_BASE_ADDR + bank.offset() + offset) as *const _)
-            }
+            self.backend
+                .read_mem_u128(MEMORY_BANK_BASE_ADDR + bank.offset() + offset)
         }
     }
 
     /// Reads len number of bytes from DLA's memory banks, starting from bank given as parameter
     fn read_data_bank(&self, bank: MemoryBank, len: usize) -> Vec<u8> {
         let mut res: Vec<u8> = Vec::with_capacity(len);
+        self.read_data_bank_rows(bank, len, |bytes| res.extend_from_slice(bytes));
+        res
+    }
 
+    /// Reads len number of bytes from DLA's memory banks, starting from bank given as parameter,
+    /// and passes them to `f` one 128-bit address at a time
+    fn read_data_bank_rows(&self, bank: MemoryBank, len: usize, mut f: impl FnMut(&[u8])) {
         let mut next_bank_offset = 0;
-        while res.len() < len {
+        while next_bank_offset < len {
             let data = self.read_data_bank_offset(bank, next_bank_offset);
-            let bytes_remaining = len - res.len();
-            let bytes_to_copy = core::cmp::min(16, bytes_remaining);
+            let bytes_to_copy = core::cmp::min(16, len - next_bank_offset);
 
             // Copy everything from one 128-bit address
-            for i in 0..bytes_to_copy {
-                let byte = ((data >> (i * 8)) & 0xFF) as u8;
-                res.push(byte)
+            let mut bytes = [0u8; 16];
+            for (i, byte) in bytes.iter_mut().enumerate().take(bytes_to_copy) {
+                *byte = ((data >> (i * 8)) & 0xFF) as u8;
             }
+            f(&bytes[..bytes_to_copy]);
             next_bank_offset += 0x10;
         }
-        res
+    }
+
+    /// Rewrites len bytes of layer output in place so that DLA can read them as layer input
+    ///
+    /// DLA reads inputs with the bytes of each 64-bit chunk reversed compared to how the
+    /// post-processor writes outputs, see [`Dla::write_data_bank`].
+    pub(crate) fn reorder_output_for_input(&self, bank: MemoryBank, len: usize) {
+        let mut data: Vec<i8> = self
+            .read_data_bank(bank, len)
+            .into_iter()
+            .map(|x| x as i8)
+            .collect();
+        self.write_data_bank(bank.offset(), &mut data);
     }
 
     /// Reads len amount of bytes from DLA's output bank(s)
-    pub fn read_output_i32(&self, len: usize) -> Vec<i32> {
-        let bytes = self.read_data_bank(self.get_output_bank(), len * 4);
-        let mut result = Vec::with_capacity(bytes.len() / 4);
+    ///
+    /// Returns [`DlaError::OutputWidthMismatch`] if the layer wasn't configured to write `i32`.
+    ///
+    /// NOTE: DLA has no register for the output width. Only the VP writes 32-bit outputs, for
+    /// layers without MAC clip when `DLA_VP_OUT32` is set in its environment.
+    pub fn read_output_i32(&self, len: usize) -> Result<Vec<i32>, DlaError> {
+        let mut result = vec![0; len];
+        self.read_output_i32_into(&mut result)?;
+        Ok(result)
+    }
 
-        for pair in bytes.chunks_exact(4) {
-            let combined = ((pair[0] as i32) << 24)
-                | ((pair[1] as i32) << 16)
-                | ((pair[2] as i32) << 8)
-                | (pair[3] as i32);
-            result.push(combined);
-        }
-        result
+    /// Fills `output` from DLA's output bank(s), see [`Dla::read_output_i32`]
+    pub fn read_output_i32_into(&self, output: &mut [i32]) -> Result<(), DlaError> {
+        self.check_output_width(OutputWidth::ThirtyTwoBits)?;
+        let len = output.len() * 4;
+        let mut values = output.iter_mut();
+        self.read_data_bank_rows(self.get_output_bank()?, len, |bytes| {
+            for (pair, value) in bytes.chunks_exact(4).zip(values.by_ref()) {
+                *value = ((pair[0] as i32) << 24)
+                    | ((pair[1] as i32) << 16)
+                    | ((pair[2] as i32) << 8)
+                    | (pair[3] as i32);
+            }
+        });
+        Ok(())
     }
 
     /// Reads len amount of bytes from DLA's output bank(s)
-    pub fn read_output_i16(&self, len: usize) -> Vec<i16> {
-        let bytes = self.read_data_bank(self.get_output_bank(), len * 2);
-        let mut result = Vec::with_capacity(bytes.len() / 2);
+    ///
+    /// Returns [`DlaError::OutputWidthMismatch`] if the layer wasn't configured to write `i16`.
+    pub fn read_output_i16(&self, len: usize) -> Result<Vec<i16>, DlaError> {
+        let mut result = vec![0; len];
+        self.read_output_i16_into(&mut result)?;
+        Ok(result)
+    }
 
-        for pair in bytes.chunks_exact(2) {
-            let combined = ((pair[0] as i16) << 8) | (pair[1] as i16 & 0xFF);
-            result.push(combined);
-        }
-        result
+    /// Fills `output` from DLA's output bank(s)
+    pub fn read_output_i16_into(&self, output: &mut [i16]) -> Result<(), DlaError> {
+        self.check_output_width(OutputWidth::SixteenBits)?;
+        let len = output.len() * 2;
+        let mut values = output.iter_mut();
+        self.read_data_bank_rows(self.get_output_bank()?, len, |bytes| {
+            for (pair, value) in bytes.chunks_exact(2).zip(values.by_ref()) {
+                *value = ((pair[0] as i16) << 8) | (pair[1] as i16 & 0xFF);
+            }
+        });
+        Ok(())
     }
 
     /// Reads len amount of bytes from DLA's output bank(s)
-    pub fn read_output_i8(&self, len: usize) -> Vec<i8> {
-        let bytes = self.read_data_bank(self.get_output_bank(), len);
-        bytes.iter().map(|&x| x as i8).collect()
+    ///
+    /// Returns [`DlaError::OutputWidthMismatch`] if the layer wasn't configured to write `i8`.
+    pub fn read_output_i8(&self, len: usize) -> Result<Vec<i8>, DlaError> {
+        let mut result = vec![0; len];
+        self.read_output_i8_into(&mut result)?;
+        Ok(result)
+    }
+
+    /// Fills `output` from DLA's output bank(s)
+    pub fn read_output_i8_into(&self, output: &mut [i8]) -> Result<(), DlaError> {
+        self.check_output_width(OutputWidth::EightBits)?;
+        let len = output.len();
+        let mut values = output.iter_mut();
+        self.read_data_bank_rows(self.get_output_bank()?, len, |bytes| {
+            for (&byte, value) in bytes.iter().zip(values.by_ref()) {
+                *value = byte as i8;
+            }
+        });
+        Ok(())
     }
 
     /// Reads len amount of bytes from DLA's output bank(s)
-    pub fn read_output_i4(&self, len: usize) -> Vec<i8> {
-        let bytes = self.read_data_bank(self.get_output_bank(), len);
+    pub fn read_output_i4(&self, len: usize) -> Result<Vec<i8>, DlaError> {
+        let bytes = self.read_data_bank(self.get_output_bank()?, len);
         let mut result = Vec::with_capacity(bytes.len() * 2);
         for &byte in bytes.iter() {
             // Extract the upper 4 bits and sign-extend to i8
@@ -373,49 +814,76 @@ impl Dla {
             result.push(upper_sign_extended);
             result.push(lower_sign_extended);
         }
-        result
+        Ok(result)
     }
 
     /// Reads len amount of bytes from DLA's input bank(s)
-    pub fn read_input_bank(&self, len: usize) -> Vec<i8> {
-        let bytes = self.read_data_bank(self.get_input_bank(), len);
-        bytes.iter().map(|&x| x as i8).collect()
+    pub fn read_input_bank(&self, len: usize) -> Result<Vec<i8>, DlaError> {
+        let bytes = self.read_data_bank(self.get_input_bank()?, len);
+        Ok(bytes.iter().map(|&x| x as i8).collect())
     }
 
     /// Reads len amount of bytes from DLA's weight bank(s)
-    pub fn read_weight_bank(&self, len: usize) -> Vec<i8> {
-        let bytes = self.read_data_bank(self.get_kernel_bank(), len);
-        bytes.iter().map(|&x| x as i8).collect()
+    pub fn read_weight_bank(&self, len: usize) -> Result<Vec<i8>, DlaError> {
+        let bytes = self.read_data_bank(self.get_kernel_bank()?, len);
+        Ok(bytes.iter().map(|&x| x as i8).collect())
     }
 
     /// Writes buffer to DLA's input bank(s)
-    pub fn write_input(&self, input: &mut [i8]) {
+    pub fn write_input(&self, input: &mut [i8]) -> Result<(), DlaError> {
+        self.write_input_iter(input.iter().copied())
+    }
+
+    /// Writes values to DLA's input bank(s) as they are read, see [`Dla::write_data_bank_iter`]
+    pub fn write_input_iter(&self, input: impl IntoIterator<Item = i8>) -> Result<(), DlaError> {
         // TODO optimize memory bank logic
-        let offset = self.get_input_bank().offset();
-        self.write_data_bank(offset, input);
+        let offset = self.get_input_bank()?.offset();
+        self.write_data_bank_iter(offset, input);
+        Ok(())
     }
 
     /// Writes buffer to DLA's kernel bank(s)
-    pub fn write_kernel(&self, kernel: &mut [i8]) {
+    pub fn write_kernel(&self, kernel: &mut [i8]) -> Result<(), DlaError> {
+        self.write_kernel_iter(kernel.iter().copied())
+    }
+
+    /// Writes values to DLA's kernel bank(s) as they are read, see [`Dla::write_data_bank_iter`]
+    pub fn write_kernel_iter(&self, kernel: impl IntoIterator<Item = i8>) -> Result<(), DlaError> {
         // TODO optimize memory bank logic
-        self.write_data_bank(self.get_kernel_bank().offset(), kernel)
+        self.write_data_bank_iter(self.get_kernel_bank()?.offset(), kernel);
+        Ok(())
     }
 
+    /// Writes bias to the address DLA has been configured to read it from
+    ///
+    /// The address can be anywhere DLA can reach, e.g. memory banks, SDRAM or shared SRAM, see
+    /// [`LayerConfig::bias_addr`] and [`dla_addr`].
     pub fn write_bias(&self, bias: &[i16]) {
-        // TODO Add support for writing to arbitrary memory location instead of dla memory banks
         let mut bytes = Vec::with_capacity(bias.len() * 2);
         for &x in bias {
             bytes.push((x & 0xFF) as u8);
             bytes.push((x >> 8) as u8);
         }
 
-        let addr = self.get_bias_addr() as usize + EXTERNAL_BIT;
+        let addr = cpu_addr(self.get_bias_addr());
         for (i, b) in bytes.iter().enumerate() {
-            let offset = addr + i;
-            unsafe { ptr::write_volatile(offset as *mut _, *b) }
+            self.backend.write_mem_u8(addr + i, *b);
         }
     }
 
+    /// Points DLA to bias that is already in memory it can reach, without copying it
+    ///
+    /// The buffer has to stay alive and unchanged until the layer has been completed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DlaError::UnreachableAddress`] if DLA can't access the buffer, e.g. because it is
+    /// in HPC's local memory.
+    pub fn set_bias_buffer(&self, bias: &[i16]) -> Result<(), DlaError> {
+        self.set_bias_addr(dla_addr(bias.as_ptr() as usize)?);
+        Ok(())
+    }
+
     /// Sets one of the DLA's memory banks as starting bank for inputs
     fn set_input_data_bank(&self, bank: MemoryBank) {
         let mut reg = self.read_u32(DLA_BUF_DATA_BANK);
@@ -549,6 +1017,49 @@ impl Dla {
         self.write_u32(DLA_HANDSHAKE, reg);
     }
 
+    /// Enables the MAC array. Post-processing only layers run with it disabled
+    fn enable_mac(&self, enable: bool) {
+        let mut reg = self.read_u32(DLA_HANDSHAKE);
+        reg = set_bits!(
+            DLA_HANDSHAKE_MAC_ENABLE_OFFSET,
+            DLA_HANDSHAKE_MAC_ENABLE_BITMASK,
+            reg,
+            enable as usize
+        );
+        self.write_u32(DLA_HANDSHAKE, reg);
+    }
+
+    /// Selects whether post-processor reads its input from the data buffer instead of the MAC
+    /// array
+    fn select_pp_input(&self, from_buffer: bool) {
+        let mut reg = self.read_u32(DLA_PP_CTRL);
+        reg = set_bits!(
+            DLA_PP_SELECT_OFFSET,
+            DLA_PP_SELECT_BITMASK,
+            reg,
+            from_buffer as usize
+        );
+        self.write_u32(DLA_PP_CTRL, reg);
+    }
+
+    /// Sets width and height of post-processor input read from the data buffer
+    fn set_pp_input_size(&self, width: u32, height: u32) {
+        let mut reg = 0;
+        reg = set_bits!(
+            DLA_PP_INPUT_WIDTH_OFFSET,
+            DLA_PP_INPUT_WIDTH_BITMASK,
+            reg,
+            width - 1
+        );
+        reg = set_bits!(
+            DLA_PP_INPUT_HEIGHT_OFFSET,
+            DLA_PP_INPUT_HEIGHT_BITMASK,
+            reg,
+            height - 1
+        );
+        self.write_u32(DLA_PP_INPUT, reg);
+    }
+
     /// Enables ReLU in post-processing. Post-processing needs to be enabled
     fn enable_relu(&self, enable: bool) {
         let mut reg = self.read_u32(DLA_HANDSHAKE);
@@ -573,6 +1084,30 @@ impl Dla {
         self.write_u32(DLA_HANDSHAKE, reg);
     }
 
+    /// Enables pooling in post-processing and sets its mode and window size. Post-processing
+    /// needs to be enabled
+    fn set_pooling(&self, pooling: Option<Pooling>) {
+        let mut reg = self.read_u32(DLA_PP_CTRL);
+        if let Some(pooling) = pooling {
+            let mode = match pooling.mode {
+                PoolMode::Max => 0,
+                PoolMode::Average => 1,
+            };
+            reg = set_bits!(DLA_POOL_MODE_OFFSET, DLA_POOL_MODE_BITMASK, reg, mode);
+            reg = set_bits!(DLA_MAX_OFFSET, DLA_MAX_BITMASK, reg, pooling.size - 1);
+        }
+        self.write_u32(DLA_PP_CTRL, reg);
+
+        let mut reg = self.read_u32(DLA_HANDSHAKE);
+        reg = set_bits!(
+            DLA_HANDSHAKE_POOL_ENABLE_OFFSET,
+            DLA_HANDSHAKE_POOL_ENABLE_BITMASK,
+            reg,
+            pooling.is_some() as usize
+        );
+        self.write_u32(DLA_HANDSHAKE, reg);
+    }
+
     /// Sets padding paramters for convolution
     fn set_input_padding(&self, padding: Padding) {
         let mut reg = 0;
@@ -647,7 +1182,7 @@ impl Dla {
     /// Gets simd mode for conv2d
     fn get_simd_mode(&self) -> SimdBitMode {
         let mut reg = self.read_u32(DLA_MAC_CTRL);
-        reg = get_bits!(reg, DLA_SIMD_SELECT_BITMASK);
+        reg = get_bits!(reg, DLA_SIMD_SELECT_BITMASK) >> DLA_SIMD_SELECT_OFFSET;
         match reg {
             0 => SimdBitMode::EightBits,
             1 => SimdBitMode::FourBits,
@@ -657,26 +1192,29 @@ impl Dla {
     }
 
     /// Reads index of the first input bank
-    fn get_input_bank(&self) -> MemoryBank {
+    fn get_input_bank(&self) -> Result<MemoryBank, DlaError> {
         let mut reg = self.read_u32(DLA_BUF_DATA_BANK);
         reg = get_bits!(reg, DLA_BUF_DATA_BANK_B_BITMASK);
         // Shift value back here
         reg >>= 16;
-        MemoryBank::try_from(reg).unwrap()
+        MemoryBank::try_from(reg).map_err(|_| DlaError::RegisterFieldOverflow)
     }
 
     /// Reads index of the first kernel bank
-    fn get_kernel_bank(&self) -> MemoryBank {
+    fn get_kernel_bank(&self) -> Result<MemoryBank, DlaError> {
         let mut reg = self.read_u32(DLA_BUF_DATA_BANK);
         reg = get_bits!(reg, DLA_BUF_DATA_BANK_A_BITMASK);
-        MemoryBank::try_from(reg).unwrap()
+        MemoryBank::try_from(reg).map_err(|_| DlaError::RegisterFieldOverflow)
     }
 
     /// Reads index of the first output bank
-    fn get_output_bank(&self) -> MemoryBank {
+    fn get_output_bank(&self) -> Result<MemoryBank, DlaError> {
         let reg = self.read_u32(DLA_PP_AXI_WRITE);
-        let bank_idx: u32 = (reg - MEMORY_BANK_BASE_ADDR as u32) / MEMORY_BANK_SIZE as u32;
-        MemoryBank::try_from(bank_idx).unwrap()
+        let bank_idx: u32 = reg
+            .checked_sub(MEMORY_BANK_BASE_ADDR as u32)
+            .ok_or(DlaError::RegisterFieldOverflow)?
+            / MEMORY_BANK_SIZE as u32;
+        MemoryBank::try_from(bank_idx).map_err(|_| DlaError::RegisterFieldOverflow)
     }
 
     /// Reads kernel parameters from DLA
@@ -715,10 +1253,10 @@ impl Dla {
     }
 
     /// Sets clipping after conv2d
-    fn set_mac_clip(&self, clip_amount: u32) -> Result<(), InvalidClip> {
+    fn set_mac_clip(&self, clip_amount: u32) -> Result<(), DlaError> {
         // Cap clipping amount
         if clip_amount > 21 {
-            return Err(InvalidClip(clip_amount));
+            return Err(DlaError::InvalidClip(clip_amount));
         }
         let mut reg = self.read_u32(DLA_MAC_CTRL);
         reg = set_bits!(DLA_MAC_CLIP_OFFSET, DLA_MAC_CLIP_BITMASK, reg, clip_amount);
@@ -727,10 +1265,10 @@ impl Dla {
     }
 
     /// Sets clipping after post-processing
-    fn set_pp_clip(&self, clip_amount: u32) -> Result<(), InvalidClip> {
+    fn set_pp_clip(&self, clip_amount: u32) -> Result<(), DlaError> {
         // Cap clipping amount
         if clip_amount > 0x1F {
-            return Err(InvalidClip(clip_amount));
+            return Err(DlaError::InvalidClip(clip_amount));
         }
         let mut reg = self.read_u32(DLA_PP_CTRL);
         reg = set_bits!(DLA_PP_CLIP_OFFSET, DLA_PP_CLIP_BITMASK, reg, clip_amount);
@@ -738,6 +1276,29 @@ impl Dla {
         Ok(())
     }
 
+    /// Sets bounds MAC results are saturated to
+    fn set_mac_saturation(&self, min: i32, max: i32) -> Result<(), DlaError> {
+        if min > max {
+            return Err(DlaError::InvalidSaturation);
+        }
+        self.write_u32(DLA_MAC_SAT_MAX, max as u32);
+        self.write_u32(DLA_MAC_SAT_MIN, min as u32);
+        Ok(())
+    }
+
+    /// Returns width of the output elements DLA has been configured to write
+    pub fn output_width(&self) -> OutputWidth {
+        OutputWidth::from_bits(OUTPUT_WIDTH.load(Ordering::Acquire))
+    }
+
+    /// Checks that output is read with the element width DLA was configured for
+    fn check_output_width(&self, width: OutputWidth) -> Result<(), DlaError> {
+        if self.output_width() != width {
+            return Err(DlaError::OutputWidthMismatch);
+        }
+        Ok(())
+    }
+
     /// Sets rounding after post-processing
     fn set_pp_rounding(&self, enable: bool) {
         let mut reg = self.read_u32(DLA_PP_CTRL);
@@ -844,11 +1405,222 @@ impl Dla {
             handshake_reg,
             1
         );
+        if get_bits!(handshake_reg, DLA_HANDSHAKE_POOL_ENABLE_BITMASK) != 0 {
+            handshake_reg = set_bits!(
+                DLA_HANDSHAKE_POOL_VALID_OFFSET,
+                DLA_HANDSHAKE_POOL_VALID_BITMASK,
+                handshake_reg,
+                1
+            );
+        }
 
         self.write_u32(DLA_HANDSHAKE, handshake_reg);
         true
     }
 
+    /// Powers down all of DLA's power domains
+    ///
+    /// Outputs of the domains are isolated before cutting the power. Memory bank contents are not
+    /// guaranteed to survive power down. The next layer powers DLA up again.
+    ///
+    /// # Errors
+    /// - [`DlaError::Timeout`] if the domains don't acknowledge within `max_polls` polls.
+    pub fn power_down(&self, max_polls: usize) -> Result<(), DlaError> {
+        self.request_power_down();
+        self.wait_power_ack(true, max_polls)
+    }
+
+    /// Requests all of DLA's power domains to power down without waiting for acknowledgements
+    fn request_power_down(&self) {
+        let mut reg = self.read_u32(DLA_POWER_CTRL);
+        reg = set_bits!(
+            DLA_POWER_CTRL_ISO_OFFSET,
+            DLA_POWER_CTRL_ISO_BITMASK,
+            reg,
+            1
+        );
+        self.write_u32(DLA_POWER_CTRL, reg);
+
+        reg = set_bits!(
+            DLA_POWER_CTRL_DOWN_0_OFFSET,
+            DLA_POWER_CTRL_DOWN_0_BITMASK,
+            reg,
+            1
+        );
+        reg = set_bits!(
+            DLA_POWER_CTRL_DOWN_1_OFFSET,
+            DLA_POWER_CTRL_DOWN_1_BITMASK,
+            reg,
+            1
+        );
+        reg = set_bits!(
+            DLA_POWER_CTRL_DOWN_2_OFFSET,
+            DLA_POWER_CTRL_DOWN_2_BITMASK,
+            reg,
+            1
+        );
+        self.write_u32(DLA_POWER_CTRL, reg);
+    }
+
+    /// Powers up all of DLA's power domains
+    ///
+    /// Isolation is released once the domains have acknowledged power up.
+    ///
+    /// # Errors
+    /// - [`DlaError::Timeout`] if the domains don't acknowledge within `max_polls` polls.
+    pub fn power_up(&self, max_polls: usize) -> Result<(), DlaError> {
+        let mut reg = self.read_u32(DLA_POWER_CTRL);
+        reg = set_bits!(
+            DLA_POWER_CTRL_DOWN_0_OFFSET,
+            DLA_POWER_CTRL_DOWN_0_BITMASK,
+            reg,
+            0
+        );
+        reg = set_bits!(
+            DLA_POWER_CTRL_DOWN_1_OFFSET,
+            DLA_POWER_CTRL_DOWN_1_BITMASK,
+            reg,
+            0
+        );
+        reg = set_bits!(
+            DLA_POWER_CTRL_DOWN_2_OFFSET,
+            DLA_POWER_CTRL_DOWN_2_BITMASK,
+            reg,
+            0
+        );
+        self.write_u32(DLA_POWER_CTRL, reg);
+        self.wait_power_ack(false, max_polls)?;
+
+        reg = set_bits!(
+            DLA_POWER_CTRL_ISO_OFFSET,
+            DLA_POWER_CTRL_ISO_BITMASK,
+            reg,
+            0
+        );
+        self.write_u32(DLA_POWER_CTRL, reg);
+        Ok(())
+    }
+
+    /// Returns true if any of DLA's power domains has been requested to power down
+    pub fn is_powered_down(&self) -> bool {
+        let reg = self.read_u32(DLA_POWER_CTRL);
+        get_bits!(
+            reg,
+            DLA_POWER_CTRL_DOWN_0_BITMASK
+                | DLA_POWER_CTRL_DOWN_1_BITMASK
+                | DLA_POWER_CTRL_DOWN_2_BITMASK
+        ) != 0
+    }
+
+    /// Polls power acknowledgements until all domains are in the requested state
+    fn wait_power_ack(&self, down: bool, max_polls: usize) -> Result<(), DlaError> {
+        // NOTE: VP does not model power domains, so acknowledgements never change there
+        if cfg!(feature = "vp") {
+            return Ok(());
+        }
+
+        let acks = (DLA_POWER_STAT_ACK_0_BITMASK
+            | DLA_POWER_STAT_ACK_1_BITMASK
+            | DLA_POWER_STAT_ACK_2_BITMASK) as u32;
+        let expected = if down { acks } else { 0 };
+        for _ in 0..max_polls {
+            if get_bits!(self.read_u32(DLA_POWER_STAT), acks) == expected {
+                return Ok(());
+            }
+        }
+        Err(DlaError::Timeout)
+    }
+
+    /// Enables or disables automatic power down
+    ///
+    /// When enabled, [`sequential::Sequential::run`] powers DLA down once the model has run,
+    /// whether it succeeded or not. DLA is powered up again when the next layer is initialized.
+    /// Layer functions in [`layers`] keep DLA powered, so that a layer split into several DLA runs
+    /// keeps its data in the memory banks. Call [`Dla::power_down`] to end other batches of
+    /// layers.
+    pub fn set_auto_power_down(&self, enable: bool) {
+        AUTO_POWER_DOWN.store(enable, Ordering::Release);
+    }
+
+    /// Returns true if automatic power down is enabled
+    pub fn auto_power_down(&self) -> bool {
+        AUTO_POWER_DOWN.load(Ordering::Acquire)
+    }
+
+    /// Saturates MAC results of the layers run by [`layers`] and [`sequential::Sequential`] to
+    /// `min..=max` before MAC clip. By default MAC results are not saturated.
+    ///
+    /// # Errors
+    /// - [`DlaError::InvalidSaturation`] if `min` is above `max`.
+    pub fn set_layer_mac_saturation(&self, min: i32, max: i32) -> Result<(), DlaError> {
+        if min > max {
+            return Err(DlaError::InvalidSaturation);
+        }
+        LAYER_MAC_SAT_MIN.store(min, Ordering::Release);
+        LAYER_MAC_SAT_MAX.store(max, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns MAC saturation bounds of the layer functions as `(min, max)`
+    pub fn layer_mac_saturation(&self) -> (i32, i32) {
+        (
+            LAYER_MAC_SAT_MIN.load(Ordering::Acquire),
+            LAYER_MAC_SAT_MAX.load(Ordering::Acquire),
+        )
+    }
+
+    /// When enabled, the layers run by [`layers`] and [`sequential::Sequential`] round their
+    /// post-processing results to nearest instead of truncating them when clipping
+    pub fn set_layer_pp_rounding(&self, enable: bool) {
+        LAYER_PP_ROUNDING.store(enable, Ordering::Release);
+    }
+
+    /// Returns true if the layer functions round post-processing results
+    pub fn layer_pp_rounding(&self) -> bool {
+        LAYER_PP_ROUNDING.load(Ordering::Acquire)
+    }
+
+    /// Ends a batch of layers, powering DLA down if automatic power down is enabled
+    ///
+    /// Power down is only requested, so that it can't fail the batch. Powering DLA up for the
+    /// next layer waits for the domains to acknowledge.
+    pub(crate) fn end_batch(&self) {
+        if self.auto_power_down() {
+            self.request_power_down();
+        }
+    }
+
+    /// Polls DLA until handshake succeeds or `max_polls` polls have been made
+    pub fn wait_handshake(&self, max_polls: usize) -> Result<(), DlaError> {
+        for _ in 0..max_polls {
+            if self.handle_handshake() {
+                return Ok(());
+            }
+        }
+        Err(DlaError::Timeout)
+    }
+
+    /// Checks without blocking whether the running layer has been completed
+    pub fn poll_layer(&self) -> bool {
+        #[cfg(all(target_arch = "riscv64", feature = "irq"))]
+        if self.backend.has_irq() {
+            return self.poll_handshake_irq();
+        }
+        self.handle_handshake()
+    }
+
+    /// Waits until the running layer has been completed
+    ///
+    /// Checks the handshake for at most `max_polls` times. With feature `irq` and a backend that
+    /// receives interrupts, the hart sleeps between the checks until DLA interrupt.
+    pub fn wait_layer(&self, max_polls: usize) -> Result<(), DlaError> {
+        #[cfg(all(target_arch = "riscv64", feature = "irq"))]
+        if self.backend.has_irq() {
+            return self.wait_handshake_irq(max_polls);
+        }
+        self.wait_handshake(max_polls)
+    }
+
     /// Prepares DLA for receiveing configuration for next layer
     fn handshake_next_layer(&self) {
         let mut reg = self.read_u32(DLA_HANDSHAKE);
@@ -875,6 +1647,9 @@ impl Dla {
 
     /// Configures the next layer in dla
     ///
+    /// Prints an error and leaves the DLA untouched if the configuration is invalid. Use
+    /// [`Dla::try_init_layer`] to handle the error instead.
+    ///
     /// # Examples
     ///
     /// ```
@@ -882,45 +1657,389 @@ impl Dla {
     /// let layer = LayerConfig {...};
     /// dla.init_layer(layer)
     /// ```
+    #[deprecated = "Errors are only printed. Use Dla::try_init_layer instead."]
     pub fn init_layer(&self, config: LayerConfig) {
+        if self.try_init_layer(config).is_err() {
+            sprintln!("Invalid layer configuration, layer not initialized")
+        }
+    }
+
+    /// Configures the next layer in dla
+    ///
+    /// Validates the whole configuration before writing any registers, so on error the DLA is
+    /// left as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let dla = Dla::new();
+    /// let layer = LayerConfig {...};
+    /// dla.try_init_layer(layer)?;
+    /// ```
+    pub fn try_init_layer(&self, config: LayerConfig) -> Result<(), DlaError> {
+        let kernel_size = config.kernel_size.unwrap_or(DEFAULT_KERNEL_SIZE);
+        let input_size = config.input_size.unwrap_or(DEFAULT_INPUT_SIZE);
+        let padding = config.padding.unwrap_or(DEFAULT_PADDING);
+        let stride = config.stride.unwrap_or(DEFAULT_STRIDE);
+        let mac_clip = config.mac_clip.unwrap_or(DEFAULT_MAC_CLIP);
+        let pp_clip = config.pp_clip.unwrap_or(DEFAULT_PP_CLIP);
+        let mac_saturation = match (config.mac_sat_min, config.mac_sat_max) {
+            (None, None) => None,
+            (min, max) => Some((
+                min.unwrap_or(DEFAULT_MAC_SAT_MIN),
+                max.unwrap_or(DEFAULT_MAC_SAT_MAX),
+            )),
+        };
+        let output_width = config.output_width.unwrap_or(DEFAULT_OUTPUT_WIDTH);
+
+        kernel_size.validate()?;
+        input_size.validate()?;
+        padding.validate()?;
+        stride.validate()?;
+        if let Some(pooling) = &config.pooling {
+            pooling.validate()?;
+        }
+        if mac_clip > 21 {
+            return Err(DlaError::InvalidClip(mac_clip));
+        }
+        if pp_clip > 0x1F {
+            return Err(DlaError::InvalidClip(pp_clip));
+        }
+        if mac_saturation.is_some_and(|(min, max)| min > max) {
+            return Err(DlaError::InvalidSaturation);
+        }
+        match output_width {
+            // Raw MAC results are written only without MAC clip, and only by some backends
+            OutputWidth::ThirtyTwoBits if mac_clip != 0 => {
+                return Err(DlaError::InvalidClip(mac_clip))
+            }
+            OutputWidth::ThirtyTwoBits if !self.backend.has_out32() => {
+                return Err(DlaError::Unsupported)
+            }
+            OutputWidth::SixteenBits => return Err(DlaError::Unsupported),
+            _ => {}
+        }
+
+        // Wake DLA up if it has been powered down
+        if self.is_powered_down() {
+            self.power_up(DEFAULT_POWER_TIMEOUT)?;
+        }
+
         // Handshake for next layer
         self.handshake_next_layer();
 
         // Set memory banks
-        self.set_input_data_bank(config.input_bank.unwrap_or(DEFAULT_INPUT_BANK));
-        self.set_kernel_data_bank(config.kernel_bank.unwrap_or(DEFAULT_KERNEL_BANK));
-        self.set_output_bank(config.output_bank.unwrap_or(DEFAULT_OUTPUT_BANK));
+        if let Some(bank) = config.input_bank.bank() {
+            self.set_input_data_bank(bank);
+        }
+        if let Some(bank) = config.kernel_bank.bank() {
+            self.set_kernel_data_bank(bank);
+        }
+        if let Some(bank) = config.output_bank.bank() {
+            self.set_output_bank(bank);
+        }
 
         // Set bias address
         self.set_bias_addr(config.bias_addr.unwrap_or(DEFAULT_BIAS_ADDR));
 
         // Enable post processor
+        self.select_pp_input(false);
         self.enable_pp(config.pp_enabled);
         self.enable_relu(config.relu_enabled);
         self.enable_bias(config.bias_enabled);
+        self.set_pooling(config.pooling);
 
         // Set input and kernel dimensions
-        self.set_kernel_size(config.kernel_size.unwrap_or(DEFAULT_KERNEL_SIZE));
+        self.set_kernel_size(kernel_size);
 
-        self.set_input_size(config.input_size.unwrap_or(DEFAULT_INPUT_SIZE));
+        self.set_input_size(input_size);
 
         // Set simd
         self.set_simd_mode(config.simd_mode.unwrap_or(DEFAULT_SIMD_MODE));
 
         // Set padding
-        self.set_input_padding(config.padding.unwrap_or(DEFAULT_PADDING));
+        self.set_input_padding(padding);
 
         // Set stride
-        self.set_stride(config.stride.unwrap_or(DEFAULT_STRIDE));
+        self.set_stride(stride);
+
+        // Set clipping, saturation and rounding
+        self.set_mac_clip(mac_clip)?;
+        self.set_pp_clip(pp_clip)?;
+        if let Some((min, max)) = mac_saturation {
+            self.set_mac_saturation(min, max)?;
+        }
+        self.set_pp_rounding(config.pp_rounding);
+
+        OUTPUT_WIDTH.store(output_width as u8, Ordering::Release);
+        Ok(())
+    }
+
+    /// Configures the next layer in dla to only run post-processing
+    ///
+    /// Prints an error and leaves the DLA untouched if the configuration is invalid. Use
+    /// [`Dla::try_init_pp_layer`] to handle the error instead.
+    #[deprecated = "Errors are only printed. Use Dla::try_init_pp_layer instead."]
+    pub fn init_pp_layer(&self, config: PpConfig) {
+        if self.try_init_pp_layer(config).is_err() {
+            sprintln!("Invalid post-processing configuration, layer not initialized")
+        }
+    }
+
+    /// Configures the next layer in dla to only run post-processing
+    ///
+    /// The MAC array is disabled and post-processor reads the input bank directly, so only input
+    /// data needs to be marked ready. Validates the whole configuration before writing any
+    /// registers, so on error the DLA is left as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let dla = Dla::new();
+    /// let layer = PpConfig {...};
+    /// dla.try_init_pp_layer(layer)?;
+    /// dla.write_input(&mut input)?;
+    /// dla.input_data_ready(true);
+    /// ```
+    pub fn try_init_pp_layer(&self, config: PpConfig) -> Result<(), DlaError> {
+        let input_size = config.input_size.unwrap_or(DEFAULT_INPUT_SIZE);
+        let pp_clip = config.pp_clip.unwrap_or(DEFAULT_PP_CLIP);
+
+        input_size.validate()?;
+        check_dim_field(
+            input_size.width,
+            DLA_PP_INPUT_WIDTH_OFFSET,
+            DLA_PP_INPUT_WIDTH_BITMASK,
+        )?;
+        check_dim_field(
+            input_size.height,
+            DLA_PP_INPUT_HEIGHT_OFFSET,
+            DLA_PP_INPUT_HEIGHT_BITMASK,
+        )?;
+        if pp_clip > 0x1F {
+            return Err(DlaError::InvalidClip(pp_clip));
+        }
 
-        // Set clipping
-        match self.set_mac_clip(config.mac_clip.unwrap_or(DEFAULT_MAC_CLIP)) {
-            Ok(_) => (),
-            Err(_) => sprintln!("Mac clip value, exceeds allowed maximum of 21"),
+        // Wake DLA up if it has been powered down
+        if self.is_powered_down() {
+            self.power_up(DEFAULT_POWER_TIMEOUT)?;
         }
-        match self.set_pp_clip(config.pp_clip.unwrap_or(DEFAULT_PP_CLIP)) {
-            Ok(_) => (),
-            Err(_) => sprintln!("PP clip value, exceeds allowed maximum of 21"),
+
+        // Handshake for next layer, without the MAC array
+        self.handshake_next_layer();
+        self.enable_mac(false);
+
+        if let Some(bank) = config.input_bank.bank() {
+            self.set_input_data_bank(bank);
+        }
+        if let Some(bank) = config.output_bank.bank() {
+            self.set_output_bank(bank);
+        }
+        self.set_bias_addr(config.bias_addr.unwrap_or(DEFAULT_BIAS_ADDR));
+
+        // Post-processor reads the input bank
+        self.select_pp_input(true);
+        self.enable_pp(true);
+        self.enable_relu(config.relu_enabled);
+        self.enable_bias(config.bias_enabled);
+        self.set_pooling(None);
+
+        // Channels are taken from the buffer input size
+        self.set_pp_input_size(input_size.width, input_size.height);
+        self.set_input_size(input_size);
+
+        self.set_pp_clip(pp_clip)?;
+        self.set_pp_rounding(config.pp_rounding);
+
+        OUTPUT_WIDTH.store(OutputWidth::EightBits as u8, Ordering::Release);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::simulated;
+
+    fn layer_config(input_bank: BankSelection) -> LayerConfig {
+        LayerConfig {
+            input_bank,
+            kernel_bank: BankSelection::Bank(MemoryBank::Bank4),
+            output_bank: BankSelection::Keep,
+            bias_addr: None,
+            pp_enabled: false,
+            relu_enabled: false,
+            bias_enabled: false,
+            input_size: None,
+            kernel_size: None,
+            padding: None,
+            stride: None,
+            mac_clip: None,
+            pp_clip: None,
+            mac_sat_max: None,
+            mac_sat_min: None,
+            pp_rounding: false,
+            simd_mode: None,
+            pooling: None,
+            output_width: None,
         }
     }
+
+    #[test]
+    fn data_bank_writes_reverse_64_bit_chunks() {
+        let (dla, _lock) = simulated();
+        let data: Vec<i8> = (1..=19).collect();
+        // Chunks are written as words at aligned offsets and byte by byte otherwise
+        for offset in [MemoryBank::Bank2.offset(), MemoryBank::Bank2.offset() + 1] {
+            dla.write_data_bank_iter(offset, data.iter().copied());
+            let expected: Vec<u8> = data
+                .chunks(8)
+                .flat_map(|chunk| chunk.iter().rev().map(|&x| x as u8))
+                .collect();
+            assert_eq!(
+                dla.backend().banks()[offset..offset + data.len()],
+                expected[..]
+            );
+        }
+    }
+
+    #[test]
+    fn kept_banks_are_unchanged() {
+        let (dla, _lock) = simulated();
+        dla.set_output_bank(MemoryBank::Bank7);
+
+        dla.try_init_layer(layer_config(BankSelection::Bank(MemoryBank::Bank3)))
+            .unwrap();
+        assert_eq!(
+            dla.get_input_bank().unwrap().offset(),
+            MemoryBank::Bank3.offset()
+        );
+        assert_eq!(
+            dla.get_kernel_bank().unwrap().offset(),
+            MemoryBank::Bank4.offset()
+        );
+        assert_eq!(
+            dla.get_output_bank().unwrap().offset(),
+            MemoryBank::Bank7.offset()
+        );
+
+        dla.try_init_layer(layer_config(BankSelection::Keep))
+            .unwrap();
+        assert_eq!(
+            dla.get_input_bank().unwrap().offset(),
+            MemoryBank::Bank3.offset()
+        );
+    }
+
+    #[test]
+    fn zero_dimensions_are_invalid() {
+        let kernel_size = KernelSize {
+            s_channels: 1,
+            kernels: 0,
+            height: 1,
+            width: 1,
+        };
+        assert_eq!(kernel_size.validate(), Err(DlaError::InvalidDimension));
+        assert_eq!(
+            Stride { x: 1, y: 0 }.validate(),
+            Err(DlaError::InvalidDimension)
+        );
+        let pooling = Pooling {
+            mode: PoolMode::Max,
+            size: 0,
+        };
+        assert_eq!(pooling.validate(), Err(DlaError::InvalidDimension));
+    }
+
+    #[test]
+    fn oversized_dimensions_overflow_register_field() {
+        let input_size = InputSize {
+            channels: 1,
+            height: 1,
+            width: (DLA_BUF_INPUT_WIDTH_BITMASK >> DLA_BUF_INPUT_WIDTH_OFFSET) as u32 + 2,
+        };
+        assert_eq!(input_size.validate(), Err(DlaError::RegisterFieldOverflow));
+        let input_size = InputSize {
+            width: (DLA_BUF_INPUT_WIDTH_BITMASK >> DLA_BUF_INPUT_WIDTH_OFFSET) as u32 + 1,
+            ..input_size
+        };
+        assert_eq!(input_size.validate(), Ok(()));
+    }
+
+    #[test]
+    fn output_is_read_with_configured_width() {
+        let (dla, _lock) = simulated();
+        let config = |mac_clip, output_width| LayerConfig {
+            output_bank: BankSelection::Bank(MemoryBank::Bank8),
+            mac_clip,
+            output_width,
+            ..layer_config(BankSelection::Keep)
+        };
+
+        dla.try_init_layer(config(None, None)).unwrap();
+        assert_eq!(dla.output_width(), OutputWidth::EightBits);
+        assert!(dla.read_output_i8(4).is_ok());
+        assert_eq!(dla.read_output_i16(4), Err(DlaError::OutputWidthMismatch));
+        assert_eq!(dla.read_output_i32(4), Err(DlaError::OutputWidthMismatch));
+
+        // 32-bit outputs need a backend that writes them and no MAC clip
+        let out32 = || config(Some(0), Some(OutputWidth::ThirtyTwoBits));
+        assert_eq!(dla.try_init_layer(out32()), Err(DlaError::Unsupported));
+        dla.backend().set_out32(true);
+        assert_eq!(
+            dla.try_init_layer(config(Some(2), Some(OutputWidth::ThirtyTwoBits))),
+            Err(DlaError::InvalidClip(2))
+        );
+        dla.try_init_layer(out32()).unwrap();
+        assert!(dla.read_output_i32(4).is_ok());
+        assert_eq!(dla.read_output_i8(4), Err(DlaError::OutputWidthMismatch));
+
+        assert_eq!(
+            dla.try_init_layer(config(None, Some(OutputWidth::SixteenBits))),
+            Err(DlaError::Unsupported)
+        );
+        assert_eq!(dla.output_width(), OutputWidth::ThirtyTwoBits);
+    }
+
+    #[test]
+    fn mac_saturation_is_only_written_when_configured() {
+        let (dla, _lock) = simulated();
+        dla.write_u32(DLA_MAC_SAT_MAX, 0x1234);
+        dla.write_u32(DLA_MAC_SAT_MIN, 0x5678);
+
+        dla.try_init_layer(layer_config(BankSelection::Keep))
+            .unwrap();
+        assert_eq!(dla.read_u32(DLA_MAC_SAT_MAX), 0x1234);
+        assert_eq!(dla.read_u32(DLA_MAC_SAT_MIN), 0x5678);
+
+        let config = LayerConfig {
+            mac_sat_max: Some(100),
+            ..layer_config(BankSelection::Keep)
+        };
+        dla.try_init_layer(config).unwrap();
+        assert_eq!(dla.read_u32(DLA_MAC_SAT_MAX), 100);
+        assert_eq!(dla.read_u32(DLA_MAC_SAT_MIN), i32::MIN as u32);
+
+        let config = LayerConfig {
+            mac_sat_max: Some(100),
+            mac_sat_min: Some(101),
+            ..layer_config(BankSelection::Keep)
+        };
+        assert_eq!(dla.try_init_layer(config), Err(DlaError::InvalidSaturation));
+    }
+
+    #[test]
+    fn pooling_window_is_capped() {
+        assert_eq!(MAX_POOL_SIZE, 4);
+        let pooling = Pooling {
+            mode: PoolMode::Average,
+            size: MAX_POOL_SIZE,
+        };
+        assert_eq!(pooling.validate(), Ok(()));
+        let pooling = Pooling {
+            size: MAX_POOL_SIZE + 1,
+            ..pooling
+        };
+        assert_eq!(pooling.validate(), Err(DlaError::RegisterFieldOverflow));
+    }
 }
@@ -14,8 +14,10 @@ pub const MEMORY_BANK_BASE_ADDR: usize = 0x170000000;
 #[cfg(not(feature = "hpc"))]
 pub const MEMORY_BANK_BASE_ADDR: usize = 0x70000000;
 
+/// Offset of memory outside HPC in HPC's address space
 #[cfg(feature = "hpc")]
 pub const EXTERNAL_BIT: usize = 0x100000000;
+/// Offset of memory outside HPC in HPC's address space
 #[cfg(not(feature = "hpc"))]
 pub const EXTERNAL_BIT: usize = 0x0;
 
@@ -66,23 +68,23 @@ pub(crate) const DLA_READ_B_VALID_BITMASK: usize = 0b1 << 8;
 pub(crate) const DLA_MAC_CTRL: usize = 0xC;
 pub(crate) const DLA_SIMD_SELECT_OFFSET: usize = 0x1;
 pub(crate) const DLA_MAC_CLIP_OFFSET: usize = 0x8;
-pub(crate) const DLA_SIMD_SELECT_BITMASK: usize = 0x11;
+pub(crate) const DLA_SIMD_SELECT_BITMASK: usize = 0b11 << 1;
 pub(crate) const DLA_MAC_CLIP_BITMASK: usize = 0b11111 << 8;
 
 pub(crate) const DLA_PP_CTRL: usize = 0x10;
 pub(crate) const DLA_ACTIVE_MODE_OFFSET: usize = 0x0;
 pub(crate) const DLA_RELU_OFFSET_UNUSED: usize = 0x2;
-pub(crate) const DLA_MAX_OFFSET_UNUSED: usize = 0x4;
+pub(crate) const DLA_MAX_OFFSET: usize = 0x4;
 pub(crate) const DLA_PP_SELECT_OFFSET: usize = 0x6;
-pub(crate) const DLA_POOL_MODE_OFFSET_UNUSED: usize = 0x7;
+pub(crate) const DLA_POOL_MODE_OFFSET: usize = 0x7;
 pub(crate) const DLA_ROUNDING_OFFSET: usize = 0x9;
 pub(crate) const DLA_CTRL_VLD_OFFSET_UNUSED: usize = 0xA;
 pub(crate) const DLA_PP_CLIP_OFFSET: usize = 0x10;
 pub(crate) const DLA_ACTIVE_MODE_BITMASK: usize = 0b11;
 pub(crate) const DLA_RELU_BITMASK_UNUSED: usize = 0b1100;
-pub(crate) const DLA_MAX_BITMASK_UNUSED: usize = 0b110000;
+pub(crate) const DLA_MAX_BITMASK: usize = 0b110000;
 pub(crate) const DLA_PP_SELECT_BITMASK: usize = 0b1 << 6;
-pub(crate) const DLA_POOL_MODE_BITMASK_UNUSED: usize = 0b11 << 7;
+pub(crate) const DLA_POOL_MODE_BITMASK: usize = 0b11 << 7;
 pub(crate) const DLA_ROUNDING_BITMASK: usize = 0b1 << 9;
 pub(crate) const DLA_CTRL_VLD_BITMASK_UNUSED: usize = 0b1 << 10;
 pub(crate) const DLA_PP_CLIP_BITMASK: usize = 0b11111 << 16;
@@ -167,6 +169,9 @@ pub(crate) const DLA_POWER_STAT_ACK_0_BITMASK: usize = 0b1;
 pub(crate) const DLA_POWER_STAT_ACK_1_BITMASK: usize = 0b10;
 pub(crate) const DLA_POWER_STAT_ACK_2_BITMASK: usize = 0b100;
 
+// NOTE: DMA_CTRL only holds read and write event bits. The register map has no source, destination
+// or length for a transfer, so the driver copies bank data with the CPU and leaves the DMA registers
+// and the DMA interrupt in STATUS unused.
 pub(crate) const DLA_DMA_CTRL: usize = 0x44;
 pub(crate) const DLA_DMA_CTRL_READ_EVENT_OFFSET: usize = 0x0;
 pub(crate) const DLA_DMA_CTRL_WRITE_EVENT_OFFSET: usize = 0x0;
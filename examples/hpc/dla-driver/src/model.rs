@@ -0,0 +1,806 @@
+//! Binary model container with a zero-copy loader and executor
+//!
+//! A model image holds the layer list of a quantized network together with its weights, so the
+//! network doesn't have to be written out as `layers::*` calls. [`Model::parse`] validates an
+//! image in place, e.g. one embedded with `include_bytes!` or loaded to SDRAM, and [`Model::run`]
+//! runs it on DLA. Weights and biases are only copied when their layer is run.
+//!
+//! # Format
+//!
+//! All values are little-endian. The image starts with a header followed by the layer records.
+//!
+//! | Offset | Size | Field                                    |
+//! |--------|------|------------------------------------------|
+//! | 0      | 4    | Magic, `b"DLAM"`                         |
+//! | 4      | 2    | Format version, [`VERSION`]              |
+//! | 6      | 2    | Number of layers                         |
+//! | 8      | 12   | Input channels, height and width as u32  |
+//! | 20     | 1    | Input order, index into [`ORDER3`]       |
+//! | 21     | 3    | Reserved                                 |
+//!
+//! Each layer record starts with a fixed size header:
+//!
+//! | Offset | Size | Field                                                                  |
+//! |--------|------|------------------------------------------------------------------------|
+//! | 0      | 1    | Kind, see [`LayerKind`]                                                |
+//! | 1      | 1    | Flags, bit 0 enables ReLU                                              |
+//! | 2      | 1    | Weight order, index into [`ORDER4`]                                    |
+//! | 3      | 1    | Bias width in bytes, 0 (no bias), 2 or 4                               |
+//! | 4      | 2    | Input, index of an earlier layer or [`MODEL_INPUT`]                    |
+//! | 6      | 2    | Second input of [`LayerKind::Add`], same encoding                      |
+//! | 8      | 16   | Weight kernels, channels, height and width as u32                      |
+//! | 24     | 4    | Padding top, right, left and bottom as u8                              |
+//! | 28     | 1    | Padding value as i8                                                    |
+//! | 29     | 2    | Stride x and y as u8                                                   |
+//! | 31     | 2    | MAC clip and PP clip as u8                                             |
+//! | 33     | 1    | Requantization rounding, index into [`ROUNDING`]                       |
+//! | 34     | 2    | Number of requantization multipliers                                   |
+//! | 36     | 12   | Input, second input and output zero points as i32                      |
+//! | 48     | 4    | Length of weights in bytes                                             |
+//! | 52     | 4    | Number of bias values                                                  |
+//!
+//! It is followed by the weights as i8, the bias values and the multipliers as pairs of i32
+//! multiplier and shift, see [`QuantizedMultiplier`]. Each of them is padded to a multiple of 4
+//! bytes.
+//!
+//! Layer kinds interpret the fields as follows:
+//! - [`LayerKind::Conv2d`]: weights of shape kernels x channels x height x width.
+//! - [`LayerKind::DepthwiseConv2d`]: weights have a single channel, see
+//!   [`crate::layers::try_depthwise_conv2d`].
+//! - [`LayerKind::FullyConnected`]: kernels is the number of outputs, channels the number of
+//!   inputs and height and width are 1. Input is flattened in HWC order and the output has a
+//!   single pixel.
+//! - [`LayerKind::MaxPool2d`], [`LayerKind::AvgPool2d`]: height and width are the size of the
+//!   window, which is also the stride. There are no weights.
+//! - [`LayerKind::Add`]: three multipliers, one for each input and the output, see
+//!   [`AddRequantization`]. There are no weights.
+//!
+//! Layers calculated on DLA are requantized when they have multipliers. DLA output is shifted back
+//! by the MAC and PP clips and requantized with the output zero point. Zero points of the input
+//! are expected to be folded into bias.
+//!
+//! NOTE: Requantization is approximate, as DLA's 8-bit output has lost the low bits of the
+//! accumulators. An output may be less than `2^(mac_clip + pp_clip) * scale + 1` below the exact
+//! one, so converters should choose clips that keep this small.
+use crate::backend::RegisterAccess;
+use crate::layers::{
+    try_add, try_avg_pool2d, try_conv2d_view, try_depthwise_conv2d, try_linear, try_max_pool2d,
+};
+use crate::quant::{requantize, AddRequantization, QuantizedMultiplier, Requantization, Rounding};
+use crate::tensor3::{Order3, Tensor3};
+use crate::tensor4::{Order4, Tensor4View};
+use crate::utils::rescale_bias;
+use crate::{Dla, DlaError, Padding, Stride};
+use alloc::vec::Vec;
+
+/// Identifies a model image
+pub const MAGIC: [u8; 4] = *b"DLAM";
+/// Version of the format this loader reads
+pub const VERSION: u16 = 1;
+/// Layer input referring to the input of the model
+pub const MODEL_INPUT: u16 = 0xFFFF;
+
+const HEADER_SIZE: usize = 24;
+const LAYER_HEADER_SIZE: usize = 56;
+
+/// Input orders by their index in the image
+pub const ORDER3: [Order3; 6] = [
+    Order3::CHW,
+    Order3::CWH,
+    Order3::HWC,
+    Order3::HCW,
+    Order3::WHC,
+    Order3::WCH,
+];
+
+/// Weight orders by their index in the image
+#[rustfmt::skip]
+pub const ORDER4: [Order4; 24] = [
+    Order4::KCHW, Order4::KCWH, Order4::KHWC, Order4::KHCW, Order4::KWHC, Order4::KWCH,
+    Order4::CKHW, Order4::CKWH, Order4::CHWK, Order4::CHKW, Order4::CWKH, Order4::CWHK,
+    Order4::HKCW, Order4::HKWC, Order4::HCKW, Order4::HCWK, Order4::HWCK, Order4::HWKC,
+    Order4::WKCH, Order4::WKHC, Order4::WCKH, Order4::WCHK, Order4::WHCK, Order4::WHKC,
+];
+
+/// Requantization roundings by their index in the image
+pub const ROUNDING: [Rounding; 3] = [Rounding::Upward, Rounding::ToNearest, Rounding::TfLite];
+
+/// Operation of a layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayerKind {
+    Conv2d = 0,
+    DepthwiseConv2d = 1,
+    FullyConnected = 2,
+    MaxPool2d = 3,
+    AvgPool2d = 4,
+    Add = 5,
+}
+
+impl TryFrom<u8> for LayerKind {
+    type Error = DlaError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(LayerKind::Conv2d),
+            1 => Ok(LayerKind::DepthwiseConv2d),
+            2 => Ok(LayerKind::FullyConnected),
+            3 => Ok(LayerKind::MaxPool2d),
+            4 => Ok(LayerKind::AvgPool2d),
+            5 => Ok(LayerKind::Add),
+            _ => Err(DlaError::InvalidModel),
+        }
+    }
+}
+
+/// Bias values of a layer, still in the model image
+#[derive(Clone, Copy, Debug)]
+pub enum Bias<'a> {
+    None,
+    I16(&'a [u8]),
+    I32(&'a [u8]),
+}
+
+impl Bias<'_> {
+    /// Returns bias values widened to 32 bits, or `None` if the layer has no bias
+    pub fn to_i32(&self) -> Option<Vec<i32>> {
+        match self {
+            Bias::None => None,
+            Bias::I16(data) => Some(
+                data.chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]) as i32)
+                    .collect(),
+            ),
+            Bias::I32(data) => Some(
+                data.chunks_exact(4)
+                    .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Layer record of a model image
+#[derive(Clone)]
+pub struct LayerDesc<'a> {
+    pub kind: LayerKind,
+    pub relu: bool,
+    pub weight_order: Order4,
+    pub input: u16,
+    pub second_input: u16,
+    /// Weight shape as kernels, channels, height and width
+    pub shape: (usize, usize, usize, usize),
+    pub padding: Padding,
+    pub stride: Stride,
+    pub mac_clip: u32,
+    pub pp_clip: u32,
+    pub rounding: Rounding,
+    /// Zero points of the input, the second input and the output
+    pub zero_points: [i32; 3],
+    pub weights: &'a [i8],
+    pub bias: Bias<'a>,
+    multipliers: &'a [u8],
+}
+
+impl<'a> LayerDesc<'a> {
+    /// Parses a layer record and returns it along with its size in bytes
+    fn parse(data: &'a [u8]) -> Result<(Self, usize), DlaError> {
+        if data.len() < LAYER_HEADER_SIZE {
+            return Err(DlaError::InvalidModel);
+        }
+        let kind = LayerKind::try_from(data[0])?;
+        let weight_order = *ORDER4.get(data[2] as usize).ok_or(DlaError::InvalidModel)?;
+        let rounding = *ROUNDING
+            .get(data[33] as usize)
+            .ok_or(DlaError::InvalidModel)?;
+        let shape = (
+            read_u32(data, 8) as usize,
+            read_u32(data, 12) as usize,
+            read_u32(data, 16) as usize,
+            read_u32(data, 20) as usize,
+        );
+
+        let weights_len = read_u32(data, 48) as usize;
+        let bias_len = read_u32(data, 52) as usize;
+        let bias_width = data[3] as usize;
+        let multipliers_len = read_u16(data, 34) as usize * 8;
+
+        let bias_bytes = bias_len
+            .checked_mul(bias_width)
+            .ok_or(DlaError::InvalidModel)?;
+        let weights_start = LAYER_HEADER_SIZE;
+        let bias_start = align4(weights_len)
+            .and_then(|len| weights_start.checked_add(len))
+            .ok_or(DlaError::InvalidModel)?;
+        let multipliers_start = align4(bias_bytes)
+            .and_then(|len| bias_start.checked_add(len))
+            .ok_or(DlaError::InvalidModel)?;
+        let size = multipliers_start
+            .checked_add(multipliers_len)
+            .ok_or(DlaError::InvalidModel)?;
+        if data.len() < size {
+            return Err(DlaError::InvalidModel);
+        }
+
+        let bias_data = &data[bias_start..bias_start + bias_bytes];
+        let bias = match bias_width {
+            0 => Bias::None,
+            2 => Bias::I16(bias_data),
+            4 => Bias::I32(bias_data),
+            _ => return Err(DlaError::InvalidModel),
+        };
+
+        let weights = &data[weights_start..weights_start + weights_len];
+        // SAFETY: i8 and u8 have the same size and alignment
+        let weights =
+            unsafe { core::slice::from_raw_parts(weights.as_ptr() as *const i8, weights.len()) };
+
+        let desc = LayerDesc {
+            kind,
+            relu: data[1] & 1 != 0,
+            weight_order,
+            input: read_u16(data, 4),
+            second_input: read_u16(data, 6),
+            shape,
+            padding: Padding {
+                top: data[24] as u32,
+                right: data[25] as u32,
+                left: data[26] as u32,
+                bottom: data[27] as u32,
+                padding_value: data[28] as i8 as i32,
+            },
+            stride: Stride {
+                x: data[29] as u32,
+                y: data[30] as u32,
+            },
+            mac_clip: data[31] as u32,
+            pp_clip: data[32] as u32,
+            rounding,
+            zero_points: [read_i32(data, 36), read_i32(data, 40), read_i32(data, 44)],
+            weights,
+            bias,
+            multipliers: &data[multipliers_start..size],
+        };
+        desc.validate()?;
+        Ok((desc, size))
+    }
+
+    /// Checks that weights, bias and multipliers match the layer kind
+    fn validate(&self) -> Result<(), DlaError> {
+        let (kernels, channels, height, width) = self.shape;
+        let weights = match self.kind {
+            LayerKind::Conv2d | LayerKind::DepthwiseConv2d | LayerKind::FullyConnected => kernels
+                .checked_mul(channels)
+                .and_then(|len| len.checked_mul(height))
+                .and_then(|len| len.checked_mul(width))
+                .ok_or(DlaError::InvalidModel)?,
+            LayerKind::MaxPool2d | LayerKind::AvgPool2d | LayerKind::Add => 0,
+        };
+        let bias = match self.bias.to_i32() {
+            Some(bias) => bias.len() == kernels && weights != 0,
+            None => true,
+        };
+        let multipliers = match self.kind {
+            LayerKind::Add => self.multipliers.len() == 3 * 8,
+            LayerKind::MaxPool2d | LayerKind::AvgPool2d => self.multipliers.is_empty(),
+            _ => true,
+        };
+        if self.weights.len() != weights || !bias || !multipliers {
+            return Err(DlaError::InvalidModel);
+        }
+        Ok(())
+    }
+
+    /// Returns the layers or model input the layer reads
+    pub fn inputs(&self) -> impl Iterator<Item = u16> {
+        let second_input = (self.kind == LayerKind::Add).then_some(self.second_input);
+        core::iter::once(self.input).chain(second_input)
+    }
+
+    /// Returns the requantization multipliers of the layer
+    pub fn multipliers(&self) -> Vec<QuantizedMultiplier> {
+        self.multipliers
+            .chunks_exact(8)
+            .map(|m| QuantizedMultiplier::new(read_i32(m, 0), read_i32(m, 4)))
+            .collect()
+    }
+
+    /// Borrows weights as a tensor, without copying them out of the image
+    fn kernels(&self) -> Tensor4View<'a, i8> {
+        let (kernels, channels, height, width) = self.shape;
+        Tensor4View::from_slice(
+            kernels,
+            channels,
+            height,
+            width,
+            self.weights,
+            self.weight_order,
+        )
+        .unwrap()
+    }
+
+    /// Returns bias fitted to DLA's 16 bits along with the MAC and PP clips to use with it
+    fn dla_bias(&self) -> Result<(Option<Vec<i16>>, u32, u32), DlaError> {
+        match self.bias {
+            Bias::None => Ok((None, self.mac_clip, self.pp_clip)),
+            Bias::I16(_) => Ok((
+                self.bias
+                    .to_i32()
+                    .map(|bias| bias.into_iter().map(|b| b as i16).collect()),
+                self.mac_clip,
+                self.pp_clip,
+            )),
+            Bias::I32(_) => {
+                let bias = self.bias.to_i32().unwrap_or_default();
+                let (bias, mac_clip, pp_clip) = rescale_bias(&bias, self.mac_clip, self.pp_clip)?;
+                Ok((Some(bias), mac_clip, pp_clip))
+            }
+        }
+    }
+
+    /// Requantizes DLA output if the layer has multipliers
+    ///
+    /// Approximate, see the [module documentation](self).
+    fn requantize(&self, output: Tensor3<i8>) -> Result<Tensor3<i8>, DlaError> {
+        if self.multipliers.is_empty() {
+            return Ok(output);
+        }
+        // Shift DLA output back to the scale of the MAC results
+        let shift = self.mac_clip + self.pp_clip;
+        let accumulators =
+            output.map(|&x| ((x as i64) << shift).clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+        requantize(
+            &accumulators,
+            &Requantization::per_channel(self.multipliers(), 0, self.zero_points[2], self.rounding),
+        )
+    }
+}
+
+/// Iterator over the layer records of a model image
+pub struct Layers<'a> {
+    data: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> Iterator for Layers<'a> {
+    type Item = LayerDesc<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // Records were validated by `Model::parse`
+        let (desc, size) = LayerDesc::parse(self.data).ok()?;
+        self.data = &self.data[size..];
+        self.remaining -= 1;
+        Some(desc)
+    }
+}
+
+/// Model image validated by [`Model::parse`]
+#[derive(Clone, Copy, Debug)]
+pub struct Model<'a> {
+    data: &'a [u8],
+    layer_count: usize,
+    /// Input shape as channels, height and width
+    pub input_shape: (usize, usize, usize),
+    pub input_order: Order3,
+}
+
+impl<'a> Model<'a> {
+    /// Validates a model image without copying it
+    ///
+    /// # Errors
+    /// - [`DlaError::InvalidModel`] if the image is truncated, of another version or has
+    ///   inconsistent layers.
+    pub fn parse(data: &'a [u8]) -> Result<Self, DlaError> {
+        if data.len() < HEADER_SIZE || data[0..4] != MAGIC || read_u16(data, 4) != VERSION {
+            return Err(DlaError::InvalidModel);
+        }
+        let model = Model {
+            data,
+            layer_count: read_u16(data, 6) as usize,
+            input_shape: (
+                read_u32(data, 8) as usize,
+                read_u32(data, 12) as usize,
+                read_u32(data, 16) as usize,
+            ),
+            input_order: *ORDER3
+                .get(data[20] as usize)
+                .ok_or(DlaError::InvalidModel)?,
+        };
+
+        let mut offset = HEADER_SIZE;
+        for idx in 0..model.layer_count {
+            let (desc, size) = LayerDesc::parse(&data[offset..])?;
+            if !desc
+                .inputs()
+                .all(|input| input == MODEL_INPUT || (input as usize) < idx)
+            {
+                return Err(DlaError::InvalidModel);
+            }
+            offset += size;
+        }
+        Ok(model)
+    }
+
+    /// Returns the layer records of the model
+    pub fn layers(&self) -> Layers<'a> {
+        Layers {
+            data: &self.data[HEADER_SIZE..],
+            remaining: self.layer_count,
+        }
+    }
+
+    /// Runs the model on `input` and returns the output of the last layer
+    ///
+    /// Outputs of layers are kept only as long as a later layer uses them.
+    ///
+    /// # Errors
+    /// - [`DlaError::DimensionMismatch`] if input doesn't match the model or outputs of layers
+    ///   don't match the layers using them.
+    /// - Any error returned by the functions of [`crate::layers`] running the layers.
+    pub fn run(&self, input: Tensor3<i8>) -> Result<Tensor3<i8>, DlaError> {
+        self.run_with(&Dla::new(), input)
+    }
+
+    /// Runs the model on `input` with the given DLA, see [`Model::run`]
+    pub fn run_with<B: RegisterAccess>(
+        &self,
+        dla: &Dla<B>,
+        input: Tensor3<i8>,
+    ) -> Result<Tensor3<i8>, DlaError> {
+        if input.dimensions() != self.input_shape {
+            return Err(DlaError::DimensionMismatch);
+        }
+
+        // Index of the last layer using each output
+        let mut last_use = vec![0; self.layer_count];
+        for (idx, desc) in self.layers().enumerate() {
+            for layer in desc.inputs().filter(|&layer| layer != MODEL_INPUT) {
+                last_use[layer as usize] = idx;
+            }
+        }
+
+        let mut outputs: Vec<Option<Tensor3<i8>>> = Vec::with_capacity(self.layer_count);
+        let mut output = None;
+        for (idx, desc) in self.layers().enumerate() {
+            let fetch = |layer: u16| match layer {
+                MODEL_INPUT => Ok(input.clone()),
+                layer => outputs[layer as usize]
+                    .clone()
+                    .ok_or(DlaError::InvalidModel),
+            };
+            let result = run_layer(dla, &desc, fetch(desc.input)?, || fetch(desc.second_input))?;
+
+            // Free outputs no later layer needs
+            for layer in desc.inputs().filter(|&layer| layer != MODEL_INPUT) {
+                if last_use[layer as usize] == idx {
+                    outputs[layer as usize] = None;
+                }
+            }
+            outputs.push((last_use[idx] > idx).then(|| result.clone()));
+            output = Some(result);
+        }
+        output.ok_or(DlaError::InvalidModel)
+    }
+}
+
+/// Runs a single layer
+fn run_layer<B: RegisterAccess>(
+    dla: &Dla<B>,
+    desc: &LayerDesc,
+    input: Tensor3<i8>,
+    second_input: impl FnOnce() -> Result<Tensor3<i8>, DlaError>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let padding = Some(desc.padding.clone());
+    let stride = Some(desc.stride.clone());
+
+    match desc.kind {
+        LayerKind::Conv2d => {
+            let (bias, mac_clip, pp_clip) = desc.dla_bias()?;
+            let output = try_conv2d_view(
+                dla,
+                input.view(),
+                desc.kernels(),
+                bias.as_deref(),
+                desc.relu,
+                padding,
+                stride,
+                None,
+                Some(mac_clip),
+                Some(pp_clip),
+                None,
+            )?;
+            desc.requantize(output)
+        }
+        LayerKind::DepthwiseConv2d => {
+            let (bias, mac_clip, pp_clip) = desc.dla_bias()?;
+            let mut output = try_depthwise_conv2d(
+                dla,
+                input,
+                desc.kernels().to_owned(),
+                bias,
+                padding,
+                stride,
+                Some(mac_clip),
+                Some(pp_clip),
+                None,
+            )?;
+            if desc.relu {
+                output = output.map(|&x: &i8| x.max(0));
+            }
+            desc.requantize(output)
+        }
+        LayerKind::FullyConnected => {
+            let (bias, mac_clip, pp_clip) = desc.dla_bias()?;
+            let outputs = desc.shape.0;
+            let output = try_linear(
+                dla,
+                input.to_buffer_with_order(Order3::HWC),
+                desc.kernels().to_buffer_with_order(Order4::KCHW),
+                bias,
+                outputs,
+                desc.relu,
+                Some(mac_clip),
+                Some(pp_clip),
+            )?;
+            desc.requantize(
+                Tensor3::from_data_buffer(outputs, 1, 1, output, Order3::HWC)
+                    .map_err(|_| DlaError::DimensionMismatch)?,
+            )
+        }
+        LayerKind::MaxPool2d => try_max_pool2d(dla, input, desc.shape.2 as u32),
+        LayerKind::AvgPool2d => try_avg_pool2d(dla, input, desc.shape.2 as u32),
+        LayerKind::Add => {
+            let second_input = second_input()?;
+            if input.dimensions() != second_input.dimensions() {
+                return Err(DlaError::DimensionMismatch);
+            }
+            let multipliers = desc.multipliers();
+            let params = AddRequantization {
+                input_multipliers: [multipliers[0], multipliers[1]],
+                input_zero_points: [desc.zero_points[0], desc.zero_points[1]],
+                output_multiplier: multipliers[2],
+                output_zero_point: desc.zero_points[2],
+                rounding: desc.rounding,
+            };
+            let output = try_add(dla, input, second_input, &params)?;
+            if desc.relu {
+                let relu_min = desc.zero_points[2].clamp(i8::MIN as i32, i8::MAX as i32) as i8;
+                return Ok(output.map(|&x| x.max(relu_min)));
+            }
+            Ok(output)
+        }
+    }
+}
+
+/// Rounds `len` up to a multiple of 4, or returns `None` if it overflows
+fn align4(len: usize) -> Option<usize> {
+    len.checked_next_multiple_of(4)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    read_u32(data, offset) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference;
+    use crate::test_utils::{self, assert_tensor_eq, rng, simulated};
+    use crate::{PoolMode, Pooling};
+
+    /// Builds a model image header
+    fn header(layers: u16, shape: [u32; 3]) -> Vec<u8> {
+        let mut image = Vec::from(MAGIC);
+        image.extend(VERSION.to_le_bytes());
+        image.extend(layers.to_le_bytes());
+        for dim in shape {
+            image.extend(dim.to_le_bytes());
+        }
+        // CHW input, reserved
+        image.extend([0, 0, 0, 0]);
+        image
+    }
+
+    /// Builds a layer record with ReLU, KCHW weights, padding (1, 0, 2, 1) of -3, stride (2, 1),
+    /// MAC clip 3, PP clip 4, TFLite rounding and zero points (1, 2, -3)
+    fn layer(
+        kind: LayerKind,
+        input: u16,
+        shape: [u32; 4],
+        weights: &[i8],
+        bias: &[i16],
+        multipliers: &[(i32, i32)],
+    ) -> Vec<u8> {
+        let mut record = vec![kind as u8, 1, 0, if bias.is_empty() { 0 } else { 2 }];
+        record.extend(input.to_le_bytes());
+        record.extend(MODEL_INPUT.to_le_bytes());
+        for dim in shape {
+            record.extend(dim.to_le_bytes());
+        }
+        record.extend([1, 0, 2, 1, -3i8 as u8, 2, 1, 3, 4, 2]);
+        record.extend((multipliers.len() as u16).to_le_bytes());
+        for zero_point in [1i32, 2, -3] {
+            record.extend(zero_point.to_le_bytes());
+        }
+        record.extend((weights.len() as u32).to_le_bytes());
+        record.extend((bias.len() as u32).to_le_bytes());
+        assert_eq!(record.len(), LAYER_HEADER_SIZE);
+
+        record.extend(weights.iter().map(|&w| w as u8));
+        record.resize(align4(record.len()).unwrap(), 0);
+        record.extend(bias.iter().flat_map(|b| b.to_le_bytes()));
+        record.resize(align4(record.len()).unwrap(), 0);
+        for (multiplier, shift) in multipliers {
+            record.extend(multiplier.to_le_bytes());
+            record.extend(shift.to_le_bytes());
+        }
+        record
+    }
+
+    #[test]
+    fn parse_reads_back_built_image() {
+        let weights: Vec<i8> = (0..2 * 3 * 3 * 3).map(|w| w as i8 - 20).collect();
+        let mut image = header(2, [3, 8, 7]);
+        image.extend(layer(
+            LayerKind::Conv2d,
+            MODEL_INPUT,
+            [2, 3, 3, 3],
+            &weights,
+            &[-300, 5],
+            &[(1 << 30, -2), (1288490189, -1)],
+        ));
+        image.extend(layer(LayerKind::MaxPool2d, 0, [0, 0, 2, 2], &[], &[], &[]));
+
+        let model = Model::parse(&image).unwrap();
+        assert_eq!(model.input_shape, (3, 8, 7));
+        assert_eq!(model.input_order, Order3::CHW);
+
+        let layers: Vec<_> = model.layers().collect();
+        assert_eq!(layers.len(), 2);
+        let conv = &layers[0];
+        assert_eq!(conv.kind, LayerKind::Conv2d);
+        assert!(conv.relu);
+        assert_eq!(conv.weight_order, Order4::KCHW);
+        assert_eq!(conv.inputs().collect::<Vec<_>>(), [MODEL_INPUT]);
+        assert_eq!(conv.shape, (2, 3, 3, 3));
+        let padding = &conv.padding;
+        assert_eq!(
+            (
+                padding.top,
+                padding.right,
+                padding.left,
+                padding.bottom,
+                padding.padding_value
+            ),
+            (1, 0, 2, 1, -3)
+        );
+        assert_eq!((conv.stride.x, conv.stride.y), (2, 1));
+        assert_eq!((conv.mac_clip, conv.pp_clip), (3, 4));
+        assert_eq!(conv.rounding, Rounding::TfLite);
+        assert_eq!(conv.zero_points, [1, 2, -3]);
+        assert_eq!(conv.weights, &weights[..]);
+        assert_eq!(conv.bias.to_i32(), Some(vec![-300, 5]));
+        assert_eq!(
+            conv.multipliers(),
+            [
+                QuantizedMultiplier::new(1 << 30, -2),
+                QuantizedMultiplier::new(1288490189, -1)
+            ]
+        );
+
+        let pool = &layers[1];
+        assert_eq!(pool.kind, LayerKind::MaxPool2d);
+        assert_eq!(pool.inputs().collect::<Vec<_>>(), [0]);
+        assert!(pool.weights.is_empty());
+        assert_eq!(pool.bias.to_i32(), None);
+        assert!(pool.multipliers().is_empty());
+    }
+
+    #[test]
+    fn run_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 8, 7);
+        let kernels = test_utils::tensor4(&mut rng, 2, 3, 3, 3);
+        let bias = test_utils::values(&mut rng, 2, -300..=300);
+
+        let mut image = header(2, [3, 8, 7]);
+        image.extend(layer(
+            LayerKind::Conv2d,
+            MODEL_INPUT,
+            [2, 3, 3, 3],
+            &kernels.to_buffer_with_order(Order4::KCHW),
+            &bias,
+            &[],
+        ));
+        image.extend(layer(LayerKind::MaxPool2d, 0, [0, 0, 2, 2], &[], &[], &[]));
+        let model = Model::parse(&image).unwrap();
+
+        let output = model.run_with(&dla, input.clone()).unwrap();
+        let expected = reference::conv2d_bias_relu(
+            &input,
+            &kernels,
+            Some(&bias),
+            true,
+            Some(model.layers().next().unwrap().padding),
+            Some(Stride { x: 2, y: 1 }),
+            Some(3),
+            Some(4),
+            None,
+        )
+        .unwrap();
+        let expected = reference::pool2d(
+            &expected,
+            Pooling {
+                mode: PoolMode::Max,
+                size: 2,
+            },
+        )
+        .unwrap();
+        assert_tensor_eq(&output, &expected);
+    }
+
+    #[test]
+    fn parse_rejects_inconsistent_images() {
+        let valid = || {
+            let mut image = header(1, [1, 4, 4]);
+            image.extend(layer(
+                LayerKind::Conv2d,
+                MODEL_INPUT,
+                [1, 1, 2, 2],
+                &[1, 2, 3, 4],
+                &[7],
+                &[],
+            ));
+            image
+        };
+        assert!(Model::parse(&valid()).is_ok());
+
+        let mut truncated = valid();
+        truncated.pop();
+        let mut bad_magic = valid();
+        bad_magic[0] = b'X';
+        let mut forward_input = valid();
+        forward_input[HEADER_SIZE + 4..HEADER_SIZE + 6].copy_from_slice(&0u16.to_le_bytes());
+        let mut shape_mismatch = valid();
+        shape_mismatch[HEADER_SIZE + 8] = 2;
+        // Weight shape whose size doesn't fit into usize
+        let mut shape_overflow = valid();
+        for offset in [8, 12, 16, 20] {
+            let offset = HEADER_SIZE + offset;
+            shape_overflow[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        }
+        let mut weights_overflow = valid();
+        weights_overflow[HEADER_SIZE + 48..HEADER_SIZE + 52]
+            .copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut bias_overflow = valid();
+        bias_overflow[HEADER_SIZE + 3] = 4;
+        bias_overflow[HEADER_SIZE + 52..HEADER_SIZE + 56].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        for image in [
+            truncated,
+            bad_magic,
+            forward_input,
+            shape_mismatch,
+            shape_overflow,
+            weights_overflow,
+            bias_overflow,
+        ] {
+            assert_eq!(Model::parse(&image).unwrap_err(), DlaError::InvalidModel);
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Integer-only requantization of layer outputs
+//!
+//! Follows the fixed-point scheme of TFLite and TVM's `qnn.requantize`: a real valued scale is
+//! represented as a 32-bit multiplier in Q0.31 format and a power of two shift, so that
+//! `scale = multiplier * 2^(shift - 31)`. Requantizing a value then only needs integer arithmetic:
+//!
+//! `output = clamp(round((input - input_zero_point) * scale) + output_zero_point, -128, 127)`
+//!
+//! Scales can be given per tensor or per output channel. Multipliers are usually calculated on
+//! host, e.g. by TVM or the TFLite converter, but [`QuantizedMultiplier::from_scale`] does the same
+//! without floating point operations.
+use crate::tensor3::{Order3, Tensor3};
+use crate::DlaError;
+use alloc::vec::Vec;
+
+/// Largest post-processing clip DLA supports
+pub(crate) const MAX_PP_CLIP: i32 = 0x1F;
+
+/// Fixed-point representation of a real valued scale
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuantizedMultiplier {
+    /// Mantissa of the scale in Q0.31 format, between 2^30 and 2^31 - 1 for non-zero scales
+    pub multiplier: i32,
+    /// Power of two exponent of the scale, positive values shift left
+    pub shift: i32,
+}
+
+impl QuantizedMultiplier {
+    /// Creates a multiplier, rounding scales below `2^-62` to zero
+    ///
+    /// Such scales round every 32-bit value to zero, like in [`QuantizedMultiplier::from_scale`].
+    pub fn new(multiplier: i32, shift: i32) -> Self {
+        if shift < -31 {
+            return QuantizedMultiplier {
+                multiplier: 0,
+                shift: 0,
+            };
+        }
+        QuantizedMultiplier { multiplier, shift }
+    }
+
+    /// Calculates multiplier and shift for a scale, as `QuantizeMultiplier` of TFLite does
+    ///
+    /// Only the bit representation of `scale` is used, so no floating point instructions are
+    /// needed. Returns `None` for negative, subnormal and non-finite scales.
+    pub fn from_scale(scale: f64) -> Option<Self> {
+        let bits = scale.to_bits();
+        // Positive or negative zero
+        if bits << 1 == 0 {
+            return Some(QuantizedMultiplier::new(0, 0));
+        }
+        let biased_exponent = ((bits >> 52) & 0x7FF) as i32;
+        if scale.is_sign_negative() || biased_exponent == 0 || biased_exponent == 0x7FF {
+            return None;
+        }
+
+        // scale = mantissa * 2^(exponent - 53), where mantissa has 53 significant bits
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let mut shift = biased_exponent - 1022;
+        // Round the mantissa to 31 fractional bits
+        let mut multiplier = (mantissa + (1 << 21)) >> 22;
+        if multiplier == 1 << 31 {
+            multiplier /= 2;
+            shift += 1;
+        }
+        // Scales too small to represent round to zero, like in TFLite
+        if shift < -31 {
+            return Some(QuantizedMultiplier::new(0, 0));
+        }
+        i32::try_from(multiplier)
+            .ok()
+            .map(|multiplier| QuantizedMultiplier::new(multiplier, shift))
+    }
+}
+
+/// How fixed-point multiplication results are rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Ties round towards positive infinity, TVM's default
+    Upward,
+    /// Ties round away from zero, TVM's `TONEAREST`
+    ToNearest,
+    /// Rounding doubling high multiply followed by rounding shift, bit-exact with TFLite
+    TfLite,
+}
+
+/// Multiplication by the high half of the doubled 64-bit product, rounding to nearest
+///
+/// Equivalent to `SaturatingRoundingDoublingHighMul` of gemmlowp.
+pub fn saturating_rounding_doubling_high_mul(a: i32, b: i32) -> i32 {
+    if a == i32::MIN && b == i32::MIN {
+        return i32::MAX;
+    }
+    let ab = a as i64 * b as i64;
+    let nudge = if ab >= 0 { 1 << 30 } else { 1 - (1 << 30) };
+    ((ab + nudge) / (1 << 31)) as i32
+}
+
+/// Arithmetic right shift, rounding ties away from zero
+///
+/// Equivalent to `RoundingDivideByPOT` of gemmlowp, which only defines exponents up to 31.
+pub fn rounding_divide_by_pot(value: i32, exponent: u32) -> i32 {
+    if exponent == 0 {
+        return value;
+    }
+    // Every 32-bit value rounds to zero when divided by 2^33 or more
+    let exponent = exponent.min(33);
+    let value = value as i64;
+    let mask = (1i64 << exponent) - 1;
+    let remainder = value & mask;
+    let threshold = (mask >> 1) + (value < 0) as i64;
+    ((value >> exponent) + (remainder > threshold) as i64) as i32
+}
+
+/// Multiplies value by the scale represented by `multiplier`
+pub fn multiply_by_quantized_multiplier(
+    value: i32,
+    multiplier: QuantizedMultiplier,
+    rounding: Rounding,
+) -> i32 {
+    let left_shift = multiplier.shift.max(0) as u32;
+    let right_shift = (-multiplier.shift).max(0) as u32;
+
+    if rounding == Rounding::TfLite {
+        let shifted =
+            ((value as i64) << left_shift.min(32)).clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        return rounding_divide_by_pot(
+            saturating_rounding_doubling_high_mul(shifted, multiplier.multiplier),
+            right_shift,
+        );
+    }
+
+    // Products of Q0.31 multipliers and 32-bit values round to zero when shifted right by 63 or
+    // more
+    let total_right_shift = right_shift.min(32) + 31;
+    let rounding_value = 1i64 << (total_right_shift - 1);
+    let rounding_value = match rounding {
+        Rounding::ToNearest if value < 0 => rounding_value - 1,
+        _ => rounding_value,
+    };
+    // 128 bits, as large left shifts would overflow 64 bits
+    let product = ((value as i128) << left_shift.min(64)) * multiplier.multiplier as i128;
+    let result = (product + rounding_value as i128) >> total_right_shift;
+    result.clamp(i32::MIN as i128, i32::MAX as i128) as i32
+}
+
+/// Parameters for requantizing a layer output to 8 bits
+#[derive(Clone, Debug)]
+pub struct Requantization {
+    /// A single scale for the whole tensor, or one for each channel
+    pub multipliers: Vec<QuantizedMultiplier>,
+    pub input_zero_point: i32,
+    pub output_zero_point: i32,
+    pub rounding: Rounding,
+}
+
+impl Requantization {
+    /// Requantization with a single scale for the whole tensor
+    pub fn per_tensor(
+        multiplier: QuantizedMultiplier,
+        input_zero_point: i32,
+        output_zero_point: i32,
+        rounding: Rounding,
+    ) -> Self {
+        Requantization {
+            multipliers: vec![multiplier],
+            input_zero_point,
+            output_zero_point,
+            rounding,
+        }
+    }
+
+    /// Requantization with a scale for each channel
+    pub fn per_channel(
+        multipliers: Vec<QuantizedMultiplier>,
+        input_zero_point: i32,
+        output_zero_point: i32,
+        rounding: Rounding,
+    ) -> Self {
+        Requantization {
+            multipliers,
+            input_zero_point,
+            output_zero_point,
+            rounding,
+        }
+    }
+
+    /// Requantizes a single value of the given channel
+    pub fn requantize_value(&self, value: i32, channel: usize) -> i8 {
+        let multiplier = if self.multipliers.len() == 1 {
+            self.multipliers[0]
+        } else {
+            self.multipliers[channel]
+        };
+        let scaled = multiply_by_quantized_multiplier(
+            value.saturating_sub(self.input_zero_point),
+            multiplier,
+            self.rounding,
+        );
+        scaled
+            .saturating_add(self.output_zero_point)
+            .clamp(i8::MIN as i32, i8::MAX as i32) as i8
+    }
+
+    /// Calculates the post-processing clip for DLA's 8-bit output that loses the least precision
+    /// before requantization without saturating
+    ///
+    /// Outputs of DLA need to be shifted left by the returned amount (plus MAC clip) to get
+    /// accumulators back in scale. As the outputs only have 8 bits, the clip is chosen
+    /// such that every accumulator requantizing into the 8-bit range still fits into 8 bits.
+    pub fn dla_pp_clip(&self) -> u32 {
+        // Largest accumulator needed is 2^7 * (1 + |zero point| / 2^7) / scale, where
+        // scale >= 2^(shift - 1)
+        let range = 128 + self.output_zero_point.unsigned_abs();
+        let range_bits = (u32::BITS - (range - 1).leading_zeros()) as i32 - 7;
+        self.multipliers
+            .iter()
+            .filter(|multiplier| multiplier.multiplier != 0)
+            .map(|multiplier| range_bits + 1 - multiplier.shift)
+            .max()
+            .unwrap_or(0)
+            .clamp(0, MAX_PP_CLIP) as u32
+    }
+}
+
+/// Left shift applied to both inputs of an addition before rescaling them, as in TFLite
+pub const ADD_LEFT_SHIFT: u32 = 20;
+
+/// Parameters for adding two quantized tensors of different scales
+///
+/// Both inputs are shifted left by [`ADD_LEFT_SHIFT`] and rescaled to a common scale, then the sum
+/// is requantized to the output scale, following TFLite's quantized `ADD`.
+#[derive(Clone, Debug)]
+pub struct AddRequantization {
+    pub input_multipliers: [QuantizedMultiplier; 2],
+    pub input_zero_points: [i32; 2],
+    pub output_multiplier: QuantizedMultiplier,
+    pub output_zero_point: i32,
+    pub rounding: Rounding,
+}
+
+impl AddRequantization {
+    /// Adds two quantized values and requantizes the sum to 8 bits
+    pub fn add(&self, a: i32, b: i32) -> i8 {
+        let rescale = |value: i32, input: usize| {
+            multiply_by_quantized_multiplier(
+                value.saturating_sub(self.input_zero_points[input]) << ADD_LEFT_SHIFT,
+                self.input_multipliers[input],
+                self.rounding,
+            )
+        };
+        let sum = rescale(a, 0).saturating_add(rescale(b, 1));
+        multiply_by_quantized_multiplier(sum, self.output_multiplier, self.rounding)
+            .saturating_add(self.output_zero_point)
+            .clamp(i8::MIN as i32, i8::MAX as i32) as i8
+    }
+}
+
+/// Parameters for multiplying two quantized tensors
+///
+/// The product of the inputs, less their zero points, is requantized with a single multiplier for
+/// `input_scale_a * input_scale_b / output_scale`, following TFLite's quantized `MUL`.
+#[derive(Clone, Debug)]
+pub struct MulRequantization {
+    pub input_zero_points: [i32; 2],
+    pub output_multiplier: QuantizedMultiplier,
+    pub output_zero_point: i32,
+    pub rounding: Rounding,
+}
+
+impl MulRequantization {
+    /// Multiplies two quantized values and requantizes the product to 8 bits
+    pub fn mul(&self, a: i32, b: i32) -> i8 {
+        let product = a
+            .saturating_sub(self.input_zero_points[0])
+            .saturating_mul(b.saturating_sub(self.input_zero_points[1]));
+        multiply_by_quantized_multiplier(product, self.output_multiplier, self.rounding)
+            .saturating_add(self.output_zero_point)
+            .clamp(i8::MIN as i32, i8::MAX as i32) as i8
+    }
+}
+
+/// Requantizes a layer output to 8 bits
+///
+/// Works with any of DLA's output widths, e.g. `Tensor3<i8>` shifted back by its clip amounts or
+/// `Tensor3<i32>` accumulators. The output keeps the order of the input.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if there is neither a single scale nor one per channel.
+pub fn requantize<T: Copy + Into<i32>>(
+    input: &Tensor3<T>,
+    params: &Requantization,
+) -> Result<Tensor3<i8>, DlaError> {
+    let (channels, height, width) = input.dimensions();
+    if params.multipliers.len() != 1 && params.multipliers.len() != channels {
+        return Err(DlaError::DimensionMismatch);
+    }
+
+    let output = input
+        .to_buffer_with_order(Order3::HWC)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| params.requantize_value(value.into(), idx % channels))
+        .collect();
+    let mut output = Tensor3::from_data_buffer(channels, height, width, output, Order3::HWC)
+        .map_err(|_| DlaError::DimensionMismatch)?;
+    output.permute(input.order());
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected values are calculated by hand with TFLite's `QuantizeMultiplier` and gemmlowp's
+    // fixed-point functions
+
+    #[test]
+    fn from_scale_matches_tflite() {
+        let cases = [
+            (0.0, Some((0, 0))),
+            (1.0, Some((1 << 30, 1))),
+            (0.25, Some((1 << 30, -1))),
+            (0.3, Some((1288490189, -1))),
+            (1.0 / 3.0, Some((1431655765, -1))),
+            (1.5, Some((1610612736, 1))),
+            // Too small to represent
+            (2f64.powi(-40), Some((0, 0))),
+            (-1.0, None),
+            (f64::INFINITY, None),
+        ];
+        for (scale, expected) in cases {
+            let expected =
+                expected.map(|(multiplier, shift)| QuantizedMultiplier::new(multiplier, shift));
+            assert_eq!(QuantizedMultiplier::from_scale(scale), expected, "{scale}");
+        }
+    }
+
+    #[test]
+    fn fixed_point_matches_gemmlowp() {
+        assert_eq!(
+            saturating_rounding_doubling_high_mul(i32::MIN, i32::MIN),
+            i32::MAX
+        );
+        assert_eq!(
+            saturating_rounding_doubling_high_mul(1 << 30, 1 << 30),
+            1 << 29
+        );
+        assert_eq!(saturating_rounding_doubling_high_mul(3, 1 << 30), 2);
+        // Ties of negative products round towards zero
+        assert_eq!(saturating_rounding_doubling_high_mul(-3, 1 << 30), -1);
+        assert_eq!(saturating_rounding_doubling_high_mul(100, 1288490189), 60);
+
+        for (value, exponent, expected) in [
+            (3, 1, 2),
+            (-3, 1, -2),
+            (5, 2, 1),
+            (6, 2, 2),
+            (-5, 2, -1),
+            (-6, 2, -2),
+            (i32::MAX, 31, 1),
+            (i32::MIN, 31, -1),
+        ] {
+            assert_eq!(rounding_divide_by_pot(value, exponent), expected);
+        }
+    }
+
+    #[test]
+    fn multiply_rounds_like_tflite_and_tvm() {
+        let quarter = QuantizedMultiplier::new(1 << 30, -1);
+        let point_three = QuantizedMultiplier::new(1288490189, -1);
+        // (value, multiplier, [Upward, ToNearest, TfLite])
+        let cases = [
+            (100, point_three, [30, 30, 30]),
+            (-7, quarter, [-2, -2, -2]),
+            (-6, quarter, [-1, -2, -2]),
+            (6, quarter, [2, 2, 2]),
+            (1000, QuantizedMultiplier::new(1 << 30, 4), [8000; 3]),
+            (-1000, QuantizedMultiplier::new(1 << 30, 4), [-8000; 3]),
+        ];
+        for (value, multiplier, expected) in cases {
+            for (rounding, expected) in [Rounding::Upward, Rounding::ToNearest, Rounding::TfLite]
+                .into_iter()
+                .zip(expected)
+            {
+                assert_eq!(
+                    multiply_by_quantized_multiplier(value, multiplier, rounding),
+                    expected,
+                    "{value} {multiplier:?} {rounding:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn large_right_shifts_round_to_zero() {
+        assert_eq!(
+            QuantizedMultiplier::new(1 << 30, -40),
+            QuantizedMultiplier::new(0, 0)
+        );
+        assert_eq!(rounding_divide_by_pot(i32::MIN, 32), -1);
+        assert_eq!(rounding_divide_by_pot(i32::MIN, 40), 0);
+
+        // Fields can be set without `new`
+        let tiny = QuantizedMultiplier {
+            multiplier: i32::MAX,
+            shift: -100,
+        };
+        for rounding in [Rounding::Upward, Rounding::ToNearest, Rounding::TfLite] {
+            for value in [i32::MIN, -1, 1, i32::MAX] {
+                assert_eq!(multiply_by_quantized_multiplier(value, tiny, rounding), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn requantize_value_applies_zero_points_and_saturates() {
+        let requantization = Requantization::per_channel(
+            vec![
+                QuantizedMultiplier::new(1288490189, -1),
+                QuantizedMultiplier::new(1 << 30, 3),
+            ],
+            10,
+            -5,
+            Rounding::TfLite,
+        );
+        assert_eq!(requantization.requantize_value(110, 0), 25);
+        assert_eq!(requantization.requantize_value(20, 1), 35);
+        assert_eq!(requantization.requantize_value(50, 1), 127);
+        assert_eq!(requantization.requantize_value(-30, 1), -128);
+    }
+}
@@ -0,0 +1,658 @@
+//! Bit-exact software model of DLA's calculation pipeline
+//!
+//! Implements the same MAC clip, bias, ReLU, post-processing clip, rounding and padding
+//! semantics as the hardware and `vp/devel/python_peripherals/DLA.py`. Can be used for verifying
+//! DLA outputs on target or generating golden data on host.
+//!
+//! Pipeline for a single output value:
+//! 1. MAC: sum of products over kernel window and all channels, padded with `padding_value`
+//! 2. MAC saturation to `MAC_SAT_MIN..=MAC_SAT_MAX`
+//! 3. MAC clip: arithmetic right shift by `mac_clip` and saturation to 16 bits, if `mac_clip` > 0
+//! 4. Bias: 16-bit bias of the output channel is added
+//! 5. ReLU
+//! 6. Saturation to 16 bits
+//! 7. PP clip: arithmetic right shift by `pp_clip` and saturation to 16 bits, if `pp_clip` > 0.
+//!    The shift rounds to nearest if the rounding bit of PP_CTRL is set, see [`pp_clip`].
+//! 8. Output saturation to 8 bits, see [`saturate_output`]
+//! 9. Pooling, see [`pool2d`]
+//!
+//! Post-processing only layers skip steps 1 to 3 and take the 8-bit input as MAC results, see
+//! [`post_process`].
+//!
+//! The layer functions of this module model DLA with MAC results unsaturated and the rounding bit
+//! cleared, which is how the VP calculates every layer.
+use crate::tensor3::{Order3, Tensor3};
+use crate::tensor4::{Order4, Tensor4};
+use crate::utils::calculate_conv2d_out_param_dim;
+use crate::{
+    DlaError, Padding, PoolMode, Pooling, SimdBitMode, Stride, DEFAULT_MAC_CLIP, DEFAULT_PADDING,
+    DEFAULT_PP_CLIP, DEFAULT_SIMD_MODE, DEFAULT_STRIDE,
+};
+use alloc::vec::Vec;
+
+/// Saturates value to the range of a signed integer of `bit_width` bits
+pub fn saturate(value: i32, bit_width: u32) -> i32 {
+    let upper_bound = (1i32 << (bit_width - 1)) - 1;
+    let lower_bound = -upper_bound - 1;
+    value.clamp(lower_bound, upper_bound)
+}
+
+/// Shifts value right by `clip_amount` bits and saturates it to `bit_width` bits
+pub fn clip(value: i32, clip_amount: u32, bit_width: u32) -> i32 {
+    saturate(value >> clip_amount, bit_width)
+}
+
+/// Applies MAC clip to a MAC output, no-op when `clip_amount` is 0
+pub fn mac_clip(value: i32, clip_amount: u32) -> i32 {
+    if clip_amount > 0 {
+        clip(value, clip_amount, 16)
+    } else {
+        value
+    }
+}
+
+/// Applies PP clip to a post-processed value, no-op when `clip_amount` is 0
+///
+/// With `round`, as with the rounding bit of PP_CTRL set, the shift rounds to nearest with halves
+/// rounded up instead of truncating.
+pub fn pp_clip(value: i32, clip_amount: u32, round: bool) -> i32 {
+    if clip_amount == 0 {
+        return value;
+    }
+    let value = if round {
+        value.saturating_add(1 << (clip_amount - 1))
+    } else {
+        value
+    };
+    clip(value, clip_amount, 16)
+}
+
+/// Saturates post-processed value to the 8-bit output of DLA
+pub fn saturate_output(value: i32) -> i8 {
+    saturate(value, 8) as i8
+}
+
+/// Interprets value as DLA does in the given SIMD mode, i.e. sign extends its low bits
+fn simd_value(value: i8, simd_mode: SimdBitMode) -> i32 {
+    match simd_mode {
+        SimdBitMode::EightBits => value as i32,
+        SimdBitMode::FourBits => ((value << 4) >> 4) as i32,
+        SimdBitMode::TwoBits => ((value << 6) >> 6) as i32,
+    }
+}
+
+/// Calculates raw MAC outputs of a 2D convolution.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `simd_mode`: An optional `SimdBitMode` defining how many low bits of each value are used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of 32-bit integers in HWC order, as DLA writes it.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if input and kernel dimensions are incompatible.
+pub fn conv2d_mac(
+    input: &Tensor3<i8>,
+    kernels: &Tensor4<i8>,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<i32>, DlaError> {
+    let padding = padding.unwrap_or(DEFAULT_PADDING);
+    let stride = stride.unwrap_or(DEFAULT_STRIDE);
+    let simd_mode = simd_mode.unwrap_or(DEFAULT_SIMD_MODE);
+
+    let (channels, height, width) = input.dimensions();
+    let (num_kernels, kernel_channels, kernel_height, kernel_width) = (
+        kernels.kernels(),
+        kernels.channels(),
+        kernels.height(),
+        kernels.width(),
+    );
+    if channels != kernel_channels
+        || kernel_height > height + (padding.top + padding.bottom) as usize
+        || kernel_width > width + (padding.left + padding.right) as usize
+        || stride.x == 0
+        || stride.y == 0
+    {
+        return Err(DlaError::DimensionMismatch);
+    }
+
+    let (output_width, output_height) = calculate_conv2d_out_param_dim(
+        (width as u32, height as u32),
+        (kernel_width as u32, kernel_height as u32),
+        Some(padding.clone()),
+        Some(stride.clone()),
+    );
+
+    // Padding value register is 8 bits wide and read as a signed byte
+    let padding_value = simd_value(padding.padding_value as i8, simd_mode);
+    let input = input.to_buffer_with_order(Order3::CHW);
+    let kernels = kernels.to_buffer_with_order(Order4::KCHW);
+
+    let mut output = Vec::with_capacity(output_height * output_width * num_kernels);
+    for out_y in 0..output_height {
+        for out_x in 0..output_width {
+            for k in 0..num_kernels {
+                let mut sum: i32 = 0;
+                for c in 0..channels {
+                    for ky in 0..kernel_height {
+                        // Coordinates in padded input
+                        let y = (out_y * stride.y as usize + ky) as isize - padding.top as isize;
+                        for kx in 0..kernel_width {
+                            let x =
+                                (out_x * stride.x as usize + kx) as isize - padding.left as isize;
+                            let value =
+                                if y < 0 || x < 0 || y as usize >= height || x as usize >= width {
+                                    padding_value
+                                } else {
+                                    simd_value(
+                                        input[(c * height + y as usize) * width + x as usize],
+                                        simd_mode,
+                                    )
+                                };
+                            let weight = simd_value(
+                                kernels
+                                    [((k * channels + c) * kernel_height + ky) * kernel_width + kx],
+                                simd_mode,
+                            );
+                            sum = sum.wrapping_add(value * weight);
+                        }
+                    }
+                }
+                output.push(sum);
+            }
+        }
+    }
+
+    Tensor3::from_data_buffer(
+        num_kernels,
+        output_height,
+        output_width,
+        output,
+        Order3::HWC,
+    )
+    .map_err(|_| DlaError::DimensionMismatch)
+}
+
+/// Calculates outputs of the MAC array and bias/ReLU stage without post-processing clipping.
+///
+/// Matches the 32-bit output of the VP (`DLA_VP_OUT32`), which is used when `mac_clip` is 0.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: An optional slice of 16-bit signed integers containing biases for each kernel.
+/// - `relu`: Enables ReLU.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `simd_mode`: An optional `SimdBitMode` defining how many low bits of each value are used.
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if `mac_clip` is out of range.
+/// - [`DlaError::DimensionMismatch`] if input, kernel and bias dimensions are incompatible.
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_bias_relu_i32(
+    input: &Tensor3<i8>,
+    kernels: &Tensor4<i8>,
+    bias: Option<&[i16]>,
+    relu: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<i32>, DlaError> {
+    let mac_clip_amount = mac_clip.unwrap_or(DEFAULT_MAC_CLIP);
+    if mac_clip_amount > 21 {
+        return Err(DlaError::InvalidClip(mac_clip_amount));
+    }
+    if let Some(bias) = bias {
+        if bias.len() != kernels.kernels() {
+            return Err(DlaError::DimensionMismatch);
+        }
+    }
+
+    let mac = conv2d_mac(input, kernels, padding, stride, simd_mode)?;
+    let (num_kernels, height, width) = mac.dimensions();
+    let output = mac
+        .to_buffer_with_order(Order3::HWC)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let mut value = self::mac_clip(value, mac_clip_amount);
+            if let Some(bias) = bias {
+                value = value.wrapping_add(bias[idx % num_kernels] as i32);
+            }
+            if relu {
+                value = value.max(0);
+            }
+            value
+        })
+        .collect();
+
+    Tensor3::from_data_buffer(num_kernels, height, width, output, Order3::HWC)
+        .map_err(|_| DlaError::DimensionMismatch)
+}
+
+/// Calculates 2D convolution + Bias + ReLU the same way as DLA does.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `kernels`: A 4-dimensional tensor of 8-bit signed integers (`Tensor4<i8>`) representing the convolution kernels.
+/// - `bias`: An optional slice of 16-bit signed integers containing biases for each kernel.
+/// - `relu`: Enables ReLU.
+/// - `padding`: An optional `Padding` parameter defining the padding strategy applied to the input.
+/// - `stride`: An optional `Stride` parameter defining the stride of the convolution in X and Y directions.
+/// - `mac_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after Conv2D operations.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+/// - `simd_mode`: An optional `SimdBitMode` defining how many low bits of each value are used.
+///
+/// # Returns
+/// - A 3-dimensional tensor of 8-bit integers in HWC order, matching the output of
+///   [`crate::layers::conv2d_bias_relu`] bit by bit.
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if `mac_clip` or `pp_clip` is out of range.
+/// - [`DlaError::DimensionMismatch`] if input, kernel and bias dimensions are incompatible.
+#[allow(clippy::too_many_arguments)]
+pub fn conv2d_bias_relu(
+    input: &Tensor3<i8>,
+    kernels: &Tensor4<i8>,
+    bias: Option<&[i16]>,
+    relu: bool,
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    mac_clip: Option<u32>,
+    pp_clip: Option<u32>,
+    simd_mode: Option<SimdBitMode>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let pp_clip_amount = pp_clip.unwrap_or(DEFAULT_PP_CLIP);
+    if pp_clip_amount > 0x1F {
+        return Err(DlaError::InvalidClip(pp_clip_amount));
+    }
+
+    let res = conv2d_bias_relu_i32(
+        input, kernels, bias, relu, padding, stride, mac_clip, simd_mode,
+    )?;
+    let (num_kernels, height, width) = res.dimensions();
+    let output = res
+        .to_buffer_with_order(Order3::HWC)
+        .into_iter()
+        .map(|value| saturate_output(self::pp_clip(saturate(value, 16), pp_clip_amount, false)))
+        .collect();
+
+    Tensor3::from_data_buffer(num_kernels, height, width, output, Order3::HWC)
+        .map_err(|_| DlaError::DimensionMismatch)
+}
+
+/// Calculates bias + ReLU on existing data the same way as DLA's post-processor does without the
+/// MAC array.
+///
+/// # Arguments
+/// - `input`: A 3-dimensional tensor of 8-bit signed integers (`Tensor3<i8>`) representing the input feature map.
+/// - `bias`: An optional slice of 16-bit signed integers containing biases for each channel.
+/// - `relu`: Enables ReLU.
+/// - `pp_clip`: An optional 32-bit unsigned integer (`u32`) specifying the amount of clipping after post-processing pipeline.
+///
+/// # Returns
+/// - A 3-dimensional tensor of 8-bit integers in HWC order, matching the output of
+///   [`crate::layers::relu`] and [`crate::layers::bias`] bit by bit before they restore the order
+///   of the input.
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if `pp_clip` is out of range.
+/// - [`DlaError::DimensionMismatch`] if there isn't a bias for each channel.
+pub fn post_process(
+    input: &Tensor3<i8>,
+    bias: Option<&[i16]>,
+    relu: bool,
+    pp_clip: Option<u32>,
+) -> Result<Tensor3<i8>, DlaError> {
+    let pp_clip_amount = pp_clip.unwrap_or(DEFAULT_PP_CLIP);
+    if pp_clip_amount > 0x1F {
+        return Err(DlaError::InvalidClip(pp_clip_amount));
+    }
+    let (channels, height, width) = input.dimensions();
+    if let Some(bias) = bias {
+        if bias.len() != channels {
+            return Err(DlaError::DimensionMismatch);
+        }
+    }
+
+    let output = input
+        .to_buffer_with_order(Order3::HWC)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let mut value = value as i32;
+            if let Some(bias) = bias {
+                value += bias[idx % channels] as i32;
+            }
+            if relu {
+                value = value.max(0);
+            }
+            saturate_output(self::pp_clip(saturate(value, 16), pp_clip_amount, false))
+        })
+        .collect();
+
+    Tensor3::from_data_buffer(channels, height, width, output, Order3::HWC)
+        .map_err(|_| DlaError::DimensionMismatch)
+}
+
+/// Pools non-overlapping `pooling.size` x `pooling.size` windows of each channel
+///
+/// Average is rounded towards negative infinity.
+///
+/// # Errors
+/// - [`DlaError::DimensionMismatch`] if window size is 0 or larger than the input.
+pub fn pool2d(input: &Tensor3<i8>, pooling: Pooling) -> Result<Tensor3<i8>, DlaError> {
+    let size = pooling.size as usize;
+    let (channels, height, width) = input.dimensions();
+    if size == 0 || size > height || size > width {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let (output_height, output_width) = (height / size, width / size);
+
+    let input = input.to_buffer_with_order(Order3::HWC);
+    let mut output = Vec::with_capacity(output_height * output_width * channels);
+    for out_y in 0..output_height {
+        for out_x in 0..output_width {
+            for c in 0..channels {
+                let window = (0..size).flat_map(|dy| {
+                    let y = out_y * size + dy;
+                    (0..size).map(move |dx| (y, out_x * size + dx))
+                });
+                let values = window.map(|(y, x)| input[(y * width + x) * channels + c] as i32);
+                let value = match pooling.mode {
+                    PoolMode::Max => values.max().unwrap_or(0),
+                    PoolMode::Average => values.sum::<i32>().div_euclid((size * size) as i32),
+                };
+                output.push(value as i8);
+            }
+        }
+    }
+
+    Tensor3::from_data_buffer(channels, output_height, output_width, output, Order3::HWC)
+        .map_err(|_| DlaError::DimensionMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Golden outputs are the RTL dumps in `examples/test_data`, also used by `validate_conv`, and
+    // layers calculated with `DLA.py`. SIMD vectors are calculated by hand, as `DLA.py` doesn't
+    // model SIMD modes.
+
+    /// Parses bytes of a `.mem` dump, written as hex digits in memory order
+    fn mem_bytes(mem: &str) -> Vec<u8> {
+        let digits: Vec<u8> = mem
+            .chars()
+            .filter_map(|c| c.to_digit(16))
+            .map(|d| d as u8)
+            .collect();
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect()
+    }
+
+    fn mem_i8(mem: &str) -> Vec<i8> {
+        mem_bytes(mem).into_iter().map(|x| x as i8).collect()
+    }
+
+    fn mem_i16(mem: &str) -> Vec<i16> {
+        mem_bytes(mem)
+            .chunks(2)
+            .map(|chunk| i16::from_be_bytes([chunk[0], chunk[1]]))
+            .collect()
+    }
+
+    fn mem_i32(mem: &str) -> Vec<i32> {
+        mem_bytes(mem)
+            .chunks(4)
+            .map(|chunk| i32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+
+    fn conv_16x16x16_3x3() -> (Tensor3<i8>, Tensor4<i8>) {
+        let input = Tensor3::from_data_buffer(
+            16,
+            16,
+            16,
+            mem_i8(include_str!(
+                "../examples/test_data/conv_16x16x16_3x3_din.mem"
+            )),
+            Order3::HWC,
+        )
+        .unwrap();
+        let kernels = Tensor4::from_data_buffer(
+            16,
+            16,
+            3,
+            3,
+            mem_i8(include_str!(
+                "../examples/test_data/conv_16x16x16_3x3_wgt.mem"
+            )),
+            Order4::HWKC,
+        )
+        .unwrap();
+        (input, kernels)
+    }
+
+    #[test]
+    fn conv2d_mac_matches_tiny_rtl_dump() {
+        let input = Tensor3::from_data_buffer(
+            3,
+            5,
+            5,
+            mem_i8(include_str!("../examples/test_data/tiny_test_din.mem")),
+            Order3::HWC,
+        )
+        .unwrap();
+        let kernels = Tensor4::from_data_buffer(
+            2,
+            3,
+            3,
+            3,
+            mem_i8(include_str!("../examples/test_data/tiny_test_wgt.mem")),
+            Order4::HWKC,
+        )
+        .unwrap();
+        let expected: Vec<i32> = mem_i8(include_str!("../examples/test_data/tiny_test_dout.mem"))
+            .into_iter()
+            .map(i32::from)
+            .collect();
+
+        let output = conv2d_mac(&input, &kernels, None, None, None).unwrap();
+        assert_eq!(output.dimensions(), (2, 3, 3));
+        assert_eq!(output.to_buffer_with_order(Order3::HWC), expected);
+    }
+
+    #[test]
+    fn conv2d_mac_matches_rtl_dump() {
+        let (input, kernels) = conv_16x16x16_3x3();
+        let expected = mem_i32(include_str!(
+            "../examples/test_data/conv_16x16x16_3x3_dout.mem"
+        ));
+
+        let output = conv2d_mac(&input, &kernels, None, None, None).unwrap();
+        assert_eq!(output.dimensions(), (16, 14, 14));
+        assert_eq!(output.to_buffer_with_order(Order3::HWC), expected);
+    }
+
+    #[test]
+    fn conv2d_bias_relu_matches_clipped_rtl_dump() {
+        let (input, kernels) = conv_16x16x16_3x3();
+        let bias = mem_i16(include_str!("../examples/test_data/bias.mem"));
+        let expected = mem_i8(include_str!("../examples/test_data/bias_test.out"));
+        let padding = Padding {
+            top: 0,
+            left: 0,
+            right: 1,
+            bottom: 1,
+            padding_value: 0,
+        };
+
+        let output = conv2d_bias_relu(
+            &input,
+            &kernels,
+            Some(&bias),
+            false,
+            Some(padding),
+            Some(Stride { x: 2, y: 2 }),
+            Some(6),
+            Some(4),
+            None,
+        )
+        .unwrap();
+        assert_eq!(output.dimensions(), (16, 8, 8));
+        assert_eq!(output.to_buffer_with_order(Order3::HWC), expected);
+    }
+
+    #[test]
+    fn conv2d_bias_relu_matches_dla_py_with_padding_value() {
+        #[rustfmt::skip]
+        let input = Tensor3::from_data_buffer(2, 4, 4, vec![
+            37, -51, 74, -104, -91, -80, 59, -99, -19, -109, -84, 94, 86, -93, -5, -82,
+            89, -98, -65, -14, -97, 75, -103, -15, -105, -60, 20, 86, -55, -68, 29, -36,
+        ], Order3::CHW).unwrap();
+        #[rustfmt::skip]
+        let kernels = Tensor4::from_data_buffer(2, 2, 3, 3, vec![
+            -76, -32, 62, -79, -96, -98, -23, 126, 90, 32, 110, 104, 57, 25, -1, -36, -4, -87,
+            25, 125, 47, 101, 19, -91, -68, 86, -44, 47, -51, 122, 87, -108, -89, 32, 46, 51,
+        ], Order4::KCHW).unwrap();
+        let padding = Padding {
+            top: 1,
+            left: 2,
+            right: 0,
+            bottom: 1,
+            padding_value: -5,
+        };
+        let stride = Stride { x: 1, y: 2 };
+        let bias = [100, -300];
+
+        #[rustfmt::skip]
+        let expected_mac = [
+            -4316, -14781, -21629, -2824, 5885, 10376, -1699, 15651,
+            -1258, -13331, 14250, 29570, 5843, -48268, -8386, -21699,
+        ];
+        let mac = conv2d_mac(
+            &input,
+            &kernels,
+            Some(padding.clone()),
+            Some(stride.clone()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(mac.dimensions(), (2, 2, 4));
+        assert_eq!(mac.to_buffer_with_order(Order3::HWC), expected_mac);
+
+        for (relu, expected) in [
+            (
+                false,
+                [
+                    -22, -128, -128, -60, 58, 43, -1, 84, 2, -128, 123, 127, 58, -128, -54, -128,
+                ],
+            ),
+            (
+                true,
+                [0, 0, 0, 0, 58, 43, 0, 84, 2, 0, 123, 127, 58, 0, 0, 0],
+            ),
+        ] {
+            let output = conv2d_bias_relu(
+                &input,
+                &kernels,
+                Some(&bias),
+                relu,
+                Some(padding.clone()),
+                Some(stride.clone()),
+                Some(4),
+                Some(3),
+                None,
+            )
+            .unwrap();
+            assert_eq!(
+                output.to_buffer_with_order(Order3::HWC),
+                expected,
+                "relu {relu}"
+            );
+        }
+    }
+
+    #[test]
+    fn conv2d_mac_uses_low_bits_in_simd_modes() {
+        // Low nibbles 7 and -8, and 3 and 2; low bit pairs -1 and 0, and -1 and -2
+        let input = Tensor3::from_data_buffer(1, 1, 2, vec![0x17, 0x28], Order3::CHW).unwrap();
+        let kernels =
+            Tensor4::from_data_buffer(1, 1, 1, 2, vec![0x13, 0xF2u8 as i8], Order4::KCHW).unwrap();
+        // Padding value is sign extended the same way, to -2 in both modes
+        let padding = Padding {
+            top: 0,
+            left: 1,
+            right: 0,
+            bottom: 0,
+            padding_value: 0x1E,
+        };
+
+        for (bits, simd_mode, expected) in [
+            (
+                8,
+                SimdBitMode::EightBits,
+                [30 * 19 + 23 * -14, 23 * 19 + 40 * -14],
+            ),
+            (4, SimdBitMode::FourBits, [-2 * 3 + 7 * 2, 7 * 3 + -8 * 2]),
+            (2, SimdBitMode::TwoBits, [-2 * -1 + -1 * -2, -1 * -1]),
+        ] {
+            let output = conv2d_mac(
+                &input,
+                &kernels,
+                Some(padding.clone()),
+                None,
+                Some(simd_mode),
+            )
+            .unwrap();
+            assert_eq!(output.to_buffer(), expected, "{bits} bits");
+        }
+    }
+
+    #[test]
+    fn pp_clip_rounds_with_rounding_bit() {
+        for (value, amount, expected_truncated, expected_rounded) in [
+            (5, 1, 2, 3),
+            (-5, 1, -3, -2),
+            (6, 2, 1, 2),
+            (5, 2, 1, 1),
+            (-6, 2, -2, -1),
+            (7, 0, 7, 7),
+            (i32::MAX, 1, i16::MAX as i32, i16::MAX as i32),
+            (i32::MIN, 1, i16::MIN as i32, i16::MIN as i32),
+        ] {
+            assert_eq!(
+                pp_clip(value, amount, false),
+                expected_truncated,
+                "{value} >> {amount}"
+            );
+            assert_eq!(
+                pp_clip(value, amount, true),
+                expected_rounded,
+                "{value} >> {amount}"
+            );
+        }
+    }
+
+    #[test]
+    fn mac_clip_saturates_to_16_bits() {
+        assert_eq!(mac_clip(100_000, 0), 100_000);
+        assert_eq!(mac_clip(100_000, 1), i16::MAX as i32);
+        assert_eq!(mac_clip(-200_000, 2), i16::MIN as i32);
+        assert_eq!(mac_clip(-7, 1), -4);
+        assert_eq!(saturate_output(200), 127);
+        assert_eq!(saturate_output(-200), -128);
+    }
+}
@@ -0,0 +1,463 @@
+//! Executor for sequential models
+//!
+//! Runs a list of layers back to back. The output of a DLA layer is left in DLA's memory banks
+//! and becomes the input of the following DLA layer without being copied to the heap. Data is
+//! moved to the heap only for layers that run on the CPU and for the final output of the model.
+//!
+//! NOTE: DLA can't read the output of a layer as input as is. It reads inputs with the bytes of
+//! each 64-bit chunk reversed compared to how the post-processor writes outputs, and there is no
+//! register to change either layout. The CPU rearranges the output in place in the banks before the
+//! next layer, which costs a read and a write of the activation over the bus.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! let mut model = Sequential::new();
+//! model.push(Layer::Conv2d(Conv2dLayer::new(kernels_0)));
+//! model.push(Layer::Conv2d(Conv2dLayer::new(kernels_1)));
+//! model.push(Layer::Cpu(Box::new(softmax)));
+//! let output = model.run(input)?;
+//! ```
+use crate::backend::RegisterAccess;
+use crate::bank::BankRange;
+use crate::job::Reservation;
+use crate::layers::{layer_output_dim, run_tiles};
+use crate::tensor3::{Order3, Tensor3};
+use crate::tensor4::{Order4, Tensor4};
+use crate::{
+    BankSelection, Dla, DlaError, InputSize, KernelSize, LayerConfig, OutputWidth, Padding,
+    SimdBitMode, Stride, DEFAULT_HANDSHAKE_TIMEOUT,
+};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// 2D convolution with optional bias and ReLU, calculated on DLA
+pub struct Conv2dLayer {
+    pub kernels: Tensor4<i8>,
+    pub bias: Option<Vec<i16>>,
+    pub relu: bool,
+    pub padding: Option<Padding>,
+    pub stride: Option<Stride>,
+    pub mac_clip: Option<u32>,
+    pub pp_clip: Option<u32>,
+}
+
+impl Conv2dLayer {
+    /// Creates a convolution layer without bias or ReLU using default parameters
+    pub fn new(kernels: Tensor4<i8>) -> Self {
+        Conv2dLayer {
+            kernels,
+            bias: None,
+            relu: false,
+            padding: None,
+            stride: None,
+            mac_clip: None,
+            pp_clip: None,
+        }
+    }
+}
+
+/// Single step of a sequential model
+pub enum Layer {
+    /// Layer calculated on DLA
+    Conv2d(Conv2dLayer),
+    /// Operation calculated on CPU, e.g. requantization or softmax
+    Cpu(Box<dyn Fn(Tensor3<i8>) -> Tensor3<i8>>),
+}
+
+/// Location of the data flowing between layers
+enum Activation {
+    /// Data is on the heap
+    Heap(Tensor3<i8>),
+    /// Data is in DLA's memory banks in HWC order, in the byte order the post-processor writes
+    Banks {
+        banks: BankRange,
+        channels: usize,
+        height: usize,
+        width: usize,
+    },
+}
+
+impl Activation {
+    fn dimensions(&self) -> (usize, usize, usize) {
+        match self {
+            Activation::Heap(tensor) => tensor.dimensions(),
+            Activation::Banks {
+                channels,
+                height,
+                width,
+                ..
+            } => (*channels, *height, *width),
+        }
+    }
+
+    /// Moves data to the heap, reading it from DLA's output banks if needed
+    fn into_heap<B: RegisterAccess>(self, dla: &Dla<B>) -> Result<Tensor3<i8>, DlaError> {
+        match self {
+            Activation::Heap(tensor) => Ok(tensor),
+            Activation::Banks {
+                channels,
+                height,
+                width,
+                ..
+            } => {
+                let output = dla.read_output_i8(channels * height * width)?;
+                Tensor3::from_data_buffer(channels, height, width, output, Order3::HWC)
+                    .map_err(|_| DlaError::DimensionMismatch)
+            }
+        }
+    }
+}
+
+/// Model consisting of layers that are run one after another
+#[derive(Default)]
+pub struct Sequential {
+    layers: Vec<Layer>,
+}
+
+impl Sequential {
+    pub fn new() -> Self {
+        Sequential { layers: Vec::new() }
+    }
+
+    /// Appends layer to the end of the model
+    pub fn push(&mut self, layer: Layer) {
+        self.layers.push(layer)
+    }
+
+    /// Returns the layers of the model
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Runs the model on `input` and returns the output of the last layer
+    ///
+    /// The model is run as a single batch, after which DLA is powered down if automatic power
+    /// down is enabled, see [`Dla::set_auto_power_down`].
+    ///
+    /// # Errors
+    /// - [`DlaError::DimensionMismatch`] if output of a layer does not match input of the next one.
+    /// - [`DlaError::BankOverflow`] if a layer does not fit into DLA's memory banks even in tiles.
+    /// - Any error returned by [`Dla::try_init_layer`] or [`Dla::wait_layer`].
+    pub fn run(&self, input: Tensor3<i8>) -> Result<Tensor3<i8>, DlaError> {
+        self.run_with(&Dla::new(), input)
+    }
+
+    /// Runs the model on `input` with the given DLA, see [`Sequential::run`]
+    pub fn run_with<B: RegisterAccess>(
+        &self,
+        dla: &Dla<B>,
+        input: Tensor3<i8>,
+    ) -> Result<Tensor3<i8>, DlaError> {
+        let output = self.run_layers(dla, input);
+        dla.end_batch();
+        output
+    }
+
+    /// Runs all layers, leaving the output of the last one on heap
+    fn run_layers<B: RegisterAccess>(
+        &self,
+        dla: &Dla<B>,
+        input: Tensor3<i8>,
+    ) -> Result<Tensor3<i8>, DlaError> {
+        let mut activation = Activation::Heap(input);
+        for layer in &self.layers {
+            activation = match layer {
+                Layer::Cpu(op) => Activation::Heap(op(activation.into_heap(dla)?)),
+                Layer::Conv2d(conv) => run_conv2d(dla, conv, activation)?,
+            };
+        }
+        activation.into_heap(dla)
+    }
+}
+
+/// Runs a convolution layer on DLA and leaves its output in DLA's memory banks
+///
+/// Layers that don't fit into the banks next to their input are run in tiles with
+/// [`run_tiles`], leaving their output on heap.
+fn run_conv2d<B: RegisterAccess>(
+    dla: &Dla<B>,
+    layer: &Conv2dLayer,
+    input: Activation,
+) -> Result<Activation, DlaError> {
+    let (channels, height, width) = input.dimensions();
+    let kernels = &layer.kernels;
+    if let Some(bias) = &layer.bias {
+        if bias.len() != kernels.kernels() {
+            return Err(DlaError::DimensionMismatch);
+        }
+    }
+    let (output_width, output_height) = layer_output_dim(
+        input.dimensions(),
+        kernels.dimensions(),
+        layer.padding.as_ref(),
+        layer.stride.as_ref(),
+    )?;
+    let input_len = channels * height * width;
+    let output_len = kernels.kernels() * output_height * output_width;
+
+    // Keep the input where the previous layer left it and fit everything else around it
+    let banks = match allocate_banks(layer, &input, input_len, output_len) {
+        Ok(banks) => banks,
+        Err(DlaError::BankOverflow) => {
+            let input = input.into_heap(dla)?;
+            return run_tiles::<i8, i8, _>(
+                dla,
+                input.view(),
+                kernels.view(),
+                layer.bias.clone(),
+                layer.bias.is_some(),
+                layer.relu,
+                layer.padding.clone(),
+                layer.stride.clone(),
+                layer.mac_clip,
+                layer.pp_clip,
+                Some(SimdBitMode::EightBits),
+            )
+            .map(Activation::Heap);
+        }
+        Err(err) => return Err(err),
+    };
+    let (input, input_banks) = match input {
+        Activation::Banks { banks, .. } => (None, banks),
+        Activation::Heap(tensor) => (Some(tensor), banks.input.ok_or(DlaError::BankOverflow)?),
+    };
+    let (kernel_banks, output_banks, bias_banks) = (banks.kernels, banks.output, banks.bias);
+    let _reservation = Reservation::acquire()?;
+
+    let bias_enabled = layer.bias.is_some();
+    let (mac_sat_min, mac_sat_max) = dla.layer_mac_saturation();
+    let config = LayerConfig {
+        input_bank: BankSelection::Bank(input_banks.start()),
+        kernel_bank: BankSelection::Bank(kernel_banks.start()),
+        output_bank: BankSelection::Bank(output_banks.start()),
+        bias_addr: bias_banks.as_ref().map(|banks| banks.addr() as u32),
+        pp_enabled: layer.relu || bias_enabled,
+        relu_enabled: layer.relu,
+        bias_enabled,
+        input_size: Some(InputSize {
+            channels: channels as u32,
+            width: width as u32,
+            height: height as u32,
+        }),
+        kernel_size: Some(KernelSize {
+            s_channels: 1,
+            kernels: kernels.kernels() as u32,
+            width: kernels.width() as u32,
+            height: kernels.height() as u32,
+        }),
+        padding: layer.padding.clone(),
+        stride: layer.stride.clone(),
+        mac_clip: layer.mac_clip,
+        pp_clip: layer.pp_clip,
+        mac_sat_max: Some(mac_sat_max),
+        mac_sat_min: Some(mac_sat_min),
+        pp_rounding: dla.layer_pp_rounding(),
+        simd_mode: Some(SimdBitMode::EightBits),
+        pooling: None,
+        output_width: Some(OutputWidth::EightBits),
+    };
+    dla.try_init_layer(config)?;
+
+    match input {
+        Some(tensor) => dla.write_input(&mut tensor.to_buffer_with_order(Order3::HWC))?,
+        // Previous output is rearranged in place by the CPU, see the module documentation
+        None => dla.reorder_output_for_input(input_banks.start(), input_len),
+    }
+    dla.write_kernel(&mut kernels.to_buffer_with_order(Order4::HWKC))?;
+    if let Some(bias) = &layer.bias {
+        dla.write_bias(bias);
+    }
+
+    // Mark data ready to start calculations
+    dla.kernel_data_ready(true);
+    dla.input_data_ready(true);
+    dla.wait_layer(DEFAULT_HANDSHAKE_TIMEOUT)?;
+
+    Ok(Activation::Banks {
+        banks: output_banks,
+        channels: kernels.kernels(),
+        height: output_height,
+        width: output_width,
+    })
+}
+
+/// Banks allocated for a layer whose input may already be in the banks
+struct LayerBanks {
+    /// `None` if the input is left in the banks by the previous layer
+    input: Option<BankRange>,
+    kernels: BankRange,
+    output: BankRange,
+    bias: Option<BankRange>,
+}
+
+/// Allocates banks for the data of a convolution layer that aren't allocated yet
+fn allocate_banks(
+    layer: &Conv2dLayer,
+    input: &Activation,
+    input_len: usize,
+    output_len: usize,
+) -> Result<LayerBanks, DlaError> {
+    let input = match input {
+        Activation::Banks { .. } => None,
+        Activation::Heap(_) => Some(BankRange::allocate(input_len)?),
+    };
+    Ok(LayerBanks {
+        input,
+        kernels: BankRange::allocate(layer.kernels.get_size())?,
+        output: BankRange::allocate(output_len)?,
+        bias: match &layer.bias {
+            Some(bias) => Some(BankRange::allocate(bias.len() * 2)?),
+            None => None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference;
+    use crate::test_utils::{self, assert_tensor_eq, rng, simulated};
+
+    #[test]
+    fn model_matches_reference() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 3, 9, 11);
+        let first = Conv2dLayer {
+            bias: Some(test_utils::values(&mut rng, 5, -500..=500)),
+            relu: true,
+            padding: Some(Padding {
+                top: 1,
+                right: 1,
+                left: 1,
+                bottom: 1,
+                padding_value: 0,
+            }),
+            pp_clip: Some(4),
+            ..Conv2dLayer::new(test_utils::tensor4(&mut rng, 5, 3, 3, 3))
+        };
+        let second = Conv2dLayer {
+            stride: Some(Stride { x: 2, y: 1 }),
+            pp_clip: Some(5),
+            ..Conv2dLayer::new(test_utils::tensor4(&mut rng, 4, 5, 2, 3))
+        };
+        let third = Conv2dLayer::new(test_utils::tensor4(&mut rng, 2, 4, 1, 1));
+        let negate = |tensor: Tensor3<i8>| tensor.map(|x| x.saturating_neg());
+
+        let run_reference = |layer: &Conv2dLayer, input: &Tensor3<i8>| {
+            reference::conv2d_bias_relu(
+                input,
+                &layer.kernels,
+                layer.bias.as_deref(),
+                layer.relu,
+                layer.padding.clone(),
+                layer.stride.clone(),
+                layer.mac_clip,
+                layer.pp_clip,
+                None,
+            )
+            .unwrap()
+        };
+        let expected = run_reference(&first, &input);
+        let expected = run_reference(&second, &expected);
+        let expected = run_reference(&third, &negate(expected));
+
+        let mut model = Sequential::new();
+        model.push(Layer::Conv2d(first));
+        model.push(Layer::Conv2d(second));
+        model.push(Layer::Cpu(Box::new(negate)));
+        model.push(Layer::Conv2d(third));
+        assert_tensor_eq(&model.run_with(&dla, input).unwrap(), &expected);
+    }
+
+    #[test]
+    fn layer_that_does_not_fit_next_to_its_input_is_tiled() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        // Output of the first layer takes 9 banks, so the second one can't be placed next to it
+        let input = test_utils::tensor3(&mut rng, 16, 96, 96);
+        let first = Conv2dLayer::new(test_utils::tensor4(&mut rng, 32, 16, 1, 1));
+        let second = Conv2dLayer {
+            relu: true,
+            ..Conv2dLayer::new(test_utils::tensor4(&mut rng, 32, 32, 1, 1))
+        };
+        let run_reference = |layer: &Conv2dLayer, input: &Tensor3<i8>| {
+            reference::conv2d_bias_relu(
+                input,
+                &layer.kernels,
+                None,
+                layer.relu,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let expected = run_reference(&second, &run_reference(&first, &input));
+
+        let mut model = Sequential::new();
+        model.push(Layer::Conv2d(first));
+        model.push(Layer::Conv2d(second));
+        assert_tensor_eq(&model.run_with(&dla, input).unwrap(), &expected);
+        assert_eq!(crate::bank::free_banks(), crate::bank::NUM_BANKS);
+    }
+
+    #[test]
+    fn auto_power_down_only_after_model() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = test_utils::tensor3(&mut rng, 4, 6, 5);
+        let kernels = test_utils::tensor4(&mut rng, 4, 2, 2, 2);
+        let conv = Conv2dLayer::new(test_utils::tensor4(&mut rng, 3, 4, 2, 2));
+        let expected = reference::conv2d_bias_relu(
+            &input,
+            &conv.kernels,
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let mut model = Sequential::new();
+        model.push(Layer::Conv2d(conv));
+        dla.set_auto_power_down(true);
+
+        // Groups are run one after another without powering DLA down in between
+        let _: Tensor3<i8> = crate::layers::try_grouped_conv2d(
+            &dla,
+            input.clone(),
+            kernels,
+            vec![0; 4],
+            None,
+            None,
+            None,
+            None,
+            None,
+            2,
+        )
+        .unwrap();
+        assert!(!dla.is_powered_down());
+
+        assert_tensor_eq(&model.run_with(&dla, input.clone()).unwrap(), &expected);
+        assert!(dla.is_powered_down());
+
+        // Failing model is powered down as well and its error is kept
+        let mismatch = test_utils::tensor3(&mut rng, 2, 6, 5);
+        dla.power_up(1).unwrap();
+        assert_eq!(
+            model.run_with(&dla, mismatch).unwrap_err(),
+            DlaError::DimensionMismatch
+        );
+        assert!(dla.is_powered_down());
+
+        // Next run powers DLA up again
+        assert_tensor_eq(&model.run_with(&dla, input).unwrap(), &expected);
+        dla.set_auto_power_down(false);
+    }
+}
@@ -0,0 +1,229 @@
+//! Packed low-precision values for DLA's 4-bit and 2-bit SIMD modes
+//!
+//! In SIMD modes DLA reads several values from each byte of its memory banks. [`I4`] and [`I2`]
+//! are used as tensor elements, e.g. `Tensor3<I4>`, and [`pack`] / [`unpack`] convert them to and
+//! from the bytes written to the banks. DLA addresses its data per pixel, so the channels of each
+//! pixel start from a new byte.
+//!
+//! NOTE: Values are packed most significant bits first, as `write_output` in
+//! `vp/devel/python_peripherals/DLA.py` packs 4-bit and 2-bit outputs, i.e. the first value of a
+//! byte is in its high nibble. There is no RTL reference for the input side. The VP doesn't
+//! calculate in SIMD modes, so low-precision layers are run with unpacked 8-bit values there, see
+//! [`crate::layers::try_conv2d_packed`]. [`Simulated`](crate::backend::Simulated) reads packed
+//! values once enabled with [`set_simd`](crate::backend::Simulated::set_simd).
+use crate::SimdBitMode;
+use alloc::vec::Vec;
+
+/// Element type DLA can read from its memory banks
+pub trait Packed: Copy + Clone + Default {
+    /// Width of a single value in bits
+    const BITS: u32;
+    /// SIMD mode DLA calculates the values in
+    const SIMD_MODE: SimdBitMode;
+
+    /// Returns the value as `i8`
+    fn value(self) -> i8;
+    /// Creates a value from the low [`BITS`](Packed::BITS) bits of `bits`
+    fn from_bits(bits: u8) -> Self;
+
+    /// Returns the value as [`BITS`](Packed::BITS) bits in two's complement
+    fn to_bits(self) -> u8 {
+        self.value() as u8 & (0xFF >> (8 - Self::BITS))
+    }
+}
+
+/// Signed 4-bit integer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct I4(i8);
+
+/// Signed 2-bit integer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct I2(i8);
+
+impl I4 {
+    pub const MIN: I4 = I4(-8);
+    pub const MAX: I4 = I4(7);
+
+    /// Creates a value, `None` if it doesn't fit into 4 bits
+    pub fn new(value: i8) -> Option<I4> {
+        (Self::MIN.0..=Self::MAX.0)
+            .contains(&value)
+            .then_some(I4(value))
+    }
+
+    /// Creates a value, saturating it to 4 bits
+    pub fn saturating(value: i8) -> I4 {
+        I4(value.clamp(Self::MIN.0, Self::MAX.0))
+    }
+}
+
+impl I2 {
+    pub const MIN: I2 = I2(-2);
+    pub const MAX: I2 = I2(1);
+
+    /// Creates a value, `None` if it doesn't fit into 2 bits
+    pub fn new(value: i8) -> Option<I2> {
+        (Self::MIN.0..=Self::MAX.0)
+            .contains(&value)
+            .then_some(I2(value))
+    }
+
+    /// Creates a value, saturating it to 2 bits
+    pub fn saturating(value: i8) -> I2 {
+        I2(value.clamp(Self::MIN.0, Self::MAX.0))
+    }
+}
+
+impl Packed for i8 {
+    const BITS: u32 = 8;
+    const SIMD_MODE: SimdBitMode = SimdBitMode::EightBits;
+
+    fn value(self) -> i8 {
+        self
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        bits as i8
+    }
+}
+
+impl Packed for I4 {
+    const BITS: u32 = 4;
+    const SIMD_MODE: SimdBitMode = SimdBitMode::FourBits;
+
+    fn value(self) -> i8 {
+        self.0
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        // Sign-extend from the 4th bit
+        I4(((bits << 4) as i8) >> 4)
+    }
+}
+
+impl Packed for I2 {
+    const BITS: u32 = 2;
+    const SIMD_MODE: SimdBitMode = SimdBitMode::TwoBits;
+
+    fn value(self) -> i8 {
+        self.0
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        // Sign-extend from the 2nd bit
+        I2(((bits << 6) as i8) >> 6)
+    }
+}
+
+/// Number of bytes `pixels` pixels of `channels` values of `T` take when packed
+pub fn packed_len<T: Packed>(pixels: usize, channels: usize) -> usize {
+    pixels * (channels * T::BITS as usize).div_ceil(8)
+}
+
+/// Packs values of pixels with `channels` values each into bytes, first value into the most
+/// significant bits
+///
+/// Each pixel starts from a new byte, unused bits of its last byte are zero.
+pub fn pack<T: Packed>(values: &[T], channels: usize) -> Vec<u8> {
+    pack_iter(values.iter().copied(), channels).collect()
+}
+
+/// Packs values into bytes as they are read, see [`pack`]
+pub fn pack_iter<T: Packed>(
+    values: impl IntoIterator<Item = T>,
+    channels: usize,
+) -> impl Iterator<Item = u8> {
+    let per_byte = (8 / T::BITS) as usize;
+    // Values can't be split into empty pixels
+    let channels = channels.max(1);
+    let mut values = values.into_iter().peekable();
+    // Values left of the current pixel
+    let mut left = channels;
+    core::iter::from_fn(move || {
+        values.peek()?;
+        let take = per_byte.min(left);
+        left -= take;
+        if left == 0 {
+            left = channels;
+        }
+        Some(
+            values
+                .by_ref()
+                .take(take)
+                .enumerate()
+                .fold(0u8, |byte, (i, value)| {
+                    byte | value.to_bits() << (8 - T::BITS as usize * (i + 1))
+                }),
+        )
+    })
+}
+
+/// Unpacks `len` values of pixels with `channels` values each from bytes packed with [`pack`]
+///
+/// Returns `None` if `len` isn't a whole number of pixels or `bytes` is too short to contain
+/// them.
+pub fn unpack<T: Packed>(bytes: &[u8], len: usize, channels: usize) -> Option<Vec<T>> {
+    let pixels = len.checked_div(channels)?;
+    if pixels * channels != len || bytes.len() < packed_len::<T>(pixels, channels) {
+        return None;
+    }
+    let per_byte = (8 / T::BITS) as usize;
+    let pixel_len = packed_len::<T>(1, channels);
+    Some(
+        (0..len)
+            .map(|i| {
+                let (pixel, channel) = (i / channels, i % channels);
+                let shift = 8 - T::BITS as usize * (channel % per_byte + 1);
+                T::from_bits(bytes[pixel * pixel_len + channel / per_byte] >> shift)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn i4s(values: &[i8]) -> Vec<I4> {
+        values.iter().map(|&x| I4::new(x).unwrap()).collect()
+    }
+
+    #[test]
+    fn pixels_start_from_new_byte() {
+        // Two pixels of three channels, first value of a byte in its high nibble
+        let values = i4s(&[1, -1, 2, 3, -8, 7]);
+        assert_eq!(pack(&values, 3), vec![0x1F, 0x20, 0x38, 0x70]);
+        assert_eq!(packed_len::<I4>(2, 3), 4);
+
+        let values: Vec<I2> = [1, -2, -1, 0, 1]
+            .iter()
+            .map(|&x| I2::new(x).unwrap())
+            .collect();
+        assert_eq!(pack(&values, 5), vec![0b0110_1100, 0b0100_0000]);
+        assert_eq!(pack(&[7i8, -1], 1), vec![7, 0xFF]);
+    }
+
+    #[test]
+    fn unpack_reverses_pack() {
+        let values: Vec<i8> = (0..7 * 5).map(|i| (i % 16 - 8) as i8).collect();
+        for channels in [1, 2, 3, 5, 7] {
+            let len = values.len() / channels * channels;
+            let i4 = i4s(&values[..len]);
+            assert_eq!(unpack(&pack(&i4, channels), len, channels), Some(i4));
+
+            let i2: Vec<I2> = values[..len].iter().map(|&x| I2::saturating(x)).collect();
+            assert_eq!(unpack(&pack(&i2, channels), len, channels), Some(i2));
+
+            let i8 = values[..len].to_vec();
+            assert_eq!(unpack(&pack(&i8, channels), len, channels), Some(i8));
+        }
+    }
+
+    #[test]
+    fn unpack_rejects_partial_pixels_and_short_input() {
+        let bytes = pack(&i4s(&[1, 2, 3, 4, 5, 6]), 3);
+        assert_eq!(unpack::<I4>(&bytes, 5, 3), None);
+        assert_eq!(unpack::<I4>(&bytes[..3], 6, 3), None);
+        assert_eq!(unpack::<I4>(&bytes, 6, 0), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Performance statistics of DLA layers
+//!
+//! Combines DLA's buffer stall counters with the HPC cycle counter and CLINT's `mtime`. The latter
+//! two read as zero on host.
+//! Statistics of a single submitted layer are available from [`DlaJob::stats`], and any code
+//! running layers on a driver, e.g. the `try_*` functions in [`layers`](crate::layers), can be
+//! measured with [`with_stats`].
+//!
+//! # Examples
+//!
+//! ```ignore
+//! let dla = Dla::new();
+//! let (output, stats) = with_stats(&dla, |dla| {
+//!     try_conv2d::<i8, _>(dla, input, kernels, None, None, None, None, None, None)
+//! });
+//! sprintln!("{} cycles, {} pipeline stalls", stats.cycles, stats.buf_pipe_stall);
+//! ```
+//!
+//! [`DlaJob::stats`]: crate::job::DlaJob::stats
+use crate::backend::RegisterAccess;
+use crate::mmap::*;
+use crate::Dla;
+use core::ops::{Add, AddAssign};
+#[cfg(target_arch = "riscv64")]
+use headsail_bsp::{riscv, CLINT};
+
+/// Counters and timers collected over one or more layers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LayerStats {
+    /// Cycles the buffer waited for data on port A (`BUF_DATA_WAIT_A`)
+    pub buf_data_wait_a: u64,
+    /// Cycles the buffer waited for data on port B (`BUF_DATA_WAIT_B`)
+    pub buf_data_wait_b: u64,
+    /// Cycles the buffer pipeline stalled (`BUF_PIPE_STALL`)
+    pub buf_pipe_stall: u64,
+    /// Elapsed `mcycle` of the measuring hart
+    pub cycles: u64,
+    /// Elapsed CLINT `mtime` ticks
+    pub mtime: u64,
+}
+
+impl Add for LayerStats {
+    type Output = LayerStats;
+
+    fn add(self, other: LayerStats) -> LayerStats {
+        LayerStats {
+            buf_data_wait_a: self.buf_data_wait_a + other.buf_data_wait_a,
+            buf_data_wait_b: self.buf_data_wait_b + other.buf_data_wait_b,
+            buf_pipe_stall: self.buf_pipe_stall + other.buf_pipe_stall,
+            cycles: self.cycles + other.cycles,
+            mtime: self.mtime + other.mtime,
+        }
+    }
+}
+
+impl AddAssign for LayerStats {
+    fn add_assign(&mut self, other: LayerStats) {
+        *self = *self + other
+    }
+}
+
+/// Counter and timer values at one point in time
+///
+/// NOTE: DLA's stall counters are treated as free running 32-bit counters, so that statistics are
+/// calculated as wrapping differences of two snapshots. VP does not model the counters and reports
+/// them as zero.
+#[derive(Clone, Copy, Debug)]
+pub struct StatsSnapshot {
+    buf_data_wait_a: u32,
+    buf_data_wait_b: u32,
+    buf_pipe_stall: u32,
+    cycles: u64,
+    mtime: u64,
+}
+
+impl StatsSnapshot {
+    /// Returns statistics accumulated since this snapshot was taken
+    pub fn elapsed<B: RegisterAccess>(&self, dla: &Dla<B>) -> LayerStats {
+        let now = dla.stats_snapshot();
+        LayerStats {
+            buf_data_wait_a: now.buf_data_wait_a.wrapping_sub(self.buf_data_wait_a) as u64,
+            buf_data_wait_b: now.buf_data_wait_b.wrapping_sub(self.buf_data_wait_b) as u64,
+            buf_pipe_stall: now.buf_pipe_stall.wrapping_sub(self.buf_pipe_stall) as u64,
+            cycles: now.cycles.wrapping_sub(self.cycles),
+            mtime: now.mtime.wrapping_sub(self.mtime),
+        }
+    }
+}
+
+impl<B: RegisterAccess> Dla<B> {
+    /// Reads DLA's stall counters together with `mcycle` and `mtime`
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            buf_data_wait_a: get_bits!(
+                self.read_u32(DLA_BUF_DATA_WAIT_A),
+                DLA_BUF_DATA_WAIT_A_BITMASK
+            ),
+            buf_data_wait_b: get_bits!(
+                self.read_u32(DLA_BUF_DATA_WAIT_B),
+                DLA_BUF_DATA_WAIT_B_BITMASK
+            ),
+            buf_pipe_stall: get_bits!(
+                self.read_u32(DLA_BUF_PIPE_STALL_STALL_CYCLES),
+                DLA_BUF_PIPE_STALL_STALL_CYCLES_BITMASK
+            ),
+            cycles: read_cycles(),
+            mtime: read_mtime(),
+        }
+    }
+}
+
+/// Reads `mcycle` of the current hart
+#[cfg(target_arch = "riscv64")]
+fn read_cycles() -> u64 {
+    riscv::register::mcycle::read64()
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn read_cycles() -> u64 {
+    0
+}
+
+/// Reads CLINT's `mtime`
+#[cfg(target_arch = "riscv64")]
+fn read_mtime() -> u64 {
+    CLINT::mtime().read()
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn read_mtime() -> u64 {
+    0
+}
+
+/// Runs `f` on `dla` and returns its result together with the statistics of everything DLA did
+/// meanwhile
+pub fn with_stats<B: RegisterAccess, R>(
+    dla: &Dla<B>,
+    f: impl FnOnce(&Dla<B>) -> R,
+) -> (R, LayerStats) {
+    let start = dla.stats_snapshot();
+    let result = f(dla);
+    (result, start.elapsed(dla))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::try_conv2d;
+    use crate::test_utils::{rng, simulated, tensor3, tensor4};
+
+    #[test]
+    fn elapsed_counts_across_counter_wrap_around() {
+        let (dla, _lock) = simulated();
+        let mut rng = rng();
+        let input = tensor3(&mut rng, 2, 6, 6);
+        let kernels = tensor4(&mut rng, 2, 2, 3, 3);
+        let backend = dla.backend();
+        backend.write_reg(DLA_BUF_DATA_WAIT_A, u32::MAX - 2);
+        backend.write_reg(DLA_BUF_DATA_WAIT_B, 7);
+        backend.write_reg(DLA_BUF_PIPE_STALL_STALL_CYCLES, u32::MAX);
+
+        let (output, stats) = with_stats(&dla, |dla| {
+            let output =
+                try_conv2d::<i8, _>(dla, input, kernels, None, None, None, None, None, None);
+            // Simulated doesn't count stalls, so advance the counters by hand
+            let backend = dla.backend();
+            backend.write_reg(DLA_BUF_DATA_WAIT_A, 10);
+            backend.write_reg(DLA_BUF_DATA_WAIT_B, 12);
+            backend.write_reg(DLA_BUF_PIPE_STALL_STALL_CYCLES, 0);
+            output
+        });
+
+        assert!(output.is_ok());
+        assert_eq!(
+            stats,
+            LayerStats {
+                buf_data_wait_a: 13,
+                buf_data_wait_b: 5,
+                buf_pipe_stall: 1,
+                cycles: 0,
+                mtime: 0,
+            }
+        );
+    }
+}
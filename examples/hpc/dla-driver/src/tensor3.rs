@@ -1,6 +1,6 @@
 use alloc::vec::*;
 use core::ffi::c_char;
-use ndarray::{s, Array, Array3};
+use ndarray::{s, Array, Array3, ArrayView, ArrayView3, Axis, Slice};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Order3 {
@@ -23,6 +23,21 @@ impl Order3 {
             Order3::WCH => [2, 0, 1],
         }
     }
+
+    /// Returns the axis of a dimension, 0 for channels, 1 for height and 2 for width
+    fn axis(self, dimension: usize) -> usize {
+        unsafe {
+            self.into_position()
+                .iter()
+                .position(|&r| r == dimension)
+                .unwrap_unchecked()
+        }
+    }
+
+    /// Returns the axes that rearrange an array in this order into `order`
+    fn permutation_to(self, order: Order3) -> [usize; 3] {
+        order.into_position().map(|dimension| self.axis(dimension))
+    }
 }
 
 impl TryFrom<&str> for Order3 {
@@ -214,25 +229,47 @@ impl<T: Clone> Tensor3<T> {
 
     /// Slice tensors channel axis with the given range
     pub fn slice_channels(&self, c_range: core::ops::Range<usize>) -> Tensor3<T> {
-        let channel_axis = match self.order {
-            Order3::CHW | Order3::CWH => 0,
-            Order3::HCW | Order3::WCH => 1,
-            Order3::HWC | Order3::WHC => 2,
-        };
+        self.view().slice_channels(c_range).to_owned()
+    }
 
-        let sliced_data = match channel_axis {
-            0 => self.data.slice(s![c_range, .., ..]).to_owned(),
-            1 => self.data.slice(s![.., c_range, ..]).to_owned(),
-            2 => self.data.slice(s![.., .., c_range]).to_owned(),
-            _ => unreachable!(),
-        };
+    /// Slice tensors height and width axes with the given ranges
+    pub fn slice_spatial(
+        &self,
+        h_range: core::ops::Range<usize>,
+        w_range: core::ops::Range<usize>,
+    ) -> Tensor3<T> {
+        self.view().slice_spatial(h_range, w_range).to_owned()
+    }
 
+    /// Applies `f` to every element, keeping dimensions and order
+    pub fn map<U: Clone>(&self, f: impl FnMut(&T) -> U) -> Tensor3<U> {
         Tensor3 {
-            data: sliced_data,
+            data: self.data.map(f),
             order: self.order,
         }
     }
 
+    /// Combines the elements at the same positions of two tensors, keeping the order of `self`
+    ///
+    /// Returns `None` if the dimensions of the tensors differ.
+    pub fn zip_map<U: Clone, V: Clone>(
+        &self,
+        other: &Tensor3<U>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> Option<Tensor3<V>> {
+        if self.dimensions() != other.dimensions() {
+            return None;
+        }
+        let data = self
+            .data
+            .iter()
+            .zip(other.view().iter_with_order(self.order))
+            .map(|(a, b)| f(a, b))
+            .collect();
+        let (channels, height, width) = self.dimensions();
+        Tensor3::from_data_buffer(channels, height, width, data, self.order).ok()
+    }
+
     /// Sets a new order for the array
     pub fn permute(&mut self, order: Order3) {
         // Early return if already in order
@@ -240,21 +277,10 @@ impl<T: Clone> Tensor3<T> {
             return;
         }
 
-        if self.order == Order3::CHW {
-            // Transmute to target order
-            let new_order: [usize; 3] = order.into_position();
-            self.data = self.data.clone().permuted_axes(new_order);
-            self.order = order;
-            return;
-        }
-
-        // Transmute to standard order
-        let std_order: [usize; 3] = self.order.into_position();
-        let std = self.data.clone().permuted_axes(std_order);
-
-        // Transmute to target order
-        let new_order: [usize; 3] = order.into_position();
-        self.data = std.permuted_axes(new_order);
+        self.data = self
+            .data
+            .clone()
+            .permuted_axes(self.order.permutation_to(order));
         self.order = order;
     }
 
@@ -274,14 +300,131 @@ impl<T: Clone> Tensor3<T> {
 
     /// Converts the 3D array to a linear buffer according to the specified order
     pub fn to_buffer_with_order(&self, order: Order3) -> Vec<T> {
-        // If order is correct no need to permute
-        if order == self.order {
-            return self.to_buffer();
+        self.view().to_buffer_with_order(order)
+    }
+}
+
+impl<T> Tensor3<T> {
+    /// Borrows the tensor without copying its data
+    pub fn view(&self) -> Tensor3View<'_, T> {
+        Tensor3View {
+            data: self.data.view(),
+            order: self.order,
         }
+    }
+}
 
-        let mut data = self.clone();
-        data.permute(order);
-        data.to_buffer()
+impl<'a, T> From<&'a Tensor3<T>> for Tensor3View<'a, T> {
+    fn from(tensor: &'a Tensor3<T>) -> Self {
+        tensor.view()
+    }
+}
+
+/// Borrowed 3D tensor, e.g. a part of a [`Tensor3`] or a feature map in the caller's buffer
+///
+/// Slicing a view doesn't copy data, and the elements can be read in any order straight from the
+/// borrowed memory.
+#[derive(Clone, Copy, Debug)]
+pub struct Tensor3View<'a, T> {
+    data: ArrayView3<'a, T>,
+    order: Order3,
+}
+
+impl<'a, T> Tensor3View<'a, T> {
+    /// Creates a view to a data buffer with the specified order
+    pub fn from_slice(
+        channels: usize,
+        height: usize,
+        width: usize,
+        data_buffer: &'a [T],
+        order: Order3,
+    ) -> Result<Self, &'static str> {
+        if data_buffer.len() != channels * height * width {
+            return Err("Data buffer size does not match specified dimensions");
+        }
+
+        let standard_shape = [channels, height, width];
+        let shape = order
+            .into_position()
+            .map(|dimension| standard_shape[dimension]);
+        let data = ArrayView::from_shape(shape, data_buffer)
+            .map_err(|_| "Failed to create array from data buffer")?;
+
+        Ok(Tensor3View { data, order })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(0)]
+    }
+
+    pub fn height(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(1)]
+    }
+
+    pub fn width(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(2)]
+    }
+
+    /// Returns the dimensions of the view
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        (self.channels(), self.height(), self.width())
+    }
+
+    /// Gets the order of the underlying data
+    pub fn order(&self) -> Order3 {
+        self.order
+    }
+
+    /// Get the number of elements in view
+    pub fn get_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Narrows the view to the given range of channels
+    pub fn slice_channels(&self, c_range: core::ops::Range<usize>) -> Tensor3View<'a, T> {
+        let mut data = self.data;
+        data.slice_axis_inplace(Axis(self.order.axis(0)), Slice::from(c_range));
+        Tensor3View {
+            data,
+            order: self.order,
+        }
+    }
+
+    /// Narrows the view to the given ranges of height and width
+    pub fn slice_spatial(
+        &self,
+        h_range: core::ops::Range<usize>,
+        w_range: core::ops::Range<usize>,
+    ) -> Tensor3View<'a, T> {
+        let mut data = self.data;
+        data.slice_axis_inplace(Axis(self.order.axis(1)), Slice::from(h_range));
+        data.slice_axis_inplace(Axis(self.order.axis(2)), Slice::from(w_range));
+        Tensor3View {
+            data,
+            order: self.order,
+        }
+    }
+
+    /// Iterates over the elements in the specified order, without copying them
+    pub fn iter_with_order(&self, order: Order3) -> impl Iterator<Item = &'a T> {
+        self.data
+            .permuted_axes(self.order.permutation_to(order))
+            .into_iter()
+    }
+}
+
+impl<T: Clone> Tensor3View<'_, T> {
+    /// Copies the viewed elements to a linear buffer according to the specified order
+    pub fn to_buffer_with_order(&self, order: Order3) -> Vec<T> {
+        self.iter_with_order(order).cloned().collect()
+    }
+
+    /// Copies the viewed elements to a new tensor with the same order
+    pub fn to_owned(&self) -> Tensor3<T> {
+        Tensor3 {
+            data: self.data.to_owned(),
+            order: self.order,
+        }
     }
 }
 
@@ -315,3 +458,29 @@ pub fn rescale(
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ORDER3;
+
+    #[test]
+    fn permute_between_any_orders() {
+        let data: Vec<i32> = (0..3 * 4 * 5).collect();
+        let tensor = Tensor3::from_data_buffer(3, 4, 5, data, Order3::CHW).unwrap();
+
+        for from in ORDER3 {
+            for to in ORDER3 {
+                let mut permuted = tensor.clone();
+                permuted.permute(from);
+                permuted.permute(to);
+                assert_eq!(permuted.order(), to);
+                assert_eq!(
+                    permuted.to_buffer(),
+                    tensor.to_buffer_with_order(to),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use alloc::vec::*;
 use core::ffi::c_char;
-use ndarray::{s, Array, Array4};
+use ndarray::{Array, Array4, ArrayView, ArrayView4, Axis, Slice};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Order4 {
@@ -59,6 +59,22 @@ impl Order4 {
             Order4::WHKC => [3, 2, 1, 0],
         }
     }
+
+    /// Returns the axis of a dimension, 0 for kernels, 1 for channels, 2 for height and 3 for
+    /// width
+    fn axis(self, dimension: usize) -> usize {
+        unsafe {
+            self.into_position()
+                .iter()
+                .position(|&r| r == dimension)
+                .unwrap_unchecked()
+        }
+    }
+
+    /// Returns the axes that rearrange an array in this order into `order`
+    fn permutation_to(self, order: Order4) -> [usize; 4] {
+        order.into_position().map(|dimension| self.axis(dimension))
+    }
 }
 
 impl TryFrom<&str> for Order4 {
@@ -276,51 +292,15 @@ impl<T: Clone> Tensor4<T> {
         (self.kernels(), self.channels(), self.height(), self.width())
     }
 
+    /// Slice tensors kernel axis with the given range
     pub fn slice_channels(&self, c_range: core::ops::Range<usize>) -> Tensor4<T> {
-        // Determine the index of the channel dimension based on the tensor order
-        let kernel_axis = match self.order {
-            Order4::KCHW
-            | Order4::KCWH
-            | Order4::KHWC
-            | Order4::KHCW
-            | Order4::KWCH
-            | Order4::KWHC => 0,
-
-            Order4::CKHW
-            | Order4::CKWH
-            | Order4::HKCW
-            | Order4::HKWC
-            | Order4::WKCH
-            | Order4::WKHC => 1,
-
-            Order4::CHKW
-            | Order4::CWKH
-            | Order4::HCKW
-            | Order4::HWKC
-            | Order4::WCKH
-            | Order4::WHKC => 2,
-
-            Order4::CHWK
-            | Order4::CWHK
-            | Order4::HWCK
-            | Order4::HCWK
-            | Order4::WCHK
-            | Order4::WHCK => 3,
-        };
-
-        // Create a slice pattern for `s![]` by slicing only on the channels axis
-        // while keeping all other axes intact with `..`.
-        let sliced_data = match kernel_axis {
-            0 => self.data.slice(s![c_range, .., .., ..]).to_owned(),
-            1 => self.data.slice(s![.., c_range, .., ..]).to_owned(),
-            2 => self.data.slice(s![.., .., c_range, ..]).to_owned(),
-            3 => self.data.slice(s![.., .., .., c_range]).to_owned(),
-            _ => unreachable!(),
-        };
+        self.view().slice_kernels(c_range).to_owned()
+    }
 
-        // Return a new Tensor4 with the sliced data and the same order
+    /// Applies `f` to every element, keeping dimensions and order
+    pub fn map<U: Clone>(&self, f: impl FnMut(&T) -> U) -> Tensor4<U> {
         Tensor4 {
-            data: sliced_data,
+            data: self.data.map(f),
             order: self.order,
         }
     }
@@ -332,21 +312,10 @@ impl<T: Clone> Tensor4<T> {
             return;
         }
 
-        if self.order == Order4::KCHW {
-            // Transmute to target order
-            let new_order: [usize; 4] = order.into_position();
-            self.data = self.data.clone().permuted_axes(new_order);
-            self.order = order;
-            return;
-        }
-
-        // Transmute to standard order
-        let std_order: [usize; 4] = self.order.into_position();
-        let std = self.data.clone().permuted_axes(std_order);
-
-        // Transmute to target order
-        let new_order: [usize; 4] = order.into_position();
-        self.data = std.permuted_axes(new_order);
+        self.data = self
+            .data
+            .clone()
+            .permuted_axes(self.order.permutation_to(order));
         self.order = order;
     }
 
@@ -366,19 +335,7 @@ impl<T: Clone> Tensor4<T> {
 
     /// Converts the 4D array to a linear buffer according to the specified order
     pub fn to_buffer_with_order(&self, order: Order4) -> Vec<T> {
-        // If order is correct no need to permute
-        if order == self.order {
-            return self.to_buffer();
-        }
-
-        // NOTE:(20240925 vaino-waltteri.granat@tuni.fi) TVM order fix
-        if self.order == Order4::HWCK && order == Order4::HWKC {
-            return self.tvm_layout_to_headsail();
-        }
-
-        let mut data = self.clone();
-        data.permute(order);
-        data.to_buffer()
+        self.view().to_buffer_with_order(order)
     }
 
     /// Convert HWIO (HWCK) order to HWOI (HWKC) for headsail
@@ -405,3 +362,153 @@ impl<T: Clone> Tensor4<T> {
         hwoi_flat
     }
 }
+
+impl<T> Tensor4<T> {
+    /// Borrows the tensor without copying its data
+    pub fn view(&self) -> Tensor4View<'_, T> {
+        Tensor4View {
+            data: self.data.view(),
+            order: self.order,
+        }
+    }
+}
+
+impl<'a, T> From<&'a Tensor4<T>> for Tensor4View<'a, T> {
+    fn from(tensor: &'a Tensor4<T>) -> Self {
+        tensor.view()
+    }
+}
+
+/// Borrowed 4D tensor, e.g. a part of a [`Tensor4`] or weights in the caller's buffer
+///
+/// Slicing a view doesn't copy data, and the elements can be read in any order straight from the
+/// borrowed memory.
+#[derive(Clone, Copy, Debug)]
+pub struct Tensor4View<'a, T> {
+    data: ArrayView4<'a, T>,
+    order: Order4,
+}
+
+impl<'a, T> Tensor4View<'a, T> {
+    /// Creates a view to a data buffer with the specified order
+    pub fn from_slice(
+        kernels: usize,
+        channels: usize,
+        height: usize,
+        width: usize,
+        data_buffer: &'a [T],
+        order: Order4,
+    ) -> Result<Self, &'static str> {
+        if data_buffer.len() != kernels * channels * height * width {
+            return Err("Data buffer size does not match specified dimensions");
+        }
+
+        let standard_shape = [kernels, channels, height, width];
+        let shape = order
+            .into_position()
+            .map(|dimension| standard_shape[dimension]);
+        let data = ArrayView::from_shape(shape, data_buffer)
+            .map_err(|_| "Failed to create array from data buffer")?;
+
+        Ok(Tensor4View { data, order })
+    }
+
+    pub fn kernels(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(0)]
+    }
+
+    pub fn channels(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(1)]
+    }
+
+    pub fn height(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(2)]
+    }
+
+    pub fn width(&self) -> usize {
+        self.data.raw_dim()[self.order.axis(3)]
+    }
+
+    /// Returns the dimensions of the view
+    pub fn dimensions(&self) -> (usize, usize, usize, usize) {
+        (self.kernels(), self.channels(), self.height(), self.width())
+    }
+
+    /// Gets the order of the underlying data
+    pub fn order(&self) -> Order4 {
+        self.order
+    }
+
+    /// Get the number of elements in view
+    pub fn get_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Shortens the lifetime of the view, e.g. to use it in place of a view to a temporary tensor
+    pub fn reborrow<'b>(self) -> Tensor4View<'b, T>
+    where
+        'a: 'b,
+    {
+        Tensor4View {
+            data: self.data.reborrow(),
+            order: self.order,
+        }
+    }
+
+    /// Narrows the view to the given range of kernels
+    pub fn slice_kernels(&self, k_range: core::ops::Range<usize>) -> Tensor4View<'a, T> {
+        let mut data = self.data;
+        data.slice_axis_inplace(Axis(self.order.axis(0)), Slice::from(k_range));
+        Tensor4View {
+            data,
+            order: self.order,
+        }
+    }
+
+    /// Iterates over the elements in the specified order, without copying them
+    pub fn iter_with_order(&self, order: Order4) -> impl Iterator<Item = &'a T> {
+        self.data
+            .permuted_axes(self.order.permutation_to(order))
+            .into_iter()
+    }
+}
+
+impl<T: Clone> Tensor4View<'_, T> {
+    /// Copies the viewed elements to a linear buffer according to the specified order
+    pub fn to_buffer_with_order(&self, order: Order4) -> Vec<T> {
+        self.iter_with_order(order).cloned().collect()
+    }
+
+    /// Copies the viewed elements to a new tensor with the same order
+    pub fn to_owned(&self) -> Tensor4<T> {
+        Tensor4 {
+            data: self.data.to_owned(),
+            order: self.order,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ORDER4;
+
+    #[test]
+    fn permute_between_any_orders() {
+        let data: Vec<i32> = (0..2 * 3 * 4 * 5).collect();
+        let tensor = Tensor4::from_data_buffer(2, 3, 4, 5, data, Order4::KCHW).unwrap();
+
+        for from in ORDER4 {
+            for to in ORDER4 {
+                let mut permuted = tensor.clone();
+                permuted.permute(from);
+                permuted.permute(to);
+                assert_eq!(
+                    permuted.to_buffer(),
+                    tensor.to_buffer_with_order(to),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Helpers shared by the host tests
+use crate::backend::Simulated;
+use crate::tensor3::{Order3, Tensor3};
+use crate::tensor4::{Order4, Tensor4};
+use crate::Dla;
+use alloc::vec::Vec;
+use rand::distributions::uniform::SampleUniform;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Mutex, MutexGuard};
+
+/// Serializes tests that run layers, as memory bank allocation and driver state are global
+pub fn lock() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Returns DLA running on the simulated backend, along with the lock held for the test
+pub fn simulated() -> (Dla<Simulated>, MutexGuard<'static, ()>) {
+    let guard = lock();
+    let dla = Dla::with_backend(Simulated::new());
+    // Reset in case a failed test left them changed
+    dla.set_auto_power_down(false);
+    dla.set_layer_pp_rounding(false);
+    dla.set_layer_mac_saturation(i32::MIN, i32::MAX).unwrap();
+    (dla, guard)
+}
+
+/// Returns a generator with a fixed seed, so that failures are reproducible
+pub fn rng() -> SmallRng {
+    SmallRng::seed_from_u64(0x0D1A)
+}
+
+/// Returns `len` random values in `range`
+pub fn values<T>(rng: &mut SmallRng, len: usize, range: core::ops::RangeInclusive<T>) -> Vec<T>
+where
+    T: SampleUniform + Copy + PartialOrd,
+{
+    (0..len).map(|_| rng.gen_range(range.clone())).collect()
+}
+
+/// Returns a random feature map
+pub fn tensor3(rng: &mut SmallRng, channels: usize, height: usize, width: usize) -> Tensor3<i8> {
+    let data = values(rng, channels * height * width, i8::MIN..=i8::MAX);
+    Tensor3::from_data_buffer(channels, height, width, data, Order3::CHW).unwrap()
+}
+
+/// Returns random kernels
+pub fn tensor4(
+    rng: &mut SmallRng,
+    kernels: usize,
+    channels: usize,
+    height: usize,
+    width: usize,
+) -> Tensor4<i8> {
+    let data = values(rng, kernels * channels * height * width, i8::MIN..=i8::MAX);
+    Tensor4::from_data_buffer(kernels, channels, height, width, data, Order4::KCHW).unwrap()
+}
+
+/// Asserts that tensors have the same dimensions and values, regardless of their data order
+#[track_caller]
+pub fn assert_tensor_eq<T: Clone + PartialEq + core::fmt::Debug>(
+    actual: &Tensor3<T>,
+    expected: &Tensor3<T>,
+) {
+    assert_eq!(actual.dimensions(), expected.dimensions());
+    assert_eq!(
+        actual.to_buffer_with_order(Order3::CHW),
+        expected.to_buffer_with_order(Order3::CHW)
+    );
+}
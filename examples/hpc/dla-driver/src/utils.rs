@@ -1,8 +1,24 @@
+use crate::bank::largest_free_range;
 use crate::mmap::MEMORY_BANK_SIZE;
 use crate::tensor3::{Order3, Tensor3};
-use crate::tensor4::Tensor4;
-use crate::{MemoryBank, Padding, Stride, DEFAULT_PADDING, DEFAULT_STRIDE, MEMORY_BANK_BASE_ADDR};
+use crate::tensor4::{Order4, Tensor4, Tensor4View};
+use crate::{
+    Dilation, DlaError, Padding, Stride, DEFAULT_DILATION, DEFAULT_PADDING, DEFAULT_STRIDE,
+};
 use alloc::vec::Vec;
+use core::ops::Range;
+
+/// PP clip of the TVM entry points when bias fits into 16 bits
+const TVM_PP_CLIP: u32 = 7;
+
+/// Checks that stride is non-zero, as output size is divided by it before the layer's registers
+/// are validated
+pub(crate) fn check_stride(stride: Option<&Stride>) -> Result<(), DlaError> {
+    match stride {
+        Some(Stride { x: 0, .. } | Stride { y: 0, .. }) => Err(DlaError::InvalidDimension),
+        _ => Ok(()),
+    }
+}
 
 /// Calculates the output size of Conv2D for a single channel based on size of the inputs
 ///
@@ -25,6 +41,124 @@ pub fn calculate_conv2d_out_param_dim(
     (output_width as usize, output_height as usize)
 }
 
+/// Calculates the output size of dilated Conv2D for a single channel based on size of the inputs
+///
+/// * `input` - Input data for a given layer.
+/// * `kernel` - Kernels/weight data for a given layer, before dilation.
+/// * `padding` - Padding used in the given layer.
+/// * `stride` - Stride used in the given layer.
+/// * `dilation` - Dilation used in the given layer.
+///
+/// # Errors
+/// - [`DlaError::InvalidDimension`] if stride or dilation is zero.
+/// - [`DlaError::DimensionMismatch`] if the kernel is empty or larger than the padded input once
+///   dilated.
+pub fn calculate_dilated_conv2d_out_param_dim(
+    input: (u32, u32),
+    kernel: (u32, u32),
+    padding: Option<Padding>,
+    stride: Option<Stride>,
+    dilation: Option<Dilation>,
+) -> Result<(usize, usize), DlaError> {
+    let padding = padding.unwrap_or(DEFAULT_PADDING);
+    let stride = stride.unwrap_or(DEFAULT_STRIDE);
+    let dilation = dilation.unwrap_or(DEFAULT_DILATION);
+    if stride.x == 0 || stride.y == 0 || dilation.x == 0 || dilation.y == 0 {
+        return Err(DlaError::InvalidDimension);
+    }
+
+    // Number of positions the dilated kernel fits into along one axis
+    let positions = |input: u32, pad: (u32, u32), kernel: u32, dilation: u32, stride: u32| {
+        let kernel = kernel
+            .checked_sub(1)?
+            .checked_mul(dilation)?
+            .checked_add(1)?;
+        let span = input
+            .checked_add(pad.0)?
+            .checked_add(pad.1)?
+            .checked_sub(kernel)?;
+        Some((span / stride + 1) as usize)
+    };
+    let output_width = positions(
+        input.0,
+        (padding.left, padding.right),
+        kernel.0,
+        dilation.x,
+        stride.x,
+    );
+    let output_height = positions(
+        input.1,
+        (padding.top, padding.bottom),
+        kernel.1,
+        dilation.y,
+        stride.y,
+    );
+    output_width
+        .zip(output_height)
+        .ok_or(DlaError::DimensionMismatch)
+}
+
+/// Expands kernels for dilated convolution by inserting `dilation - 1` zeros between elements
+///
+/// Convolution with the expanded kernels equals the dilated convolution with the original ones.
+///
+/// # Errors
+/// - [`DlaError::InvalidDimension`] if dilation is zero.
+/// - [`DlaError::DimensionMismatch`] if kernels are empty.
+pub fn dilate_kernels<T: Clone + Default>(
+    kernels: Tensor4View<T>,
+    dilation: &Dilation,
+) -> Result<Tensor4<T>, DlaError> {
+    if dilation.x == 0 || dilation.y == 0 {
+        return Err(DlaError::InvalidDimension);
+    }
+    let (k, c, h, w) = kernels.dimensions();
+    if k * c * h * w == 0 {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let (dx, dy) = (dilation.x as usize, dilation.y as usize);
+    let height = (h - 1) * dy + 1;
+    let width = (w - 1) * dx + 1;
+
+    let mut dilated = vec![T::default(); k * c * height * width];
+    for (i, value) in kernels.iter_with_order(Order4::KCHW).enumerate() {
+        let (kc, y, x) = (i / (h * w), i / w % h, i % w);
+        dilated[(kc * height + y * dy) * width + x * dx] = value.clone();
+    }
+    Ok(Tensor4::from_data_buffer(k, c, height, width, dilated, Order4::KCHW).unwrap())
+}
+
+/// Inserts `stride - 1` zeros between the pixels of the input, as needed by transposed
+/// convolution
+///
+/// The result is in HWC order.
+///
+/// # Errors
+/// - [`DlaError::InvalidDimension`] if stride is zero.
+/// - [`DlaError::DimensionMismatch`] if input is empty.
+pub fn zero_insert<T: Clone + Default>(
+    input: &Tensor3<T>,
+    stride: &Stride,
+) -> Result<Tensor3<T>, DlaError> {
+    check_stride(Some(stride))?;
+    let (c, h, w) = input.dimensions();
+    if c * h * w == 0 {
+        return Err(DlaError::DimensionMismatch);
+    }
+    let (sx, sy) = (stride.x as usize, stride.y as usize);
+    let height = (h - 1) * sy + 1;
+    let width = (w - 1) * sx + 1;
+
+    let data = input.to_buffer_with_order(Order3::HWC);
+    let mut expanded = vec![T::default(); c * height * width];
+    for (i, pixel) in data.chunks(c).enumerate() {
+        let (y, x) = (i / w * sy, i % w * sx);
+        let start = (y * width + x) * c;
+        expanded[start..start + c].clone_from_slice(pixel);
+    }
+    Ok(Tensor3::from_data_buffer(c, height, width, expanded, Order3::HWC).unwrap())
+}
+
 /// Creates a output tensor matching the given inputs from the ground truth output.
 ///
 /// * `input` - Input data for a given layer.
@@ -65,28 +199,116 @@ pub fn calculate_number_of_banks_needed(bytes: usize) -> usize {
     bytes.div_ceil(MEMORY_BANK_SIZE)
 }
 
-/// Assigns data banks for layer data
+/// Calculates the largest output tile of Conv2D whose data fits into DLA's memory banks
+///
+/// Output is tiled along height first. Width is tiled only if a single full-width output row does
+/// not fit. Only banks that are currently free are taken into account. Returns tile size as
+/// (height, width).
+///
+/// * `input` - Input dimensions as (channels, height, width).
+/// * `kernel` - Kernel dimensions as (kernels, height, width).
+/// * `output` - Output dimensions as (height, width).
+/// * `stride` - Stride used in the given layer.
+/// * `output_element_size` - Size of a single output element in bytes.
+/// * `bias` - Whether a bank needs to be reserved for bias.
+pub fn calculate_tile_size(
+    input: (usize, usize, usize),
+    kernel: (usize, usize, usize),
+    output: (usize, usize),
+    stride: Option<Stride>,
+    output_element_size: usize,
+    bias: bool,
+) -> Result<(usize, usize), DlaError> {
+    let stride = stride.unwrap_or(DEFAULT_STRIDE);
+    let (channels, input_height, input_width) = input;
+    let (kernels, kernel_height, kernel_width) = kernel;
+    let num_banks = largest_free_range();
+
+    let kernel_banks =
+        calculate_number_of_banks_needed(kernels * channels * kernel_height * kernel_width);
+    let fits = |tile_height: usize, tile_width: usize| {
+        // Padding is not stored in the banks, so a tile never needs more input than there is
+        let tile_input_height = core::cmp::min(
+            (tile_height - 1) * stride.y as usize + kernel_height,
+            input_height,
+        );
+        let tile_input_width = core::cmp::min(
+            (tile_width - 1) * stride.x as usize + kernel_width,
+            input_width,
+        );
+        let banks =
+            calculate_number_of_banks_needed(channels * tile_input_height * tile_input_width)
+                + kernel_banks
+                + calculate_number_of_banks_needed(
+                    kernels * tile_height * tile_width * output_element_size,
+                )
+                + bias as usize;
+        banks <= num_banks
+    };
+
+    for tile_width in (1..=output.1).rev() {
+        if let Some(tile_height) = (1..=output.0).rev().find(|&h| fits(h, tile_width)) {
+            return Ok((tile_height, tile_width));
+        }
+    }
+    Err(DlaError::BankOverflow)
+}
+
+/// Calculates the largest number of rows post-processing can run on at once, with input and 8-bit
+/// output of the rows fitting into DLA's memory banks
 ///
-/// * `input_size` - The size of input data in bytes
-/// * `kernel_size` - The size of kernel data in bytes
-/// * `output_size` - The size of output in bytes
-/// * `bias_size` - The size of bias in bytes. If left empty bias is not assigned.
-pub fn get_banks_for_layer(
-    input_size: usize,
-    kernels_size: usize,
-    output_size: usize,
-) -> (MemoryBank, MemoryBank, MemoryBank, Option<u32>) {
-    let num_input_banks = calculate_number_of_banks_needed(input_size);
-    let num_kernel_banks = calculate_number_of_banks_needed(kernels_size);
-    let num_output_banks = calculate_number_of_banks_needed(output_size);
+/// Only banks that are currently free are taken into account.
+///
+/// * `input` - Input dimensions as (channels, height, width).
+/// * `bias` - Whether a bank needs to be reserved for bias.
+pub fn calculate_pp_tile_height(
+    input: (usize, usize, usize),
+    bias: bool,
+) -> Result<usize, DlaError> {
+    let (channels, height, width) = input;
+    let num_banks = largest_free_range();
+
+    let fits = |rows: usize| {
+        // Output has the same size as input
+        2 * calculate_number_of_banks_needed(channels * rows * width) + bias as usize <= num_banks
+    };
+    (1..=height)
+        .rev()
+        .find(|&rows| fits(rows))
+        .ok_or(DlaError::BankOverflow)
+}
 
-    let input_bank = MemoryBank::Bank0;
-    let kernel_bank = input_bank + num_input_banks;
-    let output_bank = kernel_bank + num_kernel_banks;
+/// Calculates the input range and padding needed to produce a range of Conv2D outputs along one
+/// axis
+///
+/// Neighbouring tiles overlap by the kernel size minus stride (halo). Padding is applied only on
+/// the tiles at the edges of the input. Returns (input range, padding before, padding after).
+///
+/// * `output` - Range of output rows or columns.
+/// * `stride` - Stride along the axis.
+/// * `kernel` - Kernel size along the axis.
+/// * `padding` - Padding before the first input row or column.
+/// * `input` - Input size along the axis.
+pub fn calculate_tile_input_range(
+    output: Range<usize>,
+    stride: usize,
+    kernel: usize,
+    padding: usize,
+    input: usize,
+) -> Result<(Range<usize>, u32, u32), DlaError> {
+    // Start and end in padded coordinates
+    let start = output.start * stride;
+    let end = (output.end - 1) * stride + kernel;
 
-    let bias_bank =
-        Some((MEMORY_BANK_BASE_ADDR + (output_bank + num_output_banks).offset()) as u32);
-    (input_bank, kernel_bank, output_bank, bias_bank)
+    let first = start.saturating_sub(padding);
+    let last = core::cmp::min(end.saturating_sub(padding), input);
+    if first >= last {
+        // Tile would consist of padding only
+        return Err(DlaError::DimensionMismatch);
+    }
+    let padding_before = padding.saturating_sub(start) as u32;
+    let padding_after = end.saturating_sub(padding + input) as u32;
+    Ok((first..last, padding_before, padding_after))
 }
 
 /// Divides x with y and ceils the output
@@ -127,3 +349,49 @@ pub fn optimal_pp_bias_heuristic(bias: &[i16]) -> u32 {
     }
     pp
 }
+
+/// Calculates the smallest right shift that brings all of `bias` into the 16-bit range of DLA's
+/// post-processor
+pub fn calculate_bias_shift(bias: &[i32]) -> u32 {
+    let mut shift = 0;
+    while bias
+        .iter()
+        .any(|&x| (x >> shift) > i16::MAX as i32 || (x >> shift) < i16::MIN as i32)
+    {
+        shift += 1;
+    }
+    shift
+}
+
+/// PP clip the TVM entry points use with 32-bit bias
+///
+/// Clip is 7 like with bias clamped to 16 bits, unless fitting the bias to 16 bits with
+/// [`calculate_bias_shift`] needs a larger shift.
+pub fn tvm_bias_pp_clip(bias: &[i32]) -> u32 {
+    calculate_bias_shift(bias).max(TVM_PP_CLIP)
+}
+
+/// Fits 32-bit bias to DLA by moving part of the post-processing clip in front of the bias
+///
+/// MAC results and bias are both shifted right by [`calculate_bias_shift`] before they are added,
+/// and the post-processing clip is reduced by the same amount, so the total scaling of the output
+/// stays the same. Returns the 16-bit bias along with the adjusted MAC and PP clips.
+///
+/// # Errors
+/// - [`DlaError::InvalidClip`] if the shift doesn't fit into the PP clip or the adjusted MAC clip
+///   exceeds the hardware maximum.
+pub fn rescale_bias(
+    bias: &[i32],
+    mac_clip: u32,
+    pp_clip: u32,
+) -> Result<(Vec<i16>, u32, u32), DlaError> {
+    let shift = calculate_bias_shift(bias);
+    if shift > pp_clip {
+        return Err(DlaError::InvalidClip(shift));
+    }
+    if mac_clip + shift > 21 {
+        return Err(DlaError::InvalidClip(mac_clip + shift));
+    }
+    let bias = bias.iter().map(|&x| (x >> shift) as i16).collect();
+    Ok((bias, mac_clip + shift, pp_clip - shift))
+}
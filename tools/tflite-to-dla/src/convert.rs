@@ -0,0 +1,578 @@
+//! Maps TFLite operators onto the layers of the DLA model format
+//!
+//! Weights are converted from TFLite's OHWI (convolution) and 1HWO (depthwise) layouts to HWIO,
+//! which `Tensor4::tvm_layout_to_headsail` turns into the HWOI layout DLA reads. Zero points of
+//! layer inputs are folded into the 32-bit bias and used as padding value, and the output scale
+//! of each layer is stored as a fixed-point multiplier for requantization.
+use crate::flatbuffer::{Error, Result, Table};
+use crate::image::Layer;
+use crate::tflite::{op, tensor_type, Activation, Model, PaddingScheme, Tensor};
+use dla_driver::model::{LayerKind, MODEL_INPUT};
+use dla_driver::quant::{QuantizedMultiplier, Requantization, Rounding, ADD_LEFT_SHIFT};
+use dla_driver::tensor4::Order4;
+use dla_driver::utils::calculate_bias_shift;
+use std::collections::HashMap;
+
+/// Largest padding DLA supports on each side
+const MAX_PADDING: u32 = 15;
+
+/// Converted model
+pub struct Converted {
+    /// Input channels, height and width
+    pub input: [u32; 3],
+    pub layers: Vec<Layer>,
+}
+
+/// Converts the main subgraph of a TFLite model
+pub fn convert(model: &Model) -> Result<Converted> {
+    let codes = model.operator_codes()?;
+    let subgraph = model.subgraph()?;
+    let tensors = subgraph.tensors()?;
+    let tensor = |idx: i32| {
+        tensors
+            .get(idx as usize)
+            .ok_or_else(|| Error(format!("tensor {idx} does not exist")))
+    };
+
+    let [input] = subgraph.inputs()?[..] else {
+        return Err(Error("expected a single model input".into()));
+    };
+    let [output] = subgraph.outputs()?[..] else {
+        return Err(Error("expected a single model output".into()));
+    };
+
+    let mut converter = Converter {
+        model,
+        layers: Vec::new(),
+        sources: HashMap::from([(input, MODEL_INPUT)]),
+        input,
+    };
+
+    for operator in subgraph.operators()? {
+        let code = *codes
+            .get(operator.opcode_index()? as usize)
+            .ok_or_else(|| Error("invalid operator code index".into()))?;
+        let inputs = operator.inputs()?;
+        let outputs = operator.outputs()?;
+        let (Some(&x), Some(&y)) = (inputs.first(), outputs.first()) else {
+            return Err(Error(format!("operator {code} without input or output")));
+        };
+        let options = operator.options()?;
+        let (x_tensor, y_tensor) = (tensor(x)?, tensor(y)?);
+        let weights = |inputs: &[i32]| match inputs.get(1) {
+            Some(&w) => tensor(w),
+            None => Err(Error(format!("operator {code} without weights"))),
+        };
+
+        let layer = match code {
+            // Layout changes and quantization at the model boundaries don't change the data
+            op::RESHAPE | op::DEQUANTIZE => {
+                converter.alias(x, y)?;
+                continue;
+            }
+            op::QUANTIZE if x == input && x_tensor.tensor_type()? != tensor_type::INT8 => {
+                // Model is run with inputs already quantized
+                converter.alias(x, y)?;
+                converter.input = y;
+                continue;
+            }
+            op::CONV_2D | op::DEPTHWISE_CONV_2D => {
+                let options = options.ok_or_else(|| Error("missing convolution options".into()))?;
+                let depthwise = code == op::DEPTHWISE_CONV_2D;
+                let bias = match inputs.get(2) {
+                    Some(&b) if b >= 0 => Some(tensor(b)?),
+                    _ => None,
+                };
+                converter.convolution(
+                    x,
+                    x_tensor,
+                    weights(&inputs)?,
+                    bias,
+                    y_tensor,
+                    &options,
+                    depthwise,
+                )?
+            }
+            op::FULLY_CONNECTED => {
+                let activation = match &options {
+                    Some(options) => {
+                        if options.scalar::<i8>(1, 0)? != 0 {
+                            return Err(Error("shuffled weights are not supported".into()));
+                        }
+                        Activation::try_from(options.scalar::<i8>(0, 0)?)?
+                    }
+                    None => Activation::None,
+                };
+                let bias = match inputs.get(2) {
+                    Some(&b) if b >= 0 => Some(tensor(b)?),
+                    _ => None,
+                };
+                converter.fully_connected(
+                    x,
+                    x_tensor,
+                    weights(&inputs)?,
+                    bias,
+                    y_tensor,
+                    activation,
+                )?
+            }
+            op::MAX_POOL_2D | op::AVERAGE_POOL_2D => {
+                let options = options.ok_or_else(|| Error("missing pooling options".into()))?;
+                let kind = if code == op::MAX_POOL_2D {
+                    LayerKind::MaxPool2d
+                } else {
+                    LayerKind::AvgPool2d
+                };
+                converter.pool(kind, x, x_tensor, y_tensor, &options)?
+            }
+            op::ADD => {
+                let activation = match &options {
+                    Some(options) => Activation::try_from(options.scalar::<i8>(0, 0)?)?,
+                    None => Activation::None,
+                };
+                let b = *inputs
+                    .get(1)
+                    .ok_or_else(|| Error("ADD needs two inputs".into()))?;
+                converter.add(x, x_tensor, b, tensor(b)?, y_tensor, activation)?
+            }
+            _ => return Err(Error(format!("unsupported operator {code}"))),
+        };
+
+        let idx = u16::try_from(converter.layers.len())
+            .ok()
+            .filter(|&idx| idx != MODEL_INPUT)
+            .ok_or_else(|| Error("too many layers".into()))?;
+        converter.layers.push(layer);
+        converter.sources.insert(y, idx);
+    }
+
+    if converter.layers.is_empty()
+        || converter.sources.get(&output) != Some(&(converter.layers.len() as u16 - 1))
+    {
+        return Err(Error(
+            "model output must be produced by the last layer".into(),
+        ));
+    }
+
+    let input_shape = nhwc(tensor(converter.input)?)?;
+    Ok(Converted {
+        input: [input_shape[3], input_shape[1], input_shape[2]],
+        layers: converter.layers,
+    })
+}
+
+struct Converter<'a, 'b> {
+    model: &'b Model<'a>,
+    layers: Vec<Layer>,
+    /// Layer producing each tensor, or the model input
+    sources: HashMap<i32, u16>,
+    /// Tensor the model is run with
+    input: i32,
+}
+
+impl Converter<'_, '_> {
+    fn source(&self, tensor: i32) -> Result<u16> {
+        self.sources
+            .get(&tensor)
+            .copied()
+            .ok_or_else(|| Error(format!("tensor {tensor} is not produced by any layer")))
+    }
+
+    /// Makes `to` refer to the same data as `from`
+    fn alias(&mut self, from: i32, to: i32) -> Result<()> {
+        let source = self.source(from)?;
+        self.sources.insert(to, source);
+        Ok(())
+    }
+
+    fn weights(&self, tensor: &Tensor) -> Result<Vec<i8>> {
+        if tensor.tensor_type()? != tensor_type::INT8 {
+            return Err(Error(format!("weights {} are not int8", tensor.name()?)));
+        }
+        let (_, zero_points) = tensor.quantization()?;
+        if zero_points.iter().any(|&zp| zp != 0) {
+            return Err(Error(format!(
+                "weights {} are not symmetrically quantized",
+                tensor.name()?
+            )));
+        }
+        Ok(self
+            .model
+            .buffer(tensor.buffer()?)?
+            .iter()
+            .map(|&w| w as i8)
+            .collect())
+    }
+
+    fn bias(&self, tensor: Option<&Tensor>, len: usize) -> Result<Vec<i32>> {
+        let Some(tensor) = tensor else {
+            return Ok(vec![0; len]);
+        };
+        if tensor.tensor_type()? != tensor_type::INT32 {
+            return Err(Error(format!("bias {} is not int32", tensor.name()?)));
+        }
+        let bias: Vec<i32> = self
+            .model
+            .buffer(tensor.buffer()?)?
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if bias.len() != len {
+            return Err(Error(format!("bias {} has wrong length", tensor.name()?)));
+        }
+        Ok(bias)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn convolution(
+        &self,
+        x: i32,
+        x_tensor: &Tensor,
+        w_tensor: &Tensor,
+        b_tensor: Option<&Tensor>,
+        y_tensor: &Tensor,
+        options: &Table,
+        depthwise: bool,
+    ) -> Result<Layer> {
+        // Option fields of Conv2DOptions and DepthwiseConv2DOptions
+        let (activation_field, dilation_field) = if depthwise { (4, 5) } else { (3, 4) };
+        let padding = PaddingScheme::try_from(options.scalar::<i8>(0, 0)?)?;
+        let stride = [options.scalar::<i32>(1, 1)?, options.scalar::<i32>(2, 1)?];
+        let activation = Activation::try_from(options.scalar::<i8>(activation_field, 0)?)?;
+        if options.scalar::<i32>(dilation_field, 1)? != 1
+            || options.scalar::<i32>(dilation_field + 1, 1)? != 1
+        {
+            return Err(Error("dilated convolutions are not supported".into()));
+        }
+
+        let input = nhwc(x_tensor)?;
+        let channels = input[3] as usize;
+        let w_shape = shape4(w_tensor)?;
+        let (kernels, height, width) = if depthwise {
+            // 1HWO
+            (w_shape[3], w_shape[1], w_shape[2])
+        } else {
+            // OHWI
+            if w_shape[3] as usize != channels {
+                return Err(Error("convolution weights don't match input".into()));
+            }
+            (w_shape[0], w_shape[1], w_shape[2])
+        };
+        if depthwise && (kernels as usize).checked_rem(channels) != Some(0) {
+            return Err(Error("depthwise weights don't match input".into()));
+        }
+        let (k, h, w) = (kernels as usize, height as usize, width as usize);
+        let weight_channels = if depthwise { 1 } else { channels };
+
+        let weights = self.weights(w_tensor)?;
+        if weights.len() != k * h * w * weight_channels {
+            return Err(Error("convolution weights have wrong size".into()));
+        }
+        // HWIO, with a single input channel for depthwise weights
+        let hwio: Vec<i8> = if depthwise {
+            weights
+        } else {
+            let mut hwio = vec![0; weights.len()];
+            for (idx, &value) in weights.iter().enumerate() {
+                let (kernel, rest) = (idx / (h * w * channels), idx % (h * w * channels));
+                hwio[rest * k + kernel] = value;
+            }
+            hwio
+        };
+
+        let (in_scale, in_zp) = per_tensor(x_tensor)?;
+        let mut bias = self.bias(b_tensor, k)?;
+        for (kernel, bias) in bias.iter_mut().enumerate() {
+            let sum: i32 = (0..h * w * weight_channels)
+                .map(|idx| hwio[idx * k + kernel] as i32)
+                .sum();
+            *bias = fold_zero_point(*bias, in_zp, sum)?;
+        }
+
+        let mut layer = Layer::new(
+            if depthwise {
+                LayerKind::DepthwiseConv2d
+            } else {
+                LayerKind::Conv2d
+            },
+            self.source(x)?,
+        );
+        layer.weight_order = Order4::HWCK;
+        layer.shape = [kernels, weight_channels as u32, height, width];
+        layer.padding = same_padding(padding, [input[1], input[2]], [height, width], stride)?;
+        layer.padding_value = in_zp as i8;
+        layer.stride = [stride_u8(stride[0])?, stride_u8(stride[1])?];
+        layer.weights = hwio;
+        self.requantization(&mut layer, in_scale, w_tensor, y_tensor, activation, bias)?;
+        Ok(layer)
+    }
+
+    fn fully_connected(
+        &self,
+        x: i32,
+        x_tensor: &Tensor,
+        w_tensor: &Tensor,
+        b_tensor: Option<&Tensor>,
+        y_tensor: &Tensor,
+        activation: Activation,
+    ) -> Result<Layer> {
+        let w_shape = w_tensor.shape()?;
+        let [outputs, inputs] = w_shape[..] else {
+            return Err(Error(
+                "fully connected weights must have two dimensions".into(),
+            ));
+        };
+        let in_features: i32 = x_tensor.shape()?.iter().product();
+        if in_features != inputs {
+            return Err(Error("fully connected weights don't match input".into()));
+        }
+
+        let weights = self.weights(w_tensor)?;
+        let (in_scale, in_zp) = per_tensor(x_tensor)?;
+        let mut bias = self.bias(b_tensor, outputs as usize)?;
+        for (row, bias) in weights.chunks(inputs as usize).zip(bias.iter_mut()) {
+            *bias = fold_zero_point(*bias, in_zp, row.iter().map(|&w| w as i32).sum())?;
+        }
+
+        let mut layer = Layer::new(LayerKind::FullyConnected, self.source(x)?);
+        layer.weight_order = Order4::KCHW;
+        layer.shape = [outputs as u32, inputs as u32, 1, 1];
+        layer.weights = weights;
+        self.requantization(&mut layer, in_scale, w_tensor, y_tensor, activation, bias)?;
+        Ok(layer)
+    }
+
+    /// Stores bias, multipliers and clips of a layer calculated on DLA
+    fn requantization(
+        &self,
+        layer: &mut Layer,
+        in_scale: f64,
+        w_tensor: &Tensor,
+        y_tensor: &Tensor,
+        activation: Activation,
+        bias: Vec<i32>,
+    ) -> Result<()> {
+        let (w_scales, _) = w_tensor.quantization()?;
+        let (out_scale, out_zp) = per_tensor(y_tensor)?;
+        let kernels = layer.shape[0] as usize;
+        if w_scales.len() != 1 && w_scales.len() != kernels {
+            return Err(Error(format!(
+                "weights {} have wrong number of scales",
+                w_tensor.name()?
+            )));
+        }
+
+        layer.multipliers = w_scales
+            .iter()
+            .map(|&w_scale| quantize_multiplier(in_scale * w_scale as f64 / out_scale))
+            .collect::<Result<_>>()?;
+        layer.relu = relu(activation, out_scale, out_zp)?;
+        layer.zero_points = [0, 0, out_zp];
+        let pp_clip =
+            Requantization::per_channel(layer.multipliers.clone(), 0, out_zp, Rounding::TfLite)
+                .dla_pp_clip();
+        layer.pp_clip = pp_clip.max(calculate_bias_shift(&bias)) as u8;
+        layer.bias = bias;
+        Ok(())
+    }
+
+    fn pool(
+        &self,
+        kind: LayerKind,
+        x: i32,
+        x_tensor: &Tensor,
+        y_tensor: &Tensor,
+        options: &Table,
+    ) -> Result<Layer> {
+        let padding = PaddingScheme::try_from(options.scalar::<i8>(0, 0)?)?;
+        let stride = [options.scalar::<i32>(1, 0)?, options.scalar::<i32>(2, 0)?];
+        let filter = [options.scalar::<i32>(3, 0)?, options.scalar::<i32>(4, 0)?];
+        let activation = Activation::try_from(options.scalar::<i8>(5, 0)?)?;
+
+        // DLA pools square windows without overlap
+        let input = nhwc(x_tensor)?;
+        let size = filter[0];
+        if filter[1] != size || stride != filter || size <= 0 {
+            return Err(Error(
+                "only square pooling with stride of window size is supported".into(),
+            ));
+        }
+        if padding == PaddingScheme::Same
+            && (!input[1].is_multiple_of(size as u32) || !input[2].is_multiple_of(size as u32))
+        {
+            return Err(Error("padded pooling is not supported".into()));
+        }
+        if per_tensor(x_tensor)? != per_tensor(y_tensor)? || activation != Activation::None {
+            return Err(Error("pooling must not requantize".into()));
+        }
+
+        let mut layer = Layer::new(kind, self.source(x)?);
+        layer.shape = [0, 0, size as u32, size as u32];
+        Ok(layer)
+    }
+
+    fn add(
+        &self,
+        a: i32,
+        a_tensor: &Tensor,
+        b: i32,
+        b_tensor: &Tensor,
+        y_tensor: &Tensor,
+        activation: Activation,
+    ) -> Result<Layer> {
+        if a_tensor.shape()? != b_tensor.shape()? {
+            return Err(Error("broadcasting ADD is not supported".into()));
+        }
+        let (a_scale, a_zp) = per_tensor(a_tensor)?;
+        let (b_scale, b_zp) = per_tensor(b_tensor)?;
+        let (out_scale, out_zp) = per_tensor(y_tensor)?;
+
+        // As in TFLite's quantized ADD
+        let twice_max = 2.0 * a_scale.max(b_scale);
+        let mut layer = Layer::new(LayerKind::Add, self.source(a)?);
+        layer.second_input = self.source(b)?;
+        layer.multipliers = vec![
+            quantize_multiplier(a_scale / twice_max)?,
+            quantize_multiplier(b_scale / twice_max)?,
+            quantize_multiplier(twice_max / ((1 << ADD_LEFT_SHIFT) as f64 * out_scale))?,
+        ];
+        layer.zero_points = [a_zp, b_zp, out_zp];
+        layer.relu = relu(activation, out_scale, out_zp)?;
+        Ok(layer)
+    }
+}
+
+/// Returns the shape of an activation tensor, which must have a batch of one
+fn nhwc(tensor: &Tensor) -> Result<[u32; 4]> {
+    let shape = shape4(tensor)?;
+    if shape[0] != 1 {
+        return Err(Error(format!(
+            "tensor {} has a batch size other than 1",
+            tensor.name()?
+        )));
+    }
+    if tensor.tensor_type()? != tensor_type::INT8 {
+        return Err(Error(format!("tensor {} is not int8", tensor.name()?)));
+    }
+    Ok(shape)
+}
+
+fn shape4(tensor: &Tensor) -> Result<[u32; 4]> {
+    let shape = tensor.shape()?;
+    match shape[..] {
+        [a, b, c, d] if shape.iter().all(|&dim| dim > 0) => {
+            Ok([a as u32, b as u32, c as u32, d as u32])
+        }
+        _ => Err(Error(format!(
+            "tensor {} is not 4-dimensional",
+            tensor.name()?
+        ))),
+    }
+}
+
+/// Returns the scale and zero point of a tensor quantized per tensor
+fn per_tensor(tensor: &Tensor) -> Result<(f64, i32)> {
+    let (scales, zero_points) = tensor.quantization()?;
+    match (&scales[..], &zero_points[..]) {
+        ([scale], [zero_point]) => Ok((*scale as f64, *zero_point as i32)),
+        _ => Err(Error(format!(
+            "tensor {} is not quantized per tensor",
+            tensor.name()?
+        ))),
+    }
+}
+
+/// Calculates multiplier and shift of a scale like `QuantizeMultiplier` of TFLite
+fn quantize_multiplier(scale: f64) -> Result<QuantizedMultiplier> {
+    QuantizedMultiplier::from_scale(scale).ok_or_else(|| Error(format!("invalid scale {scale}")))
+}
+
+/// Folds input zero point into bias: sum((x - zp) * w) = sum(x * w) - zp * sum(w)
+fn fold_zero_point(bias: i32, zero_point: i32, weight_sum: i32) -> Result<i32> {
+    zero_point
+        .checked_mul(weight_sum)
+        .and_then(|offset| bias.checked_sub(offset))
+        .ok_or_else(|| Error("bias overflows when folding in the input zero point".into()))
+}
+
+/// Whether the activation is done by DLA's ReLU
+///
+/// ReLU6 is only supported when the output range ends below 6 anyway.
+fn relu(activation: Activation, out_scale: f64, out_zp: i32) -> Result<bool> {
+    match activation {
+        Activation::None => Ok(false),
+        Activation::Relu => Ok(true),
+        Activation::Relu6 if (6.0 / out_scale).round() as i64 + out_zp as i64 >= 127 => Ok(true),
+        Activation::Relu6 => Err(Error(
+            "ReLU6 within the output range is not supported".into(),
+        )),
+    }
+}
+
+/// Calculates top, right, left and bottom padding like TFLite
+fn same_padding(
+    scheme: PaddingScheme,
+    input: [u32; 2],
+    kernel: [u32; 2],
+    stride: [i32; 2],
+) -> Result<[u8; 4]> {
+    if scheme == PaddingScheme::Valid {
+        return Ok([0; 4]);
+    }
+    let total = |input: u32, kernel: u32, stride: i32| {
+        let stride = stride.max(1) as u32;
+        let output = input.div_ceil(stride);
+        ((output - 1) * stride + kernel).saturating_sub(input)
+    };
+    // Input is height, width and stride x, y
+    let vertical = total(input[0], kernel[0], stride[1]);
+    let horizontal = total(input[1], kernel[1], stride[0]);
+    let (top, left) = (vertical / 2, horizontal / 2);
+    let padding = [top, horizontal - left, left, vertical - top];
+    if padding.iter().any(|&p| p > MAX_PADDING) {
+        return Err(Error("padding is larger than DLA supports".into()));
+    }
+    Ok(padding.map(|p| p as u8))
+}
+
+fn stride_u8(stride: i32) -> Result<u8> {
+    u8::try_from(stride)
+        .ok()
+        .filter(|&s| s > 0)
+        .ok_or_else(|| Error(format!("invalid stride {stride}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folding_zero_point_checks_overflow() {
+        assert_eq!(fold_zero_point(100, -3, 50).unwrap(), 250);
+        assert!(fold_zero_point(i32::MAX, -1, 1).is_err());
+        assert!(fold_zero_point(0, 128, i32::MAX / 100).is_err());
+    }
+
+    #[test]
+    fn quantize_multiplier_rejects_invalid_scales() {
+        assert_eq!(
+            quantize_multiplier(0.3).unwrap(),
+            QuantizedMultiplier::new(1288490189, -1)
+        );
+        assert!(quantize_multiplier(-0.5).is_err());
+        assert!(quantize_multiplier(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn same_padding_matches_tflite() {
+        assert_eq!(
+            same_padding(PaddingScheme::Same, [5, 6], [3, 3], [2, 2]).unwrap(),
+            [1, 1, 0, 1]
+        );
+        assert_eq!(
+            same_padding(PaddingScheme::Valid, [5, 6], [3, 3], [1, 1]).unwrap(),
+            [0; 4]
+        );
+        assert!(same_padding(PaddingScheme::Same, [40, 40], [40, 40], [1, 1]).is_err());
+    }
+}
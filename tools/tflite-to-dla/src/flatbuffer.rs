@@ -0,0 +1,214 @@
+//! Minimal reader for the FlatBuffers binary format
+//!
+//! Only supports what reading a TFLite model needs: tables, scalars, strings and vectors of
+//! scalars or tables. Every access is bounds checked, so malformed files produce errors instead
+//! of panics.
+use std::fmt;
+
+#[derive(Debug)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn out_of_bounds(pos: usize) -> Error {
+    Error(format!("flatbuffer access out of bounds at {pos}"))
+}
+
+/// Scalar types stored in flatbuffers
+pub trait Scalar: Sized + Copy {
+    const SIZE: usize;
+    fn from_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_scalar {
+    ($($ty:ty),*) => {
+        $(impl Scalar for $ty {
+            const SIZE: usize = core::mem::size_of::<$ty>();
+            fn from_le(bytes: &[u8]) -> Self {
+                <$ty>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        })*
+    };
+}
+
+impl_scalar!(u8, i8, u16, i16, u32, i32, u64, i64, f32);
+
+/// Returns `len` bytes at `pos`
+fn bytes(buf: &[u8], pos: usize, len: usize) -> Result<&[u8]> {
+    pos.checked_add(len)
+        .and_then(|end| buf.get(pos..end))
+        .ok_or_else(|| out_of_bounds(pos))
+}
+
+fn read<T: Scalar>(buf: &[u8], pos: usize) -> Result<T> {
+    bytes(buf, pos, T::SIZE).map(T::from_le)
+}
+
+/// Returns `pos` moved by `offset` bytes
+fn offset(pos: usize, offset: isize) -> Result<usize> {
+    pos.checked_add_signed(offset)
+        .ok_or_else(|| out_of_bounds(pos))
+}
+
+/// Follows an unsigned offset stored at `pos`
+fn follow(buf: &[u8], pos: usize) -> Result<usize> {
+    offset(pos, read::<u32>(buf, pos)? as isize)
+}
+
+/// Table of a flatbuffer
+#[derive(Clone, Copy)]
+pub struct Table<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Table<'a> {
+    /// Returns the root table of a flatbuffer
+    pub fn root(buf: &'a [u8]) -> Result<Self> {
+        Ok(Table {
+            buf,
+            pos: follow(buf, 0)?,
+        })
+    }
+
+    /// Returns the position of field `idx`, or `None` if it is not present
+    fn field(&self, idx: usize) -> Result<Option<usize>> {
+        // Vtable is at a signed offset backwards from the table
+        let vtable = offset(self.pos, -(read::<i32>(self.buf, self.pos)? as isize))?;
+        let vtable_size = read::<u16>(self.buf, vtable)? as usize;
+        let entry = 4 + 2 * idx;
+        if entry + 2 > vtable_size {
+            return Ok(None);
+        }
+        match read::<u16>(self.buf, vtable + entry)? {
+            0 => Ok(None),
+            field => offset(self.pos, field as isize).map(Some),
+        }
+    }
+
+    /// Reads scalar field `idx`, returning `default` if it is not present
+    pub fn scalar<T: Scalar>(&self, idx: usize, default: T) -> Result<T> {
+        match self.field(idx)? {
+            Some(pos) => read(self.buf, pos),
+            None => Ok(default),
+        }
+    }
+
+    /// Reads table field `idx`
+    pub fn table(&self, idx: usize) -> Result<Option<Table<'a>>> {
+        match self.field(idx)? {
+            Some(pos) => Ok(Some(Table {
+                buf: self.buf,
+                pos: follow(self.buf, pos)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads string field `idx`
+    pub fn string(&self, idx: usize) -> Result<Option<&'a str>> {
+        match self.vector_bytes(idx, 1)? {
+            Some(bytes) => std::str::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| Error("invalid string in flatbuffer".into())),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the raw bytes of vector field `idx` with elements of `size` bytes
+    pub fn vector_bytes(&self, idx: usize, size: usize) -> Result<Option<&'a [u8]>> {
+        let Some(pos) = self.field(idx)? else {
+            return Ok(None);
+        };
+        let start = follow(self.buf, pos)?;
+        let len = read::<u32>(self.buf, start)? as usize;
+        let len = len.checked_mul(size).ok_or_else(|| out_of_bounds(start))?;
+        bytes(self.buf, start + 4, len).map(Some)
+    }
+
+    /// Reads vector of scalars field `idx`, empty if it is not present
+    pub fn vector<T: Scalar>(&self, idx: usize) -> Result<Vec<T>> {
+        Ok(self
+            .vector_bytes(idx, T::SIZE)?
+            .unwrap_or_default()
+            .chunks_exact(T::SIZE)
+            .map(T::from_le)
+            .collect())
+    }
+
+    /// Reads vector of tables field `idx`, empty if it is not present
+    pub fn tables(&self, idx: usize) -> Result<Vec<Table<'a>>> {
+        let Some(pos) = self.field(idx)? else {
+            return Ok(Vec::new());
+        };
+        let start = follow(self.buf, pos)?;
+        let len = read::<u32>(self.buf, start)? as usize;
+        (0..len)
+            .map(|i| {
+                Ok(Table {
+                    buf: self.buf,
+                    pos: follow(self.buf, start + 4 + 4 * i)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flatbuffer with a vtable at 4 and a root table at 12 holding i32 field 0
+    fn buffer() -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(12u32.to_le_bytes());
+        // Vtable: its size, table size and offset of field 0
+        for value in [6u16, 8, 4, 0] {
+            buf.extend(value.to_le_bytes());
+        }
+        // Table: offset back to the vtable and field 0
+        buf.extend(8i32.to_le_bytes());
+        buf.extend(42i32.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn reads_fields() {
+        let buf = buffer();
+        let table = Table::root(&buf).unwrap();
+        assert_eq!(table.scalar::<i32>(0, 0).unwrap(), 42);
+        // Not in the vtable
+        assert_eq!(table.scalar::<i32>(1, 7).unwrap(), 7);
+    }
+
+    #[test]
+    fn bad_offsets_are_errors() {
+        for soffset in [16, -100, i32::MIN, i32::MAX] {
+            let mut buf = buffer();
+            buf[12..16].copy_from_slice(&soffset.to_le_bytes());
+            let table = Table::root(&buf).unwrap();
+            assert!(table.scalar::<i32>(0, 0).is_err(), "{soffset}");
+        }
+
+        let mut buf = buffer();
+        buf[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Table::root(&buf)
+            .and_then(|table| table.scalar::<i32>(0, 0))
+            .is_err());
+
+        // Vector of u32::MAX elements of u32::MAX bytes
+        let mut buf = buffer();
+        buf[16..20].copy_from_slice(&4u32.to_le_bytes());
+        buf.extend(u32::MAX.to_le_bytes());
+        let table = Table::root(&buf).unwrap();
+        assert!(table.vector_bytes(0, u32::MAX as usize).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Writer for the model images `dla_driver::model` loads
+//!
+//! See the module documentation of `dla_driver::model` for the layout.
+use dla_driver::model::{LayerKind, MAGIC, MODEL_INPUT, ORDER3, ORDER4, ROUNDING, VERSION};
+use dla_driver::quant::{QuantizedMultiplier, Rounding};
+use dla_driver::tensor3::Order3;
+use dla_driver::tensor4::Order4;
+
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub kind: LayerKind,
+    pub relu: bool,
+    pub weight_order: Order4,
+    pub input: u16,
+    pub second_input: u16,
+    /// Kernels, channels, height and width
+    pub shape: [u32; 4],
+    /// Top, right, left and bottom
+    pub padding: [u8; 4],
+    pub padding_value: i8,
+    /// X and Y
+    pub stride: [u8; 2],
+    pub mac_clip: u8,
+    pub pp_clip: u8,
+    pub rounding: Rounding,
+    /// Input, second input and output
+    pub zero_points: [i32; 3],
+    pub weights: Vec<i8>,
+    pub bias: Vec<i32>,
+    pub multipliers: Vec<QuantizedMultiplier>,
+}
+
+impl Layer {
+    pub fn new(kind: LayerKind, input: u16) -> Self {
+        Layer {
+            kind,
+            relu: false,
+            weight_order: Order4::KCHW,
+            input,
+            second_input: MODEL_INPUT,
+            shape: [0; 4],
+            padding: [0; 4],
+            padding_value: 0,
+            stride: [1, 1],
+            mac_clip: 0,
+            pp_clip: 0,
+            rounding: Rounding::TfLite,
+            zero_points: [0; 3],
+            weights: Vec::new(),
+            bias: Vec::new(),
+            multipliers: Vec::new(),
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.kind as u8);
+        out.push(self.relu as u8);
+        out.push(index(&ORDER4, self.weight_order));
+        // Bias is always stored with 32 bits
+        out.push(if self.bias.is_empty() { 0 } else { 4 });
+        out.extend(self.input.to_le_bytes());
+        out.extend(self.second_input.to_le_bytes());
+        for dim in self.shape {
+            out.extend(dim.to_le_bytes());
+        }
+        out.extend(self.padding);
+        out.push(self.padding_value as u8);
+        out.extend(self.stride);
+        out.push(self.mac_clip);
+        out.push(self.pp_clip);
+        out.push(index(&ROUNDING, self.rounding));
+        out.extend((self.multipliers.len() as u16).to_le_bytes());
+        for zero_point in self.zero_points {
+            out.extend(zero_point.to_le_bytes());
+        }
+        out.extend((self.weights.len() as u32).to_le_bytes());
+        out.extend((self.bias.len() as u32).to_le_bytes());
+
+        out.extend(self.weights.iter().map(|&w| w as u8));
+        pad4(out);
+        for bias in &self.bias {
+            out.extend(bias.to_le_bytes());
+        }
+        for multiplier in &self.multipliers {
+            out.extend(multiplier.multiplier.to_le_bytes());
+            out.extend(multiplier.shift.to_le_bytes());
+        }
+    }
+}
+
+/// Serializes a model with input of the given channels, height and width in HWC order
+pub fn write(input: [u32; 3], layers: &[Layer]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    out.extend((layers.len() as u16).to_le_bytes());
+    for dim in input {
+        out.extend(dim.to_le_bytes());
+    }
+    out.push(index(&ORDER3, Order3::HWC));
+    out.extend([0; 3]);
+    for layer in layers {
+        layer.write(&mut out);
+    }
+    out
+}
+
+/// Returns the index `value` is stored as in the image
+fn index<T: PartialEq>(table: &[T], value: T) -> u8 {
+    table.iter().position(|entry| *entry == value).unwrap() as u8
+}
+
+fn pad4(out: &mut Vec<u8>) {
+    out.resize(out.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dla_driver::backend::Simulated;
+    use dla_driver::model::Model;
+    use dla_driver::tensor3::Tensor3;
+    use dla_driver::Dla;
+
+    fn layers() -> Vec<Layer> {
+        let mut conv = Layer::new(LayerKind::Conv2d, MODEL_INPUT);
+        conv.relu = true;
+        conv.weight_order = Order4::HWCK;
+        conv.shape = [4, 3, 3, 3];
+        conv.padding = [1, 1, 1, 1];
+        conv.padding_value = -5;
+        conv.stride = [1, 2];
+        conv.mac_clip = 2;
+        conv.pp_clip = 6;
+        conv.zero_points = [0, 0, -7];
+        conv.weights = (0..4 * 3 * 3 * 3).map(|w| (w % 11) as i8 - 5).collect();
+        // Needs to be rescaled to 16 bits
+        conv.bias = vec![-100_000, 0, 3, 70_000];
+        conv.multipliers = vec![QuantizedMultiplier::new(1288490189, -6)];
+
+        let mut pool = Layer::new(LayerKind::MaxPool2d, 0);
+        pool.shape = [0, 0, 2, 2];
+
+        let mut add = Layer::new(LayerKind::Add, 1);
+        add.second_input = 1;
+        add.zero_points = [-7, -7, 3];
+        add.multipliers = vec![
+            QuantizedMultiplier::new(1 << 30, 0),
+            QuantizedMultiplier::new(1 << 30, 0),
+            QuantizedMultiplier::new(1 << 30, -18),
+        ];
+        vec![conv, pool, add]
+    }
+
+    #[test]
+    fn model_reads_written_image() {
+        let layers = layers();
+        let image = write([3, 8, 6], &layers);
+        let model = Model::parse(&image).unwrap();
+        assert_eq!(model.input_shape, (3, 8, 6));
+        assert_eq!(model.input_order, Order3::HWC);
+
+        let parsed: Vec<_> = model.layers().collect();
+        assert_eq!(parsed.len(), layers.len());
+        for (desc, layer) in parsed.iter().zip(&layers) {
+            let [k, c, h, w] = layer.shape.map(|dim| dim as usize);
+            assert_eq!(desc.kind, layer.kind);
+            assert_eq!(desc.relu, layer.relu);
+            assert_eq!(desc.weight_order, layer.weight_order);
+            assert_eq!(desc.input, layer.input);
+            assert_eq!(desc.shape, (k, c, h, w));
+            assert_eq!(
+                [
+                    desc.padding.top,
+                    desc.padding.right,
+                    desc.padding.left,
+                    desc.padding.bottom
+                ],
+                layer.padding.map(u32::from)
+            );
+            assert_eq!(desc.padding.padding_value, layer.padding_value as i32);
+            assert_eq!([desc.stride.x, desc.stride.y], layer.stride.map(u32::from));
+            assert_eq!(desc.mac_clip, layer.mac_clip as u32);
+            assert_eq!(desc.pp_clip, layer.pp_clip as u32);
+            assert_eq!(desc.rounding, layer.rounding);
+            assert_eq!(desc.zero_points, layer.zero_points);
+            assert_eq!(desc.weights, &layer.weights[..]);
+            assert_eq!(
+                desc.bias.to_i32(),
+                (!layer.bias.is_empty()).then(|| layer.bias.clone())
+            );
+            assert_eq!(desc.multipliers(), layer.multipliers);
+        }
+        assert_eq!(parsed[2].second_input, 1);
+    }
+
+    #[test]
+    fn written_image_runs() {
+        let image = write([3, 8, 6], &layers());
+        let model = Model::parse(&image).unwrap();
+        let input = Tensor3::from_data_buffer(
+            3,
+            8,
+            6,
+            (0..3 * 8 * 6).map(|x| (x % 50) as i8 - 25).collect(),
+            Order3::HWC,
+        )
+        .unwrap();
+
+        let output = model
+            .run_with(&Dla::with_backend(Simulated::new()), input)
+            .unwrap();
+        assert_eq!(output.dimensions(), (4, 2, 3));
+    }
+}
@@ -0,0 +1,53 @@
+//! Converts quantized int8 TFLite models to the model images `dla_driver::model` runs
+//!
+//! Supports `CONV_2D`, `DEPTHWISE_CONV_2D`, `FULLY_CONNECTED`, `MAX_POOL_2D`, `AVERAGE_POOL_2D`
+//! and `ADD`. `RESHAPE` and quantization at the model boundaries are skipped, as the model is run
+//! with quantized inputs.
+//!
+//! # Usage
+//!
+//! ```sh
+//! cargo run --release -- model.tflite model.bin
+//! ```
+mod convert;
+mod flatbuffer;
+mod image;
+mod tflite;
+
+use std::process::ExitCode;
+
+fn run(input: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(input)?;
+    let model = tflite::Model::parse(&data)?;
+    let converted = convert::convert(&model)?;
+
+    let [channels, height, width] = converted.input;
+    eprintln!("input: {channels}x{height}x{width} (CHW)");
+    for (idx, layer) in converted.layers.iter().enumerate() {
+        let [k, c, h, w] = layer.shape;
+        eprintln!(
+            "{idx}: {:?} {k}x{c}x{h}x{w}, relu: {}, pp clip: {}",
+            layer.kind, layer.relu, layer.pp_clip
+        );
+    }
+
+    let image = image::write(converted.input, &converted.layers);
+    std::fs::write(output, &image)?;
+    eprintln!("wrote {} bytes to {output}", image.len());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, input, output] = &args[..] else {
+        eprintln!("usage: tflite-to-dla <model.tflite> <model.bin>");
+        return ExitCode::FAILURE;
+    };
+    match run(input, output) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
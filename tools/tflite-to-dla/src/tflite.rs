@@ -0,0 +1,201 @@
+//! Accessors for the parts of the TFLite schema the converter uses
+//!
+//! Field indices follow `tensorflow/lite/schema/schema.fbs`.
+use crate::flatbuffer::{Error, Result, Table};
+
+/// Builtin operators the converter knows about
+pub mod op {
+    pub const ADD: i32 = 0;
+    pub const AVERAGE_POOL_2D: i32 = 1;
+    pub const CONV_2D: i32 = 3;
+    pub const DEPTHWISE_CONV_2D: i32 = 4;
+    pub const DEQUANTIZE: i32 = 6;
+    pub const FULLY_CONNECTED: i32 = 9;
+    pub const MAX_POOL_2D: i32 = 17;
+    pub const RESHAPE: i32 = 22;
+    pub const QUANTIZE: i32 = 114;
+}
+
+/// Tensor element types
+pub mod tensor_type {
+    pub const INT32: i8 = 2;
+    pub const INT8: i8 = 9;
+}
+
+/// Fused activation functions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activation {
+    None,
+    Relu,
+    Relu6,
+}
+
+impl TryFrom<i8> for Activation {
+    type Error = Error;
+    fn try_from(value: i8) -> Result<Self> {
+        match value {
+            0 => Ok(Activation::None),
+            1 => Ok(Activation::Relu),
+            3 => Ok(Activation::Relu6),
+            _ => Err(Error(format!("unsupported fused activation {value}"))),
+        }
+    }
+}
+
+/// Padding schemes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingScheme {
+    Same,
+    Valid,
+}
+
+impl TryFrom<i8> for PaddingScheme {
+    type Error = Error;
+    fn try_from(value: i8) -> Result<Self> {
+        match value {
+            0 => Ok(PaddingScheme::Same),
+            1 => Ok(PaddingScheme::Valid),
+            _ => Err(Error(format!("unknown padding {value}"))),
+        }
+    }
+}
+
+pub struct Model<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> Model<'a> {
+    pub fn parse(buf: &'a [u8]) -> Result<Self> {
+        if buf.get(4..8) != Some(b"TFL3") {
+            return Err(Error("not a TFLite model".into()));
+        }
+        Ok(Model {
+            table: Table::root(buf)?,
+        })
+    }
+
+    /// Builtin code of each operator code, in the order operators refer to them
+    pub fn operator_codes(&self) -> Result<Vec<i32>> {
+        self.table
+            .tables(1)?
+            .iter()
+            .map(|code| {
+                // Codes above 127 are only in the newer 32-bit field
+                let deprecated = code.scalar::<i8>(0, 0)? as i32;
+                let builtin = code.scalar::<i32>(3, 0)?;
+                Ok(deprecated.max(builtin))
+            })
+            .collect()
+    }
+
+    /// Returns the main subgraph
+    pub fn subgraph(&self) -> Result<SubGraph<'a>> {
+        let subgraphs = self.table.tables(2)?;
+        match subgraphs.as_slice() {
+            [subgraph] => Ok(SubGraph { table: *subgraph }),
+            _ => Err(Error(format!(
+                "expected a single subgraph, found {}",
+                subgraphs.len()
+            ))),
+        }
+    }
+
+    /// Returns the contents of buffer `idx`
+    pub fn buffer(&self, idx: u32) -> Result<&'a [u8]> {
+        let buffers = self.table.tables(4)?;
+        let buffer = buffers
+            .get(idx as usize)
+            .ok_or_else(|| Error(format!("buffer {idx} does not exist")))?;
+        if buffer.scalar::<u64>(1, 0)? > 1 {
+            return Err(Error(
+                "buffers stored outside of the flatbuffer are not supported".into(),
+            ));
+        }
+        Ok(buffer.vector_bytes(0, 1)?.unwrap_or_default())
+    }
+}
+
+pub struct SubGraph<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> SubGraph<'a> {
+    pub fn tensors(&self) -> Result<Vec<Tensor<'a>>> {
+        Ok(self
+            .table
+            .tables(0)?
+            .into_iter()
+            .map(|table| Tensor { table })
+            .collect())
+    }
+
+    pub fn inputs(&self) -> Result<Vec<i32>> {
+        self.table.vector(1)
+    }
+
+    pub fn outputs(&self) -> Result<Vec<i32>> {
+        self.table.vector(2)
+    }
+
+    pub fn operators(&self) -> Result<Vec<Operator<'a>>> {
+        Ok(self
+            .table
+            .tables(3)?
+            .into_iter()
+            .map(|table| Operator { table })
+            .collect())
+    }
+}
+
+pub struct Tensor<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> Tensor<'a> {
+    pub fn shape(&self) -> Result<Vec<i32>> {
+        self.table.vector(0)
+    }
+
+    pub fn tensor_type(&self) -> Result<i8> {
+        self.table.scalar(1, 0)
+    }
+
+    pub fn buffer(&self) -> Result<u32> {
+        self.table.scalar(2, 0)
+    }
+
+    pub fn name(&self) -> Result<&'a str> {
+        Ok(self.table.string(3)?.unwrap_or_default())
+    }
+
+    /// Returns scales and zero points, one for each channel or a single one for the tensor
+    pub fn quantization(&self) -> Result<(Vec<f32>, Vec<i64>)> {
+        match self.table.table(4)? {
+            Some(quantization) => Ok((quantization.vector(2)?, quantization.vector(3)?)),
+            None => Ok((Vec::new(), Vec::new())),
+        }
+    }
+}
+
+pub struct Operator<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> Operator<'a> {
+    pub fn opcode_index(&self) -> Result<u32> {
+        self.table.scalar(0, 0)
+    }
+
+    pub fn inputs(&self) -> Result<Vec<i32>> {
+        self.table.vector(1)
+    }
+
+    pub fn outputs(&self) -> Result<Vec<i32>> {
+        self.table.vector(2)
+    }
+
+    /// Returns the builtin options table, e.g. `Conv2DOptions`
+    pub fn options(&self) -> Result<Option<Table<'a>>> {
+        self.table.table(4)
+    }
+}
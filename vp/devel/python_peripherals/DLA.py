@@ -10,6 +10,10 @@ MEMORY_BANK_ADDR = 0x70000000
 MEMORY_BANK_SIZE = 0x8000
 NO_MEMORY_BANKS = 16
 
+# Completion interrupt, raised until the handshake has been completed
+PLIC_NAME = "sysbus.plic"
+DLA_IRQ = 25
+
 # Register map
 DLA_ADDR = 0xFF700000
 REG_BASE_ADDR = 0x1000
@@ -533,7 +537,11 @@ class MemoryBank:
 class Dla:
     """Implements control flow and MMIO registers of DLA. This should be the top level component."""
 
-    def __init__(self):
+    def __init__(self, irq=None):
+        """Params:
+        irq -- Function driving the completion interrupt line, or None if it isn't connected
+        """
+        self.irq = irq
         self.mem = bytearray(MEM_SIZE)  # Memory initalizaed
         self.mac = DlaMac()
         # Initialize memory banks
@@ -923,18 +931,24 @@ class Dla:
         )
         return filter_amount, s_channels, width, height, column_wise
 
-    def get_input_data(self):
+    def get_input_data(self, width=None, height=None):
         # TODO: Only read as much data as is needed to fill input layer (C* W * H)
         """Get all input data from memory banks in CWH format
 
+        Params:
+        width -- Int Width of input, read from BUF_INPUT if not given
+        height -- Int Height of input, read from BUF_INPUT if not given
+
         Returns:
         channels -- Int Number of channels
         width -- Int Width of input
         Height -- Int Height of input
         data -- [[Int]] List of all the input values in CWH format
         """
-        width = self.get_register(BUF_INPUT, BUF_WIDTH_OFFSET, 9) + 1
-        height = self.get_register(BUF_INPUT, BUF_HEIGHT_OFFSET, 9) + 1
+        if width is None:
+            width = self.get_register(BUF_INPUT, BUF_WIDTH_OFFSET, 9) + 1
+        if height is None:
+            height = self.get_register(BUF_INPUT, BUF_HEIGHT_OFFSET, 9) + 1
         channels = self.get_register(BUF_INPUT, BUF_CHANNELS_OFFSET, 12) + 1
         bank_idx = self.get_register(BUF_DATA_BANK, BUF_DATA_BANK_B_OFFSET, 4)
         bank = self.banks[bank_idx]
@@ -1078,8 +1092,24 @@ class Dla:
                 self.set_register(HANDSHAKE, HANDSHAKE_ACTIVE_VALID_OFFSET, 1, 0)
                 self.set_register(STATUS_ADDR, PP_DONE_OFFSET, 1, 0)
 
+        if not self.is_done():
+            self.set_irq(False)
+
         # PROCESSJUMPTAG
 
+    def is_done(self):
+        """Whether any of the done status bits is still set"""
+        return (
+            self.get_register(STATUS_ADDR, BUF_DONE_OFFSET, 1)
+            or self.get_register(STATUS_ADDR, MAC_DONE_OFFSET, 1)
+            or self.get_register(STATUS_ADDR, PP_DONE_OFFSET, 1)
+        )
+
+    def set_irq(self, level):
+        """Drives the completion interrupt line, if connected"""
+        if self.irq is not None:
+            self.irq(level)
+
     def process(self):
         """Runs next tick of the DLA state"""
 
@@ -1087,73 +1117,86 @@ class Dla:
         self.handle_handshake()
 
         # Don't move if done hasn't been acknowledged VP only
-        if (
-            self.get_register(STATUS_ADDR, BUF_DONE_OFFSET, 1)
-            or self.get_register(STATUS_ADDR, MAC_DONE_OFFSET, 1)
-            or self.get_register(STATUS_ADDR, PP_DONE_OFFSET, 1)
-        ):
+        if self.is_done():
             self.print_register(STATUS_ADDR)
             print("Status not cleared")
             return
 
-        # Check if data is ready
-        if not self.get_register(
-            BUF_CTRL, READ_A_VALID_OFFSET, 1
-        ) or not self.get_register(BUF_CTRL, READ_B_VALID_OFFSET, 1):
-            return
-
-        # Load data from memory banks and reshape
-        input_ch, input_w, input_h, input_data = self.get_input_data()
-
-        (
-            kernel_amount,
-            s_channels,
-            kernel_w,
-            kernel_h,
-            kernel_data,
-        ) = self.get_weight_data()
-
-        # Convonlution
-        padding, dilation, stride = self.get_conv_params()
-
-        print("input:", input_ch, input_w, input_h)
-        print("kernel:", kernel_amount, s_channels, kernel_w, kernel_h)
-        print("padding:", padding)
-        print("dilation:", dilation)
-        print("stride:", stride)
-        print("CONV2D")
-
-        # Pack output according to clipping
-        output_bit_width = (
-            self.get_register(MAC_CTRL, MAC_CLIP_OFFSET, 5)
-            if self.get_register(MAC_CTRL, MAC_CLIP_OFFSET, 5) > 0
-            else 32
-        )
+        # With the MAC array disabled and PP_SELECT set, the post-processor reads its input
+        # straight from the data buffer
+        pp_select = self.get_register(PP_CTRL, PP_SELECT_OFFSET, 1)
+        mac_enabled = self.get_register(HANDSHAKE, HANDSHAKE_MAC_ENABLE_OFFSET, 1)
+        pp_only = pp_select and not mac_enabled
 
-        if self.get_register(HANDSHAKE, HANDSHAKE_MAC_ENABLE_OFFSET, 1):
-            # for i, r in enumerate(input_data):
-            #     print_matrix(input_data[i], "{} INPUT:".format(i))
+        # Check if data is ready, post-processing only needs input data
+        if not self.get_register(BUF_CTRL, READ_B_VALID_OFFSET, 1) or (
+            not pp_only and not self.get_register(BUF_CTRL, READ_A_VALID_OFFSET, 1)
+        ):
+            return
 
-            print_matrix(input_data[0], "{} INPUT0:".format(0))
-            print_matrix(kernel_data[0][0], "{} KERNEL0:".format(0))
-            print("Mac not enabled")
-            # TODO: This might be not correct, make sure S_CHANNELS work like this
-            padding_value = cast_long_to_signed_byte(
-                self.get_register(BUF_PAD, BUF_PAD_VALUE_OFFSET, 8)
+        if pp_only:
+            # Post-processor input is PP_INPUT sized planes of 8-bit values
+            pp_width = self.get_register(PP_INPUT, PP_INPUT_WIDTH_OFFSET, 9) + 1
+            pp_height = self.get_register(PP_INPUT, PP_INPUT_HEIGHT_OFFSET, 9) + 1
+            input_ch, input_w, input_h, input_data = self.get_input_data(
+                pp_width, pp_height
             )
-            res = self.mac.conv2d(
-                input_data,
+            print("PP input:", input_ch, input_w, input_h)
+            res = input_data
+            output_bit_width = 8
+        else:
+            # Load data from memory banks and reshape
+            input_ch, input_w, input_h, input_data = self.get_input_data()
+
+            (
+                kernel_amount,
+                s_channels,
+                kernel_w,
+                kernel_h,
                 kernel_data,
-                padding,
-                dilation,
-                stride,
-                padding_value=padding_value,
+            ) = self.get_weight_data()
+
+            # Convonlution
+            padding, dilation, stride = self.get_conv_params()
+
+            print("input:", input_ch, input_w, input_h)
+            print("kernel:", kernel_amount, s_channels, kernel_w, kernel_h)
+            print("padding:", padding)
+            print("dilation:", dilation)
+            print("stride:", stride)
+            print("CONV2D")
+
+            # Pack output according to clipping
+            output_bit_width = (
+                self.get_register(MAC_CTRL, MAC_CLIP_OFFSET, 5)
+                if self.get_register(MAC_CTRL, MAC_CLIP_OFFSET, 5) > 0
+                else 32
             )
 
-            # Clip results
-            res = dla.mac_clip(res)
-            # for i, r in enumerate(res):
-            #     print_matrix(res[i], "{} MAC:".format(i))
+            if self.get_register(HANDSHAKE, HANDSHAKE_MAC_ENABLE_OFFSET, 1):
+                # for i, r in enumerate(input_data):
+                #     print_matrix(input_data[i], "{} INPUT:".format(i))
+
+                print_matrix(input_data[0], "{} INPUT0:".format(0))
+                print_matrix(kernel_data[0][0], "{} KERNEL0:".format(0))
+                print("Mac not enabled")
+                # TODO: This might be not correct, make sure S_CHANNELS work like this
+                padding_value = cast_long_to_signed_byte(
+                    self.get_register(BUF_PAD, BUF_PAD_VALUE_OFFSET, 8)
+                )
+                res = self.mac.conv2d(
+                    input_data,
+                    kernel_data,
+                    padding,
+                    dilation,
+                    stride,
+                    padding_value=padding_value,
+                )
+
+                # Clip results
+                res = dla.mac_clip(res)
+                # for i, r in enumerate(res):
+                #     print_matrix(res[i], "{} MAC:".format(i))
 
         if self.get_register(HANDSHAKE, HANDSHAKE_BYPASS_ENABLE_OFFSET, 1):
             if self.get_register(HANDSHAKE, HANDSHAKE_BIAS_ENABLE_OFFSET, 1):
@@ -1205,6 +1248,7 @@ class Dla:
         # Set data not ready
         self.set_register(BUF_CTRL, READ_A_VALID_OFFSET, 1, 0)
         self.set_register(BUF_CTRL, READ_B_VALID_OFFSET, 1, 0)
+        self.set_irq(True)
 
 
 class DlaMac:
@@ -1511,6 +1555,26 @@ def read(request, dla):
     # print("Absolute: 0x%x  Reading request offset: %s at 0x%x, value 0x%x" % (request.absolute, str(request.type), request.offset, request.value))
 
 
+def connect_irq(peripheral):
+    """Returns a function driving DLA's interrupt line to the PLIC, or None outside Renode
+
+    Python peripherals have no GPIO outputs, so the PLIC input is driven directly.
+    """
+    try:
+        from Antmicro.Renode.Core import EmulationManager
+
+        emulation = EmulationManager.Instance.CurrentEmulation
+        found, machine = emulation.TryGetMachineForPeripheral(peripheral)
+        plic = machine[PLIC_NAME] if found else None
+    except Exception as e:
+        print("Could not connect %s interrupt: %s" % (NAME, e))
+        return None
+    if plic is None:
+        print("Could not connect %s interrupt: no machine" % NAME)
+        return None
+    return lambda level: plic.OnGPIO(DLA_IRQ, level)
+
+
 if __name__ == "__main__":
     print("Running as independent module")
 
@@ -1621,7 +1685,7 @@ else:
             sys.stdout = open(os.devnull, "w")
 
         # Initialized DLA
-        dla = Dla()
+        dla = Dla(connect_irq(self))
         print("%s initialized" % NAME)
         self.NoisyLog("%s initialized" % NAME)
     elif request.isRead: